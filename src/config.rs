@@ -0,0 +1,251 @@
+use std::{env, fmt, fs, net::SocketAddr, path::Path};
+
+use serde::{Deserialize, Deserializer};
+use url::Url;
+
+/// File read when `CONFIG_FILE` is not set. It is optional.
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(String, std::io::Error),
+    Parse(String, toml::de::Error),
+    Env(&'static str, String),
+    Missing(&'static str),
+    Invalid(&'static str, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "can not read {}: {}", path, e),
+            ConfigError::Parse(path, e) => write!(f, "can not parse {}: {}", path, e),
+            ConfigError::Env(name, value) => {
+                write!(
+                    f,
+                    "invalid value {:?} for environment variable {}",
+                    value, name
+                )
+            }
+            ConfigError::Missing(name) => write!(f, "{} is required", name),
+            ConfigError::Invalid(name, reason) => write!(f, "invalid {}: {}", name, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// An inclusive range of accepted values plus the value new chats start with.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Limit {
+    pub min: i64,
+    pub max: i64,
+    pub default: i64,
+}
+
+/// A [`Limit`] as written in the config file. Fields left out keep their built-in values.
+#[derive(Deserialize)]
+struct PartialLimit {
+    min: Option<i64>,
+    max: Option<i64>,
+    default: Option<i64>,
+}
+
+impl PartialLimit {
+    fn or(self, base: Limit) -> Limit {
+        Limit {
+            min: self.min.unwrap_or(base.min),
+            max: self.max.unwrap_or(base.max),
+            default: self.default.unwrap_or(base.default),
+        }
+    }
+}
+
+fn vote_count_limit<'de, D: Deserializer<'de>>(d: D) -> Result<Limit, D::Error> {
+    Ok(PartialLimit::deserialize(d)?.or(Config::default().vote_count))
+}
+
+fn poll_delete_delay_limit<'de, D: Deserializer<'de>>(d: D) -> Result<Limit, D::Error> {
+    Ok(PartialLimit::deserialize(d)?.or(Config::default().poll_delete_delay))
+}
+
+impl Limit {
+    pub fn contains(&self, value: i64) -> bool {
+        self.min <= value && value <= self.max
+    }
+
+    fn validate(&self, name: &'static str) -> Result<(), ConfigError> {
+        if self.min > self.max {
+            return Err(ConfigError::Invalid(
+                name,
+                format!("min ({}) is greater than max ({})", self.min, self.max),
+            ));
+        }
+
+        if !self.contains(self.default) {
+            return Err(ConfigError::Invalid(
+                name,
+                format!(
+                    "default ({}) is outside of {}..={}",
+                    self.default, self.min, self.max
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bot_token: String,
+    pub db_url: String,
+    pub db_pool_size: u32,
+    pub webhook_url: Option<Url>,
+    pub health_addr: Option<SocketAddr>,
+    pub default_locale: String,
+    #[serde(deserialize_with = "vote_count_limit")]
+    pub vote_count: Limit,
+    #[serde(deserialize_with = "poll_delete_delay_limit")]
+    pub poll_delete_delay: Limit,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bot_token: String::new(),
+            db_url: String::new(),
+            db_pool_size: 5,
+            webhook_url: None,
+            health_addr: None,
+            default_locale: "en".into(),
+            vote_count: Limit {
+                min: 1,
+                max: 10,
+                default: 5,
+            },
+            poll_delete_delay: Limit {
+                min: 5,
+                max: 60,
+                default: 5,
+            },
+        }
+    }
+}
+
+fn env_parse<T>(name: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T: std::str::FromStr,
+{
+    match env::var(name) {
+        Ok(v) => v.parse().map(Some).map_err(|_| ConfigError::Env(name, v)),
+        Err(_) => Ok(None),
+    }
+}
+
+impl Config {
+    /// Loads the TOML file named by `CONFIG_FILE` (or `config.toml`, if present), applies
+    /// environment variable overrides and validates the result.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match env::var("CONFIG_FILE") {
+            Ok(path) => Config::from_file(&path)?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Config::from_file(DEFAULT_CONFIG_FILE)?
+            }
+            Err(_) => Config::default(),
+        };
+
+        config.override_from_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|e| ConfigError::Read(path.into(), e))?;
+
+        toml::from_str(&content).map_err(|e| ConfigError::Parse(path.into(), e))
+    }
+
+    fn override_from_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(v) = env_parse("BOT_TOKEN")? {
+            self.bot_token = v;
+        }
+        if let Some(v) = env_parse("DB_URL")? {
+            self.db_url = v;
+        }
+        if let Some(v) = env_parse("DB_POOL_SIZE")? {
+            self.db_pool_size = v;
+        }
+        if let Some(v) = env_parse("WEBHOOK_URL")? {
+            self.webhook_url = Some(v);
+        }
+        if let Some(v) = env_parse("HEALTH_ADDR")? {
+            self.health_addr = Some(v);
+        }
+        if let Some(v) = env_parse("DEFAULT_LOCALE")? {
+            self.default_locale = v;
+        }
+        if let Some(v) = env_parse("VOTE_COUNT_MIN")? {
+            self.vote_count.min = v;
+        }
+        if let Some(v) = env_parse("VOTE_COUNT_MAX")? {
+            self.vote_count.max = v;
+        }
+        if let Some(v) = env_parse("VOTE_COUNT_DEFAULT")? {
+            self.vote_count.default = v;
+        }
+        if let Some(v) = env_parse("POLL_DELETE_DELAY_MIN")? {
+            self.poll_delete_delay.min = v;
+        }
+        if let Some(v) = env_parse("POLL_DELETE_DELAY_MAX")? {
+            self.poll_delete_delay.max = v;
+        }
+        if let Some(v) = env_parse("POLL_DELETE_DELAY_DEFAULT")? {
+            self.poll_delete_delay.default = v;
+        }
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.bot_token.is_empty() {
+            return Err(ConfigError::Missing("bot_token (BOT_TOKEN)"));
+        }
+
+        if self.db_url.is_empty() {
+            return Err(ConfigError::Missing("db_url (DB_URL)"));
+        }
+
+        if self.db_pool_size == 0 {
+            return Err(ConfigError::Invalid(
+                "db_pool_size",
+                "must be at least 1".into(),
+            ));
+        }
+
+        if self.default_locale.is_empty() {
+            return Err(ConfigError::Missing("default_locale (DEFAULT_LOCALE)"));
+        }
+
+        self.vote_count.validate("vote_count")?;
+        self.poll_delete_delay.validate("poll_delete_delay")?;
+
+        if self.vote_count.min < 1 {
+            return Err(ConfigError::Invalid(
+                "vote_count",
+                "min must be at least 1".into(),
+            ));
+        }
+
+        if self.poll_delete_delay.min < 0 {
+            return Err(ConfigError::Invalid(
+                "poll_delete_delay",
+                "min must not be negative".into(),
+            ));
+        }
+
+        Ok(())
+    }
+}
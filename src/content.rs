@@ -0,0 +1,60 @@
+//! What a message carries, in a form that can be stored and sent again.
+
+use teloxide::types::{ForwardedFrom, Message};
+
+use crate::types::ContentKind;
+
+/// The kind of content in `msg`, or `None` for messages such as polls, locations or service
+/// messages.
+pub fn kind(msg: &Message) -> Option<ContentKind> {
+    if msg.photo().is_some() {
+        Some(ContentKind::Photo)
+    } else if msg.video().is_some() {
+        Some(ContentKind::Video)
+    } else if msg.animation().is_some() {
+        Some(ContentKind::Animation)
+    } else if msg.document().is_some() {
+        Some(ContentKind::Document)
+    } else if msg.audio().is_some() {
+        Some(ContentKind::Audio)
+    } else if msg.voice().is_some() {
+        Some(ContentKind::Voice)
+    } else if msg.video_note().is_some() {
+        Some(ContentKind::VideoNote)
+    } else if msg.sticker().is_some() {
+        Some(ContentKind::Sticker)
+    } else if msg.text().is_some() {
+        Some(ContentKind::Text)
+    } else {
+        None
+    }
+}
+
+/// The id the bot can send the media in `msg` again with. Photos use their largest size.
+pub fn file_id(msg: &Message) -> Option<&str> {
+    msg.photo()
+        .and_then(|sizes| sizes.last())
+        .map(|p| p.file.id.as_str())
+        .or_else(|| msg.video().map(|v| v.file.id.as_str()))
+        .or_else(|| msg.animation().map(|a| a.file.id.as_str()))
+        .or_else(|| msg.document().map(|d| d.file.id.as_str()))
+        .or_else(|| msg.audio().map(|a| a.file.id.as_str()))
+        .or_else(|| msg.voice().map(|v| v.file.id.as_str()))
+        .or_else(|| msg.video_note().map(|v| v.file.id.as_str()))
+        .or_else(|| msg.sticker().map(|s| s.file.id.as_str()))
+}
+
+/// The text of `msg`, or the caption of its media.
+pub fn text(msg: &Message) -> Option<&str> {
+    msg.text().or_else(|| msg.caption())
+}
+
+/// Who `msg` was forwarded from: a chat title, a user's name, or the name of a sender who hides
+/// their account. `None` for messages written in the chat.
+pub fn forward_source(msg: &Message) -> Option<String> {
+    match msg.forward_from()? {
+        ForwardedFrom::User(user) => Some(user.full_name()),
+        ForwardedFrom::Chat(chat) => Some(chat.title().unwrap_or_default().into()),
+        ForwardedFrom::SenderName(name) => Some(name.clone()),
+    }
+}
@@ -1,10 +1,15 @@
 #![allow(dead_code)]
+use async_trait::async_trait;
 use sqlx::{
     any::{AnyPool, AnyPoolOptions},
     query, query_as, Error, FromRow,
 };
 
-use crate::types::VoteType;
+use crate::storage::{new_token, Storage, MAX_TRACKED_MESSAGES};
+use crate::types::{
+    AppealStatus, ContentKind, Escalation, FederationAction, FingerprintAction, PollOrigin, Scope,
+    Trigger, VoteType,
+};
 
 #[derive(Debug, Clone)]
 pub struct Database {
@@ -21,6 +26,40 @@ pub struct Poll {
     pub minimum_vote_count: i64,
     pub vote_count_yes: i64,
     pub vote_count_no: i64,
+    pub reason: Option<String>,
+    pub scope_messages: i64,
+    pub scope_minutes: i64,
+    /// When the `scope_minutes` window starts, counted back from the target's date.
+    pub scope_since: i64,
+    pub fingerprints: Option<String>,
+    pub origin: Option<String>,
+    /// The forum topic the poll was opened in.
+    pub thread_id: Option<i32>,
+}
+
+impl Poll {
+    pub fn scope(&self) -> Option<Scope> {
+        if self.scope_messages > 0 {
+            Some(Scope::Messages(self.scope_messages))
+        } else if self.scope_minutes > 0 {
+            Some(Scope::Minutes(self.scope_minutes))
+        } else {
+            None
+        }
+    }
+
+    pub fn origin(&self) -> Option<PollOrigin> {
+        self.origin.as_deref().and_then(PollOrigin::parse)
+    }
+
+    /// Fingerprints of the target message, see [`crate::fingerprint::fingerprints`].
+    pub fn fingerprints(&self) -> Vec<&str> {
+        self.fingerprints
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, FromRow)]
@@ -37,6 +76,136 @@ pub struct Chat {
     pub minimum_vote_count: i64,
     pub locale: String,
     pub poll_delete_delay: i64,
+    pub trigger_mention: bool,
+    pub trigger_command: bool,
+    pub initiator_votes: bool,
+    pub exclude_bots: bool,
+    pub voter_min_age: i64,
+    pub voter_min_messages: i64,
+    pub poll_rate_count: i64,
+    pub poll_rate_window: i64,
+    pub max_open_polls: i64,
+    pub strike_window: i64,
+    pub mute_after: i64,
+    pub ban_after: i64,
+    pub fingerprint_action: Option<String>,
+    pub share_fingerprints: bool,
+    pub federation_id: Option<i64>,
+    pub federation_action: String,
+    pub undo_window: i64,
+    pub appeal_chat_id: Option<i64>,
+}
+
+impl Chat {
+    pub fn federation_action(&self) -> FederationAction {
+        FederationAction::parse(&self.federation_action).unwrap_or(FederationAction::Warn)
+    }
+
+    pub fn fingerprint_action(&self) -> Option<FingerprintAction> {
+        self.fingerprint_action
+            .as_deref()
+            .and_then(FingerprintAction::parse)
+    }
+}
+
+/// A group of chats sharing a blocklist. Chats join with the `token`.
+#[derive(Debug, Clone, FromRow)]
+pub struct Federation {
+    pub id: i64,
+    pub name: String,
+    pub owner_chat_id: i64,
+    pub token: String,
+}
+
+/// A user on a federation's blocklist, and the chat that put them there.
+#[derive(Debug, Clone, FromRow)]
+pub struct FederationBan {
+    pub id: i64,
+    pub federation_id: i64,
+    pub user_id: i64,
+    pub chat_id: i64,
+    pub timestamp: i64,
+}
+
+/// Settings of a forum topic that differ from the rest of its chat. A `minimum_vote_count` of 0
+/// keeps the chat's own.
+#[derive(Debug, Clone, FromRow)]
+pub struct Topic {
+    pub id: i64,
+    pub chat_id: i64,
+    pub thread_id: i32,
+    pub minimum_vote_count: i64,
+    pub enabled: bool,
+}
+
+/// A copy of a poll's target message, kept so that admins can restore it for a while after it
+/// was deleted by vote. `expires` is unset while the poll is still open.
+#[derive(Debug, Clone, FromRow)]
+pub struct Archive {
+    pub id: i64,
+    pub chat_id: i64,
+    pub poll_id: i32,
+    pub user_id: i64,
+    pub kind: String,
+    pub file_id: Option<String>,
+    pub text: Option<String>,
+    pub expires: Option<i64>,
+    /// The forum topic the target was posted in, where it is restored.
+    pub thread_id: Option<i32>,
+}
+
+impl Archive {
+    pub fn kind(&self) -> ContentKind {
+        ContentKind::parse(&self.kind).unwrap_or(ContentKind::Text)
+    }
+}
+
+/// A deleted message's author asking the admins to reconsider. `poll_id` is the result
+/// message of the poll that deleted it.
+#[derive(Debug, Clone, FromRow)]
+pub struct Appeal {
+    pub id: i64,
+    pub chat_id: i64,
+    pub poll_id: i32,
+    pub user_id: i64,
+    pub statement: Option<String>,
+    pub status: String,
+    pub timestamp: i64,
+}
+
+impl Appeal {
+    pub fn status(&self) -> AppealStatus {
+        AppealStatus::parse(&self.status).unwrap_or(AppealStatus::Open)
+    }
+}
+
+/// A `/filter` pattern. Messages matching it get a poll, or are deleted right away if it is
+/// strict.
+#[derive(Debug, Clone, FromRow)]
+pub struct Pattern {
+    pub id: i64,
+    pub chat_id: i64,
+    pub pattern: String,
+    pub strict: bool,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct RecentMessage {
+    pub id: i64,
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub message_id: i32,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct Member {
+    pub id: i64,
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub first_seen: i64,
+    pub message_count: i64,
+    pub username: Option<String>,
 }
 
 #[derive(Debug, Clone, FromRow)]
@@ -56,7 +225,14 @@ message_id INTEGER NOT NULL,
 message_user_id INTEGER NOT NULL,
 minimum_vote_count INTEGER NOT NULL,
 vote_count_yes INTEGER DEFAULT 0,
-vote_count_no INTEGER DEFAULT 0
+vote_count_no INTEGER DEFAULT 0,
+reason VARCHAR,
+scope_messages INTEGER DEFAULT 0,
+scope_minutes INTEGER DEFAULT 0,
+scope_since INTEGER DEFAULT 0,
+fingerprints VARCHAR,
+origin VARCHAR,
+thread_id INTEGER
 );
 
 CREATE TABLE IF NOT EXISTS voters (
@@ -70,7 +246,131 @@ id INTEGER PRIMARY KEY,
 chat_id INTEGER NOT NULL,
 minimum_vote_count INTEGER DEFAULT 5,
 locale VARCHAR DEFAULT 'en',
-poll_delete_delay INTEGER DEFAULT 5
+poll_delete_delay INTEGER DEFAULT 5,
+trigger_mention BOOLEAN DEFAULT TRUE,
+trigger_command BOOLEAN DEFAULT TRUE,
+initiator_votes BOOLEAN DEFAULT FALSE,
+exclude_bots BOOLEAN DEFAULT FALSE,
+voter_min_age INTEGER DEFAULT 0,
+voter_min_messages INTEGER DEFAULT 0,
+poll_rate_count INTEGER DEFAULT 0,
+poll_rate_window INTEGER DEFAULT 0,
+max_open_polls INTEGER DEFAULT 0,
+strike_window INTEGER DEFAULT 604800,
+mute_after INTEGER DEFAULT 0,
+ban_after INTEGER DEFAULT 0,
+fingerprint_action VARCHAR,
+share_fingerprints BOOLEAN DEFAULT FALSE,
+federation_id INTEGER,
+federation_action VARCHAR DEFAULT 'warn',
+undo_window INTEGER DEFAULT 0,
+appeal_chat_id INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS appeals (
+id INTEGER PRIMARY KEY,
+chat_id INTEGER NOT NULL,
+poll_id INTEGER NOT NULL,
+user_id INTEGER NOT NULL,
+statement VARCHAR,
+status VARCHAR NOT NULL,
+timestamp INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS archives (
+id INTEGER PRIMARY KEY,
+chat_id INTEGER NOT NULL,
+poll_id INTEGER NOT NULL,
+user_id INTEGER NOT NULL,
+kind VARCHAR NOT NULL,
+file_id VARCHAR,
+text VARCHAR,
+expires INTEGER,
+thread_id INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS federations (
+id INTEGER PRIMARY KEY,
+name VARCHAR NOT NULL,
+owner_chat_id INTEGER NOT NULL,
+token VARCHAR NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS federation_bans (
+id INTEGER PRIMARY KEY,
+federation_id INTEGER NOT NULL,
+user_id INTEGER NOT NULL,
+chat_id INTEGER NOT NULL,
+timestamp INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS poll_starts (
+id INTEGER PRIMARY KEY,
+chat_id INTEGER NOT NULL,
+user_id INTEGER NOT NULL,
+timestamp INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS members (
+id INTEGER PRIMARY KEY,
+chat_id INTEGER NOT NULL,
+user_id INTEGER NOT NULL,
+first_seen INTEGER NOT NULL,
+message_count INTEGER DEFAULT 0,
+username VARCHAR
+);
+
+CREATE TABLE IF NOT EXISTS strikes (
+id INTEGER PRIMARY KEY,
+chat_id INTEGER NOT NULL,
+user_id INTEGER NOT NULL,
+timestamp INTEGER NOT NULL,
+poll_id INTEGER,
+escalation VARCHAR
+);
+
+CREATE TABLE IF NOT EXISTS recent_messages (
+id INTEGER PRIMARY KEY,
+chat_id INTEGER NOT NULL,
+user_id INTEGER NOT NULL,
+message_id INTEGER NOT NULL,
+timestamp INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS patterns (
+id INTEGER PRIMARY KEY,
+chat_id INTEGER NOT NULL,
+pattern VARCHAR NOT NULL,
+strict BOOLEAN DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS fingerprints (
+id INTEGER PRIMARY KEY,
+chat_id INTEGER NOT NULL,
+value VARCHAR NOT NULL,
+timestamp INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS reason_vote_counts (
+id INTEGER PRIMARY KEY,
+chat_id INTEGER NOT NULL,
+reason VARCHAR NOT NULL,
+minimum_vote_count INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS type_vote_counts (
+id INTEGER PRIMARY KEY,
+chat_id INTEGER NOT NULL,
+kind VARCHAR NOT NULL,
+minimum_vote_count INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS topics (
+id INTEGER PRIMARY KEY,
+chat_id INTEGER NOT NULL,
+thread_id INTEGER NOT NULL,
+minimum_vote_count INTEGER DEFAULT 0,
+enabled BOOLEAN DEFAULT TRUE
 );
 
 CREATE TABLE IF NOT EXISTS scheduled_to_delete (
@@ -81,13 +381,82 @@ timestamp INTEGER NOT NULL
 );
 ";
 
+/// Columns and indexes added after a table was first released. `CREATE TABLE IF NOT EXISTS`
+/// leaves existing tables alone, so these are applied one by one and "duplicate column" errors
+/// are ignored.
+static SCHEMA_UPGRADE: &[&str] = &[
+    "ALTER TABLE chats ADD COLUMN trigger_mention BOOLEAN DEFAULT TRUE",
+    "ALTER TABLE chats ADD COLUMN trigger_command BOOLEAN DEFAULT TRUE",
+    "ALTER TABLE polls ADD COLUMN reason VARCHAR",
+    "ALTER TABLE chats ADD COLUMN initiator_votes BOOLEAN DEFAULT FALSE",
+    "ALTER TABLE chats ADD COLUMN exclude_bots BOOLEAN DEFAULT FALSE",
+    "ALTER TABLE chats ADD COLUMN voter_min_age INTEGER DEFAULT 0",
+    "ALTER TABLE chats ADD COLUMN voter_min_messages INTEGER DEFAULT 0",
+    "ALTER TABLE chats ADD COLUMN poll_rate_count INTEGER DEFAULT 0",
+    "ALTER TABLE chats ADD COLUMN poll_rate_window INTEGER DEFAULT 0",
+    "ALTER TABLE chats ADD COLUMN max_open_polls INTEGER DEFAULT 0",
+    "ALTER TABLE polls ADD COLUMN scope_messages INTEGER DEFAULT 0",
+    "ALTER TABLE polls ADD COLUMN scope_minutes INTEGER DEFAULT 0",
+    "CREATE INDEX IF NOT EXISTS recent_messages_chat_user ON recent_messages (chat_id, user_id)",
+    "ALTER TABLE chats ADD COLUMN strike_window INTEGER DEFAULT 604800",
+    "ALTER TABLE chats ADD COLUMN mute_after INTEGER DEFAULT 0",
+    "ALTER TABLE chats ADD COLUMN ban_after INTEGER DEFAULT 0",
+    "ALTER TABLE members ADD COLUMN username VARCHAR",
+    "CREATE INDEX IF NOT EXISTS strikes_chat_user ON strikes (chat_id, user_id)",
+    "ALTER TABLE polls ADD COLUMN fingerprints VARCHAR",
+    "ALTER TABLE chats ADD COLUMN fingerprint_action VARCHAR",
+    "ALTER TABLE chats ADD COLUMN share_fingerprints BOOLEAN DEFAULT FALSE",
+    "CREATE INDEX IF NOT EXISTS fingerprints_value ON fingerprints (value)",
+    "ALTER TABLE polls ADD COLUMN origin VARCHAR",
+    "ALTER TABLE chats ADD COLUMN federation_id INTEGER",
+    "ALTER TABLE chats ADD COLUMN federation_action VARCHAR DEFAULT 'warn'",
+    "CREATE UNIQUE INDEX IF NOT EXISTS federations_token ON federations (token)",
+    "CREATE INDEX IF NOT EXISTS federation_bans_user ON federation_bans (federation_id, user_id)",
+    "ALTER TABLE chats ADD COLUMN undo_window INTEGER DEFAULT 0",
+    "CREATE INDEX IF NOT EXISTS archives_poll ON archives (chat_id, poll_id)",
+    "ALTER TABLE chats ADD COLUMN appeal_chat_id INTEGER",
+    "CREATE INDEX IF NOT EXISTS appeals_poll ON appeals (chat_id, poll_id)",
+    "CREATE INDEX IF NOT EXISTS appeals_user ON appeals (user_id, status)",
+    "ALTER TABLE polls ADD COLUMN scope_since INTEGER DEFAULT 0",
+    "CREATE INDEX IF NOT EXISTS polls_chat_user ON polls (chat_id, message_user_id)",
+    "CREATE INDEX IF NOT EXISTS members_chat_user ON members (chat_id, user_id)",
+    "ALTER TABLE strikes ADD COLUMN poll_id INTEGER",
+    "ALTER TABLE strikes ADD COLUMN escalation VARCHAR",
+    "CREATE INDEX IF NOT EXISTS strikes_poll ON strikes (chat_id, poll_id)",
+    "ALTER TABLE polls ADD COLUMN thread_id INTEGER",
+    "ALTER TABLE archives ADD COLUMN thread_id INTEGER",
+    "CREATE INDEX IF NOT EXISTS topics_chat_thread ON topics (chat_id, thread_id)",
+];
+
+/// Run after `SCHEMA_UPGRADE`. Older versions could open several polls about one message, so the
+/// duplicates are dropped before the unique index is created. These must succeed.
+static SCHEMA_CONSTRAINTS: &[&str] = &[
+    "DELETE FROM polls WHERE id NOT IN (SELECT MIN(id) FROM polls GROUP BY chat_id, message_id)",
+    "DELETE FROM voters WHERE poll_id NOT IN (SELECT id FROM polls)",
+    "CREATE UNIQUE INDEX IF NOT EXISTS polls_chat_message ON polls (chat_id, message_id)",
+];
+
+fn trigger_column(trigger: Trigger) -> &'static str {
+    match trigger {
+        Trigger::Mention => "trigger_mention",
+        Trigger::Command => "trigger_command",
+    }
+}
+
+fn escalation_column(escalation: Escalation) -> &'static str {
+    match escalation {
+        Escalation::Mute => "mute_after",
+        Escalation::Ban => "ban_after",
+    }
+}
+
 impl Database {
-    pub async fn new<S>(url: S) -> Self
+    pub async fn new<S>(url: S, max_connections: u32) -> Self
     where
         S: Into<String>,
     {
         let pool = AnyPoolOptions::new()
-            .max_connections(5)
+            .max_connections(max_connections)
             .connect(&url.into())
             .await
             .expect("Database connection failed");
@@ -104,33 +473,55 @@ impl Database {
             .execute(&self.pool)
             .await
             .expect("Database initialisation failed");
+
+        for q in SCHEMA_UPGRADE {
+            sqlx::query(q).execute(&self.pool).await.ok();
+        }
+
+        for q in SCHEMA_CONSTRAINTS {
+            sqlx::query(q)
+                .execute(&self.pool)
+                .await
+                .expect("Database upgrade failed");
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for Database {
+    async fn ping(&self) -> Result<(), Error> {
+        query("SELECT 1").execute(&self.pool).await?;
+
+        Ok(())
     }
 
-    pub async fn create_poll(
+    async fn create_poll(
         &self,
         chat_id: i64,
         poll_id: i32,
         message_id: i32,
         message_user_id: i64,
         minimum_vote_count: i64,
+        reason: Option<&str>,
     ) -> Result<(), Error> {
         query(
             "INSERT INTO polls \
-            (chat_id, poll_id, message_id, message_user_id, minimum_vote_count) \
-            VALUES ($1, $2, $3, $4, $5)",
+            (chat_id, poll_id, message_id, message_user_id, minimum_vote_count, reason) \
+            VALUES ($1, $2, $3, $4, $5, $6)",
         )
         .bind(chat_id)
         .bind(poll_id)
         .bind(message_id)
         .bind(message_user_id)
         .bind(minimum_vote_count)
+        .bind(reason)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
-    pub async fn get_poll(&self, chat_id: i64, poll_id: i32) -> Result<Option<Poll>, Error> {
+    async fn get_poll(&self, chat_id: i64, poll_id: i32) -> Result<Option<Poll>, Error> {
         query_as::<_, Poll>("SELECT * FROM polls WHERE chat_id = $1 AND poll_id = $2")
             .bind(chat_id)
             .bind(poll_id)
@@ -138,7 +529,77 @@ impl Database {
             .await
     }
 
-    pub async fn register_vote(&self, poll_id: i64, v: VoteType) -> Result<bool, Error> {
+    async fn get_poll_by_message(
+        &self,
+        chat_id: i64,
+        message_id: i32,
+    ) -> Result<Option<Poll>, Error> {
+        query_as::<_, Poll>("SELECT * FROM polls WHERE chat_id = $1 AND message_id = $2")
+            .bind(chat_id)
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn set_poll_scope(&self, poll_id: i64, scope: Scope, date: i64) -> Result<bool, Error> {
+        let (messages, minutes, since) = match scope {
+            Scope::Messages(count) => (count, 0, 0),
+            Scope::Minutes(minutes) => (0, minutes, date - minutes * 60),
+        };
+
+        let affected = query(
+            "UPDATE polls SET scope_messages = $1, scope_minutes = $2, scope_since = $3 \
+            WHERE id = $4",
+        )
+        .bind(messages)
+        .bind(minutes)
+        .bind(since)
+        .bind(poll_id)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_poll_fingerprints(
+        &self,
+        poll_id: i64,
+        fingerprints: &[String],
+    ) -> Result<bool, Error> {
+        let affected = query("UPDATE polls SET fingerprints = $1 WHERE id = $2")
+            .bind(fingerprints.join(" "))
+            .bind(poll_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_poll_origin(&self, poll_id: i64, origin: PollOrigin) -> Result<bool, Error> {
+        let affected = query("UPDATE polls SET origin = $1 WHERE id = $2")
+            .bind(origin.as_str())
+            .bind(poll_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_poll_thread(&self, poll_id: i64, thread_id: i32) -> Result<bool, Error> {
+        let affected = query("UPDATE polls SET thread_id = $1 WHERE id = $2")
+            .bind(thread_id)
+            .bind(poll_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn register_vote(&self, poll_id: i64, v: VoteType) -> Result<bool, Error> {
         match v {
             VoteType::Yes => {
                 let affected =
@@ -163,7 +624,7 @@ impl Database {
         }
     }
 
-    pub async fn remove_poll(&self, poll_id: i64) -> Result<bool, Error> {
+    async fn remove_poll(&self, poll_id: i64) -> Result<bool, Error> {
         let affected = query("DELETE FROM polls WHERE id = $1")
             .bind(poll_id)
             .execute(&self.pool)
@@ -173,7 +634,80 @@ impl Database {
         Ok(affected > 0)
     }
 
-    pub async fn create_voter(&self, poll_id: i64, user_id: i64) -> Result<(), Error> {
+    async fn get_polls(&self) -> Result<Vec<Poll>, Error> {
+        query_as::<_, Poll>("SELECT * FROM polls")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn count_polls(&self, chat_id: i64) -> Result<i64, Error> {
+        let (count,) = query_as::<_, (i64,)>("SELECT COUNT(*) FROM polls WHERE chat_id = $1")
+            .bind(chat_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn count_polls_about(&self, chat_id: i64, message_user_id: i64) -> Result<i64, Error> {
+        let (count,) = query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM polls WHERE chat_id = $1 AND message_user_id = $2",
+        )
+        .bind(chat_id)
+        .bind(message_user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn create_poll_start(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        query("INSERT INTO poll_starts (chat_id, user_id, timestamp) VALUES ($1, $2, $3)")
+            .bind(chat_id)
+            .bind(user_id)
+            .bind(timestamp)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn count_poll_starts(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        since: i64,
+    ) -> Result<i64, Error> {
+        let (count,) = query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM poll_starts \
+            WHERE chat_id = $1 AND user_id = $2 AND timestamp >= $3",
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn remove_poll_starts(&self, chat_id: i64, before: i64) -> Result<bool, Error> {
+        let affected = query("DELETE FROM poll_starts WHERE chat_id = $1 AND timestamp < $2")
+            .bind(chat_id)
+            .bind(before)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn create_voter(&self, poll_id: i64, user_id: i64) -> Result<(), Error> {
         query("INSERT INTO voters (poll_id, user_id) VALUES ($1, $2)")
             .bind(poll_id)
             .bind(user_id)
@@ -183,7 +717,7 @@ impl Database {
         Ok(())
     }
 
-    pub async fn get_voter(&self, poll_id: i64, user_id: i64) -> Result<Option<Voter>, Error> {
+    async fn get_voter(&self, poll_id: i64, user_id: i64) -> Result<Option<Voter>, Error> {
         query_as::<_, Voter>("SELECT * FROM voters WHERE poll_id = $1 AND user_id = $2")
             .bind(poll_id)
             .bind(user_id)
@@ -191,7 +725,7 @@ impl Database {
             .await
     }
 
-    pub async fn remove_voter(&self, voter_id: i64) -> Result<bool, Error> {
+    async fn remove_voter(&self, voter_id: i64) -> Result<bool, Error> {
         let affected = query("DELETE FROM voters WHERE id = $1")
             .bind(voter_id)
             .execute(&self.pool)
@@ -201,7 +735,7 @@ impl Database {
         Ok(affected > 0)
     }
 
-    pub async fn remove_voters(&self, poll_id: i64) -> Result<bool, Error> {
+    async fn remove_voters(&self, poll_id: i64) -> Result<bool, Error> {
         let affected = query("DELETE FROM voters WHERE poll_id = $1")
             .bind(poll_id)
             .execute(&self.pool)
@@ -211,9 +745,8 @@ impl Database {
         Ok(affected > 0)
     }
 
-    pub async fn create_chat(&self, chat_id: i64) -> Result<bool, Error> {
-        let affected = query("INSERT INTO chats (chat_id) VALUES ($1)")
-            .bind(chat_id)
+    async fn remove_orphan_voters(&self) -> Result<bool, Error> {
+        let affected = query("DELETE FROM voters WHERE poll_id NOT IN (SELECT id FROM polls)")
             .execute(&self.pool)
             .await?
             .rows_affected();
@@ -221,28 +754,59 @@ impl Database {
         Ok(affected > 0)
     }
 
-    pub async fn get_chat(&self, chat_id: i64) -> Result<Option<Chat>, Error> {
-        query_as::<_, Chat>("SELECT * FROM chats WHERE chat_id = $1")
-            .bind(chat_id)
-            .fetch_optional(&self.pool)
-            .await
-    }
-
-    pub async fn get_chat_votes(&self, chat_id: i64) -> Result<Option<i64>, Error> {
-        let x = query_as::<_, (i64,)>("SELECT minimum_vote_count FROM chats WHERE chat_id = $1")
-            .bind(chat_id)
-            .fetch_optional(&self.pool)
-            .await?;
+    async fn get_chat_ids(&self) -> Result<Vec<i64>, Error> {
+        let ids =
+            query_as::<_, (i64,)>("SELECT chat_id FROM chats UNION SELECT chat_id FROM polls")
+                .fetch_all(&self.pool)
+                .await?;
 
-        if let Some((y,)) = x {
-            Ok(Some(y))
-        } else {
-            Ok(None)
-        }
+        Ok(ids.into_iter().map(|(id,)| id).collect())
     }
 
-    pub async fn set_chat_votes(&self, chat_id: i64, votes_count: i64) -> Result<bool, Error> {
-        let affected = query("UPDATE chats SET minimum_vote_count = $1 WHERE chat_id = $2")
+    async fn create_chat(
+        &self,
+        chat_id: i64,
+        minimum_vote_count: i64,
+        locale: &str,
+        poll_delete_delay: i64,
+    ) -> Result<bool, Error> {
+        let affected = query(
+            "INSERT INTO chats (chat_id, minimum_vote_count, locale, poll_delete_delay) \
+            VALUES ($1, $2, $3, $4)",
+        )
+        .bind(chat_id)
+        .bind(minimum_vote_count)
+        .bind(locale)
+        .bind(poll_delete_delay)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn get_chat(&self, chat_id: i64) -> Result<Option<Chat>, Error> {
+        query_as::<_, Chat>("SELECT * FROM chats WHERE chat_id = $1")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn get_chat_votes(&self, chat_id: i64) -> Result<Option<i64>, Error> {
+        let x = query_as::<_, (i64,)>("SELECT minimum_vote_count FROM chats WHERE chat_id = $1")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        if let Some((y,)) = x {
+            Ok(Some(y))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn set_chat_votes(&self, chat_id: i64, votes_count: i64) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET minimum_vote_count = $1 WHERE chat_id = $2")
             .bind(votes_count)
             .bind(chat_id)
             .execute(&self.pool)
@@ -252,7 +816,7 @@ impl Database {
         Ok(affected > 0)
     }
 
-    pub async fn get_chat_locale(&self, chat_id: i64) -> Result<Option<String>, Error> {
+    async fn get_chat_locale(&self, chat_id: i64) -> Result<Option<String>, Error> {
         let x = query_as::<_, (String,)>("SELECT locale FROM chats WHERE chat_id = $1")
             .bind(chat_id)
             .fetch_optional(&self.pool)
@@ -265,7 +829,7 @@ impl Database {
         }
     }
 
-    pub async fn set_chat_locale(&self, chat_id: i64, locale: &str) -> Result<bool, Error> {
+    async fn set_chat_locale(&self, chat_id: i64, locale: &str) -> Result<bool, Error> {
         let affected = query("UPDATE chats SET locale = $1 WHERE chat_id = $2")
             .bind(locale)
             .bind(chat_id)
@@ -276,7 +840,7 @@ impl Database {
         Ok(affected > 0)
     }
 
-    pub async fn get_chat_poll_delete_delay(&self, chat_id: i64) -> Result<Option<i64>, Error> {
+    async fn get_chat_poll_delete_delay(&self, chat_id: i64) -> Result<Option<i64>, Error> {
         let x = query_as::<_, (i64,)>("SELECT poll_delete_delay FROM chats WHERE chat_id = $1")
             .bind(chat_id)
             .fetch_optional(&self.pool)
@@ -289,7 +853,7 @@ impl Database {
         }
     }
 
-    pub async fn set_chat_poll_delete_delay(
+    async fn set_chat_poll_delete_delay(
         &self,
         chat_id: i64,
         poll_delete_delay: i64,
@@ -304,9 +868,340 @@ impl Database {
         Ok(affected > 0)
     }
 
-    pub async fn remove_chat(&self, chat_id: i64) -> Result<bool, Error> {
-        let affected = query("DELETE FROM chats WHERE chat_id = $1")
+    async fn get_chat_trigger(
+        &self,
+        chat_id: i64,
+        trigger: Trigger,
+    ) -> Result<Option<bool>, Error> {
+        let x = query_as::<_, (bool,)>(&format!(
+            "SELECT {} FROM chats WHERE chat_id = $1",
+            trigger_column(trigger)
+        ))
+        .bind(chat_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(x.map(|(y,)| y))
+    }
+
+    async fn set_chat_trigger(
+        &self,
+        chat_id: i64,
+        trigger: Trigger,
+        enabled: bool,
+    ) -> Result<bool, Error> {
+        let affected = query(&format!(
+            "UPDATE chats SET {} = $1 WHERE chat_id = $2",
+            trigger_column(trigger)
+        ))
+        .bind(enabled)
+        .bind(chat_id)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn get_chat_initiator_votes(&self, chat_id: i64) -> Result<Option<bool>, Error> {
+        let x = query_as::<_, (bool,)>("SELECT initiator_votes FROM chats WHERE chat_id = $1")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(x.map(|(y,)| y))
+    }
+
+    async fn set_chat_initiator_votes(&self, chat_id: i64, enabled: bool) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET initiator_votes = $1 WHERE chat_id = $2")
+            .bind(enabled)
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_exclude_bots(&self, chat_id: i64, enabled: bool) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET exclude_bots = $1 WHERE chat_id = $2")
+            .bind(enabled)
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_voter_min_age(&self, chat_id: i64, seconds: i64) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET voter_min_age = $1 WHERE chat_id = $2")
+            .bind(seconds)
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_voter_min_messages(&self, chat_id: i64, count: i64) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET voter_min_messages = $1 WHERE chat_id = $2")
+            .bind(count)
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_poll_rate(
+        &self,
+        chat_id: i64,
+        count: i64,
+        window: i64,
+    ) -> Result<bool, Error> {
+        let affected = query(
+            "UPDATE chats SET poll_rate_count = $1, poll_rate_window = $2 WHERE chat_id = $3",
+        )
+        .bind(count)
+        .bind(window)
+        .bind(chat_id)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_max_open_polls(&self, chat_id: i64, count: i64) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET max_open_polls = $1 WHERE chat_id = $2")
+            .bind(count)
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_strike_window(&self, chat_id: i64, seconds: i64) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET strike_window = $1 WHERE chat_id = $2")
+            .bind(seconds)
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_escalation(
+        &self,
+        chat_id: i64,
+        escalation: Escalation,
+        count: i64,
+    ) -> Result<bool, Error> {
+        let affected = query(&format!(
+            "UPDATE chats SET {} = $1 WHERE chat_id = $2",
+            escalation_column(escalation)
+        ))
+        .bind(count)
+        .bind(chat_id)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_fingerprint_action(
+        &self,
+        chat_id: i64,
+        action: Option<FingerprintAction>,
+    ) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET fingerprint_action = $1 WHERE chat_id = $2")
+            .bind(action.map(|a| a.as_str()))
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_share_fingerprints(
+        &self,
+        chat_id: i64,
+        enabled: bool,
+    ) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET share_fingerprints = $1 WHERE chat_id = $2")
+            .bind(enabled)
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_undo_window(&self, chat_id: i64, seconds: i64) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET undo_window = $1 WHERE chat_id = $2")
+            .bind(seconds)
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_appeal_chat(
+        &self,
+        chat_id: i64,
+        appeal_chat_id: Option<i64>,
+    ) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET appeal_chat_id = $1 WHERE chat_id = $2")
+            .bind(appeal_chat_id)
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_federation(
+        &self,
+        chat_id: i64,
+        federation_id: Option<i64>,
+    ) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET federation_id = $1 WHERE chat_id = $2")
+            .bind(federation_id)
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_chat_federation_action(
+        &self,
+        chat_id: i64,
+        action: FederationAction,
+    ) -> Result<bool, Error> {
+        let affected = query("UPDATE chats SET federation_action = $1 WHERE chat_id = $2")
+            .bind(action.as_str())
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn track_member(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        username: Option<&str>,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        let affected = query(
+            "UPDATE members SET message_count = message_count + 1, username = $3 \
+            WHERE chat_id = $1 AND user_id = $2",
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(username)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        if affected == 0 {
+            query(
+                "INSERT INTO members (chat_id, user_id, first_seen, message_count, username) \
+                VALUES ($1, $2, $3, 1, $4)",
+            )
+            .bind(chat_id)
+            .bind(user_id)
+            .bind(timestamp)
+            .bind(username)
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn track_message(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        message_id: i32,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        query(
+            "INSERT INTO recent_messages (chat_id, user_id, message_id, timestamp) \
+            VALUES ($1, $2, $3, $4)",
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(message_id)
+        .bind(timestamp)
+        .execute(&self.pool)
+        .await?;
+
+        query(
+            "DELETE FROM recent_messages WHERE chat_id = $1 AND user_id = $2 AND id NOT IN \
+            (SELECT id FROM recent_messages WHERE chat_id = $1 AND user_id = $2 \
+            ORDER BY id DESC LIMIT $3)",
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(MAX_TRACKED_MESSAGES)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_recent_messages(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        since: i64,
+        limit: i64,
+    ) -> Result<Vec<RecentMessage>, Error> {
+        query_as::<_, RecentMessage>(
+            "SELECT * FROM recent_messages \
+            WHERE chat_id = $1 AND user_id = $2 AND timestamp >= $3 \
+            ORDER BY id DESC LIMIT $4",
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn remove_recent_message(&self, chat_id: i64, message_id: i32) -> Result<bool, Error> {
+        let affected = query("DELETE FROM recent_messages WHERE chat_id = $1 AND message_id = $2")
             .bind(chat_id)
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn remove_recent_messages_before(&self, timestamp: i64) -> Result<bool, Error> {
+        let affected = query("DELETE FROM recent_messages WHERE timestamp < $1")
+            .bind(timestamp)
             .execute(&self.pool)
             .await?
             .rows_affected();
@@ -314,7 +1209,750 @@ impl Database {
         Ok(affected > 0)
     }
 
-    pub async fn schedule_message_delete(
+    async fn get_member(&self, chat_id: i64, user_id: i64) -> Result<Option<Member>, Error> {
+        query_as::<_, Member>("SELECT * FROM members WHERE chat_id = $1 AND user_id = $2")
+            .bind(chat_id)
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn get_member_by_username(
+        &self,
+        chat_id: i64,
+        username: &str,
+    ) -> Result<Option<Member>, Error> {
+        query_as::<_, Member>(
+            "SELECT * FROM members WHERE chat_id = $1 AND LOWER(username) = LOWER($2)",
+        )
+        .bind(chat_id)
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn create_strike(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        poll_id: i32,
+        timestamp: i64,
+    ) -> Result<bool, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let (count,) = query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM strikes WHERE chat_id = $1 AND poll_id = $2",
+        )
+        .bind(chat_id)
+        .bind(poll_id)
+        .fetch_one(&mut tx)
+        .await?;
+
+        if count > 0 {
+            return Ok(false);
+        }
+
+        query("INSERT INTO strikes (chat_id, user_id, poll_id, timestamp) VALUES ($1, $2, $3, $4)")
+            .bind(chat_id)
+            .bind(user_id)
+            .bind(poll_id)
+            .bind(timestamp)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn set_strike_escalation(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+        escalation: Escalation,
+    ) -> Result<bool, Error> {
+        let affected =
+            query("UPDATE strikes SET escalation = $1 WHERE chat_id = $2 AND poll_id = $3")
+                .bind(escalation.as_str())
+                .bind(chat_id)
+                .bind(poll_id)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn count_strikes(&self, chat_id: i64, user_id: i64, since: i64) -> Result<i64, Error> {
+        let (count,) = query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM strikes \
+            WHERE chat_id = $1 AND user_id = $2 AND timestamp >= $3",
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn remove_strikes(&self, chat_id: i64, user_id: i64) -> Result<bool, Error> {
+        let affected = query("DELETE FROM strikes WHERE chat_id = $1 AND user_id = $2")
+            .bind(chat_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn remove_strikes_before(&self, chat_id: i64, before: i64) -> Result<bool, Error> {
+        let affected = query("DELETE FROM strikes WHERE chat_id = $1 AND timestamp < $2")
+            .bind(chat_id)
+            .bind(before)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn create_archive(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+        user_id: i64,
+        kind: ContentKind,
+        file_id: Option<&str>,
+        text: Option<&str>,
+    ) -> Result<(), Error> {
+        query(
+            "INSERT INTO archives (chat_id, poll_id, user_id, kind, file_id, text) \
+            VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(chat_id)
+        .bind(poll_id)
+        .bind(user_id)
+        .bind(kind.as_str())
+        .bind(file_id)
+        .bind(text)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_archive(&self, chat_id: i64, poll_id: i32) -> Result<Option<Archive>, Error> {
+        query_as::<_, Archive>("SELECT * FROM archives WHERE chat_id = $1 AND poll_id = $2")
+            .bind(chat_id)
+            .bind(poll_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn set_archive_expiry(&self, archive_id: i64, expires: i64) -> Result<bool, Error> {
+        let affected = query("UPDATE archives SET expires = $1 WHERE id = $2")
+            .bind(expires)
+            .bind(archive_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_archive_thread(&self, archive_id: i64, thread_id: i32) -> Result<bool, Error> {
+        let affected = query("UPDATE archives SET thread_id = $1 WHERE id = $2")
+            .bind(thread_id)
+            .bind(archive_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn remove_archive(&self, archive_id: i64) -> Result<bool, Error> {
+        let affected = query("DELETE FROM archives WHERE id = $1")
+            .bind(archive_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn remove_stale_archives(&self, timestamp: i64) -> Result<bool, Error> {
+        let affected = query(
+            "DELETE FROM archives WHERE expires < $1 OR (expires IS NULL AND NOT EXISTS \
+            (SELECT 1 FROM polls WHERE polls.chat_id = archives.chat_id \
+            AND polls.poll_id = archives.poll_id))",
+        )
+        .bind(timestamp)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn create_federation(
+        &self,
+        name: &str,
+        owner_chat_id: i64,
+        token: &str,
+    ) -> Result<(), Error> {
+        query("INSERT INTO federations (name, owner_chat_id, token) VALUES ($1, $2, $3)")
+            .bind(name)
+            .bind(owner_chat_id)
+            .bind(token)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_federation(&self, federation_id: i64) -> Result<Option<Federation>, Error> {
+        query_as::<_, Federation>("SELECT * FROM federations WHERE id = $1")
+            .bind(federation_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn get_federation_by_token(&self, token: &str) -> Result<Option<Federation>, Error> {
+        query_as::<_, Federation>("SELECT * FROM federations WHERE token = $1")
+            .bind(token)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn set_federation_token(&self, federation_id: i64, token: &str) -> Result<bool, Error> {
+        let affected = query("UPDATE federations SET token = $1 WHERE id = $2")
+            .bind(token)
+            .bind(federation_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn get_federation_chats(&self, federation_id: i64) -> Result<Vec<Chat>, Error> {
+        query_as::<_, Chat>("SELECT * FROM chats WHERE federation_id = $1")
+            .bind(federation_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn remove_empty_federations(&self) -> Result<bool, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        query(
+            "DELETE FROM federation_bans WHERE federation_id NOT IN \
+            (SELECT federation_id FROM chats WHERE federation_id IS NOT NULL)",
+        )
+        .execute(&mut tx)
+        .await?;
+
+        let affected = query(
+            "DELETE FROM federations WHERE id NOT IN \
+            (SELECT federation_id FROM chats WHERE federation_id IS NOT NULL)",
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+
+        Ok(affected > 0)
+    }
+
+    async fn create_federation_ban(
+        &self,
+        federation_id: i64,
+        user_id: i64,
+        chat_id: i64,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        let affected = query(
+            "UPDATE federation_bans SET chat_id = $1, timestamp = $2 \
+            WHERE federation_id = $3 AND user_id = $4",
+        )
+        .bind(chat_id)
+        .bind(timestamp)
+        .bind(federation_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        if affected == 0 {
+            query(
+                "INSERT INTO federation_bans (federation_id, user_id, chat_id, timestamp) \
+                VALUES ($1, $2, $3, $4)",
+            )
+            .bind(federation_id)
+            .bind(user_id)
+            .bind(chat_id)
+            .bind(timestamp)
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_federation_ban(
+        &self,
+        federation_id: i64,
+        user_id: i64,
+    ) -> Result<Option<FederationBan>, Error> {
+        query_as::<_, FederationBan>(
+            "SELECT * FROM federation_bans WHERE federation_id = $1 AND user_id = $2",
+        )
+        .bind(federation_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn remove_federation_ban(&self, federation_id: i64, user_id: i64) -> Result<bool, Error> {
+        let affected =
+            query("DELETE FROM federation_bans WHERE federation_id = $1 AND user_id = $2")
+                .bind(federation_id)
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn remove_poll_strike(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+    ) -> Result<Option<Escalation>, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let escalation = query_as::<_, (Option<String>,)>(
+            "SELECT escalation FROM strikes WHERE chat_id = $1 AND poll_id = $2",
+        )
+        .bind(chat_id)
+        .bind(poll_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .and_then(|(e,)| e)
+        .and_then(|e| Escalation::parse(&e));
+
+        query("DELETE FROM strikes WHERE chat_id = $1 AND poll_id = $2")
+            .bind(chat_id)
+            .bind(poll_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(escalation)
+    }
+
+    async fn create_appeal(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+        user_id: i64,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        query(
+            "INSERT INTO appeals (chat_id, poll_id, user_id, status, timestamp) \
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(chat_id)
+        .bind(poll_id)
+        .bind(user_id)
+        .bind(AppealStatus::Open.as_str())
+        .bind(timestamp)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_appeal(&self, chat_id: i64, poll_id: i32) -> Result<Option<Appeal>, Error> {
+        query_as::<_, Appeal>("SELECT * FROM appeals WHERE chat_id = $1 AND poll_id = $2")
+            .bind(chat_id)
+            .bind(poll_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn get_user_appeal(
+        &self,
+        user_id: i64,
+        status: AppealStatus,
+    ) -> Result<Option<Appeal>, Error> {
+        query_as::<_, Appeal>(
+            "SELECT * FROM appeals WHERE user_id = $1 AND status = $2 ORDER BY id DESC",
+        )
+        .bind(user_id)
+        .bind(status.as_str())
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn set_appeal_status(&self, appeal_id: i64, status: AppealStatus) -> Result<bool, Error> {
+        let affected = query("UPDATE appeals SET status = $1 WHERE id = $2")
+            .bind(status.as_str())
+            .bind(appeal_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn decide_appeal(&self, appeal_id: i64, status: AppealStatus) -> Result<bool, Error> {
+        let affected = query("UPDATE appeals SET status = $1 WHERE id = $2 AND status = $3")
+            .bind(status.as_str())
+            .bind(appeal_id)
+            .bind(AppealStatus::Pending.as_str())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_appeal_statement(&self, appeal_id: i64, statement: &str) -> Result<bool, Error> {
+        let affected = query("UPDATE appeals SET statement = $1, status = $2 WHERE id = $3")
+            .bind(statement)
+            .bind(AppealStatus::Pending.as_str())
+            .bind(appeal_id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn remove_appeals_before(&self, timestamp: i64) -> Result<bool, Error> {
+        let affected = query("DELETE FROM appeals WHERE timestamp < $1")
+            .bind(timestamp)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn create_pattern(&self, chat_id: i64, pattern: &str, strict: bool) -> Result<(), Error> {
+        let affected = query("UPDATE patterns SET strict = $1 WHERE chat_id = $2 AND pattern = $3")
+            .bind(strict)
+            .bind(chat_id)
+            .bind(pattern)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        if affected == 0 {
+            query("INSERT INTO patterns (chat_id, pattern, strict) VALUES ($1, $2, $3)")
+                .bind(chat_id)
+                .bind(pattern)
+                .bind(strict)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_patterns(&self, chat_id: i64) -> Result<Vec<Pattern>, Error> {
+        query_as::<_, Pattern>("SELECT * FROM patterns WHERE chat_id = $1 ORDER BY id")
+            .bind(chat_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn remove_pattern(&self, chat_id: i64, pattern: &str) -> Result<bool, Error> {
+        let affected = query("DELETE FROM patterns WHERE chat_id = $1 AND pattern = $2")
+            .bind(chat_id)
+            .bind(pattern)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn create_fingerprint(
+        &self,
+        chat_id: i64,
+        value: &str,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        let affected =
+            query("UPDATE fingerprints SET timestamp = $1 WHERE chat_id = $2 AND value = $3")
+                .bind(timestamp)
+                .bind(chat_id)
+                .bind(value)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+        if affected == 0 {
+            query("INSERT INTO fingerprints (chat_id, value, timestamp) VALUES ($1, $2, $3)")
+                .bind(chat_id)
+                .bind(value)
+                .bind(timestamp)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn has_fingerprint(&self, chat_id: i64, value: &str) -> Result<bool, Error> {
+        let (count,) = query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM fingerprints WHERE value = $1 AND (chat_id = $2 OR (\
+            EXISTS (SELECT 1 FROM chats WHERE chat_id = $2 AND share_fingerprints) \
+            AND chat_id IN (SELECT chat_id FROM chats WHERE share_fingerprints)))",
+        )
+        .bind(value)
+        .bind(chat_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count > 0)
+    }
+
+    async fn remove_fingerprints_before(&self, timestamp: i64) -> Result<bool, Error> {
+        let affected = query("DELETE FROM fingerprints WHERE timestamp < $1")
+            .bind(timestamp)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn get_reason_votes(&self, chat_id: i64, reason: &str) -> Result<Option<i64>, Error> {
+        let x = query_as::<_, (i64,)>(
+            "SELECT minimum_vote_count FROM reason_vote_counts WHERE chat_id = $1 AND reason = $2",
+        )
+        .bind(chat_id)
+        .bind(reason)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(x.map(|(y,)| y))
+    }
+
+    async fn set_reason_votes(
+        &self,
+        chat_id: i64,
+        reason: &str,
+        votes_count: i64,
+    ) -> Result<bool, Error> {
+        self.remove_reason_votes(chat_id, reason).await?;
+
+        let affected = query(
+            "INSERT INTO reason_vote_counts (chat_id, reason, minimum_vote_count) \
+            VALUES ($1, $2, $3)",
+        )
+        .bind(chat_id)
+        .bind(reason)
+        .bind(votes_count)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn remove_reason_votes(&self, chat_id: i64, reason: &str) -> Result<bool, Error> {
+        let affected = query("DELETE FROM reason_vote_counts WHERE chat_id = $1 AND reason = $2")
+            .bind(chat_id)
+            .bind(reason)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn get_type_votes(&self, chat_id: i64, kind: ContentKind) -> Result<Option<i64>, Error> {
+        let x = query_as::<_, (i64,)>(
+            "SELECT minimum_vote_count FROM type_vote_counts WHERE chat_id = $1 AND kind = $2",
+        )
+        .bind(chat_id)
+        .bind(kind.as_str())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(x.map(|(y,)| y))
+    }
+
+    async fn set_type_votes(
+        &self,
+        chat_id: i64,
+        kind: ContentKind,
+        votes_count: i64,
+    ) -> Result<bool, Error> {
+        self.remove_type_votes(chat_id, kind).await?;
+
+        let affected = query(
+            "INSERT INTO type_vote_counts (chat_id, kind, minimum_vote_count) \
+            VALUES ($1, $2, $3)",
+        )
+        .bind(chat_id)
+        .bind(kind.as_str())
+        .bind(votes_count)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn remove_type_votes(&self, chat_id: i64, kind: ContentKind) -> Result<bool, Error> {
+        let affected = query("DELETE FROM type_vote_counts WHERE chat_id = $1 AND kind = $2")
+            .bind(chat_id)
+            .bind(kind.as_str())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn get_topic(&self, chat_id: i64, thread_id: i32) -> Result<Option<Topic>, Error> {
+        query_as::<_, Topic>("SELECT * FROM topics WHERE chat_id = $1 AND thread_id = $2")
+            .bind(chat_id)
+            .bind(thread_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn set_topic_votes(
+        &self,
+        chat_id: i64,
+        thread_id: i32,
+        votes_count: i64,
+    ) -> Result<bool, Error> {
+        let affected = query(
+            "UPDATE topics SET minimum_vote_count = $1 WHERE chat_id = $2 AND thread_id = $3",
+        )
+        .bind(votes_count)
+        .bind(chat_id)
+        .bind(thread_id)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        if affected > 0 {
+            return Ok(true);
+        }
+
+        let affected = query(
+            "INSERT INTO topics (chat_id, thread_id, minimum_vote_count) VALUES ($1, $2, $3)",
+        )
+        .bind(chat_id)
+        .bind(thread_id)
+        .bind(votes_count)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn set_topic_enabled(
+        &self,
+        chat_id: i64,
+        thread_id: i32,
+        enabled: bool,
+    ) -> Result<bool, Error> {
+        let affected =
+            query("UPDATE topics SET enabled = $1 WHERE chat_id = $2 AND thread_id = $3")
+                .bind(enabled)
+                .bind(chat_id)
+                .bind(thread_id)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+        if affected > 0 {
+            return Ok(true);
+        }
+
+        let affected =
+            query("INSERT INTO topics (chat_id, thread_id, enabled) VALUES ($1, $2, $3)")
+                .bind(chat_id)
+                .bind(thread_id)
+                .bind(enabled)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+        Ok(affected > 0)
+    }
+
+    async fn remove_chat(&self, chat_id: i64) -> Result<bool, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        query("DELETE FROM voters WHERE poll_id IN (SELECT id FROM polls WHERE chat_id = $1)")
+            .bind(chat_id)
+            .execute(&mut tx)
+            .await?;
+
+        for table in [
+            "polls",
+            "members",
+            "recent_messages",
+            "poll_starts",
+            "strikes",
+            "fingerprints",
+            "patterns",
+            "archives",
+            "appeals",
+            "reason_vote_counts",
+            "type_vote_counts",
+            "topics",
+            "scheduled_to_delete",
+        ] {
+            query(&format!("DELETE FROM {} WHERE chat_id = $1", table))
+                .bind(chat_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        let owned = query_as::<_, (i64,)>("SELECT id FROM federations WHERE owner_chat_id = $1")
+            .bind(chat_id)
+            .fetch_all(&mut tx)
+            .await?;
+
+        for (federation_id,) in owned {
+            query("UPDATE federations SET token = $1 WHERE id = $2")
+                .bind(new_token())
+                .bind(federation_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        let affected = query("DELETE FROM chats WHERE chat_id = $1")
+            .bind(chat_id)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+
+        Ok(affected > 0)
+    }
+
+    async fn schedule_message_delete(
         &self,
         chat_id: i64,
         message_id: i64,
@@ -333,7 +1971,7 @@ impl Database {
         Ok(affected > 0)
     }
 
-    pub async fn get_pending_messages_to_delete(
+    async fn get_pending_messages_to_delete(
         &self,
         timestamp: i64,
     ) -> Result<Vec<MessageToDelete>, Error> {
@@ -343,7 +1981,7 @@ impl Database {
             .await
     }
 
-    pub async fn remove_from_scheduled_delete(&self, id: i64) -> Result<bool, Error> {
+    async fn remove_from_scheduled_delete(&self, id: i64) -> Result<bool, Error> {
         let affected = query("DELETE FROM scheduled_to_delete WHERE id = $1")
             .bind(id)
             .execute(&self.pool)
@@ -0,0 +1,131 @@
+use teloxide::types::{Message, MessageEntity, MessageEntityKind};
+use url::Url;
+
+use crate::handlers::filters::entity_text;
+
+/// Shortest normalized text, in characters, worth a fingerprint. Shorter texts such as "ok"
+/// would match far too much.
+const MIN_TEXT_LEN: usize = 16;
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher` it is guaranteed to stay the same across Rust
+/// releases, so stored hashes keep matching.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// `text` lowercased with everything but letters and digits removed, so that reposts with
+/// changed spacing, punctuation or emoji still match.
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Hosts that ordinary messages link to all the time. A repost of spam would match their
+/// fingerprint along with countless unrelated messages, so they never get one. Subdomains such as
+/// `m.youtube.com` are covered too.
+const COMMON_HOSTS: &[&str] = &[
+    "t.me",
+    "telegram.me",
+    "telegram.org",
+    "telegra.ph",
+    "google.com",
+    "goo.gl",
+    "youtube.com",
+    "youtu.be",
+    "twitter.com",
+    "x.com",
+    "facebook.com",
+    "instagram.com",
+    "tiktok.com",
+    "reddit.com",
+    "wikipedia.org",
+    "github.com",
+    "medium.com",
+    "linkedin.com",
+    "bit.ly",
+    "apple.com",
+    "microsoft.com",
+    "amazon.com",
+];
+
+fn is_common(host: &str) -> bool {
+    COMMON_HOSTS
+        .iter()
+        .any(|common| host == *common || host.ends_with(&format!(".{}", common)))
+}
+
+/// The host of a link, without a leading `www.`, unless it is one of `COMMON_HOSTS`. Links in
+/// text may lack a scheme.
+fn domain(link: &str) -> Option<String> {
+    let url = if link.contains("://") {
+        Url::parse(link)
+    } else {
+        Url::parse(&format!("http://{}", link))
+    }
+    .ok()?;
+
+    let host = url.host_str()?.to_lowercase();
+    let host = host.trim_start_matches("www.");
+
+    (!is_common(host)).then(|| host.into())
+}
+
+fn domains(text: &str, entities: &[MessageEntity]) -> Vec<String> {
+    entities
+        .iter()
+        .filter_map(|e| match &e.kind {
+            MessageEntityKind::Url => domain(&entity_text(text, e)),
+            MessageEntityKind::TextLink { url } => domain(url.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn file_unique_id(msg: &Message) -> Option<&str> {
+    msg.photo()
+        .and_then(|sizes| sizes.last())
+        .map(|p| p.file.unique_id.as_str())
+        .or_else(|| msg.video().map(|v| v.file.unique_id.as_str()))
+        .or_else(|| msg.animation().map(|a| a.file.unique_id.as_str()))
+        .or_else(|| msg.document().map(|d| d.file.unique_id.as_str()))
+        .or_else(|| msg.sticker().map(|s| s.file.unique_id.as_str()))
+        .or_else(|| msg.audio().map(|a| a.file.unique_id.as_str()))
+        .or_else(|| msg.voice().map(|v| v.file.unique_id.as_str()))
+        .or_else(|| msg.video_note().map(|v| v.file.unique_id.as_str()))
+}
+
+/// What identifies the content of `msg` when it is posted again: a hash of its normalized
+/// text, the unique id of its media and the domains it links to, apart from common ones. Each is
+/// one `kind:value` string without whitespace.
+pub fn fingerprints(msg: &Message) -> Vec<String> {
+    let mut prints = Vec::new();
+
+    let (text, entities) = match (msg.text(), msg.caption()) {
+        (Some(text), _) => (text, msg.entities().unwrap_or_default()),
+        (None, Some(caption)) => (caption, msg.caption_entities().unwrap_or_default()),
+        _ => ("", &[][..]),
+    };
+
+    let normalized = normalize(text);
+    if normalized.chars().count() >= MIN_TEXT_LEN {
+        prints.push(format!("text:{:016x}", fnv1a(normalized.as_bytes())));
+    }
+
+    if let Some(id) = file_unique_id(msg) {
+        prints.push(format!("file:{}", id));
+    }
+
+    for d in domains(text, entities) {
+        let print = format!("domain:{}", d);
+
+        if !prints.contains(&print) {
+            prints.push(print);
+        }
+    }
+
+    prints
+}
@@ -0,0 +1,104 @@
+//! Building MarkdownV2 messages. Translations and user-controlled values such as names and
+//! reasons are escaped; only markup added on purpose through [`Markdown`] survives.
+
+use std::fmt;
+
+use loon::Opts;
+use teloxide::{
+    types::{Chat, User},
+    utils::markdown,
+};
+
+use crate::types::Localization;
+
+/// Text that is safe to send with `ParseMode::MarkdownV2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Markdown(String);
+
+/// Escapes `text` to be shown as is. Unlike [`markdown::escape`], this also escapes backslashes.
+pub fn escape(text: &str) -> String {
+    markdown::escape(&text.replace('\\', r"\\"))
+}
+
+impl Markdown {
+    /// Plain text, shown exactly as given.
+    pub fn text<S: AsRef<str>>(text: S) -> Self {
+        Markdown(escape(text.as_ref()))
+    }
+
+    /// A link to `user`, showing their full name.
+    pub fn user(user: &User) -> Self {
+        Markdown(markdown::user_mention(
+            user.id.0.try_into().unwrap(),
+            &escape(&user.full_name()),
+        ))
+    }
+
+    /// The title of `chat`, linked to it if the chat is public.
+    pub fn chat(chat: &Chat) -> Self {
+        let title = Markdown::text(chat.title().unwrap_or_default());
+
+        match chat.username() {
+            Some(username) => title.link(&format!("https://t.me/{}", username)),
+            None => title,
+        }
+    }
+
+    pub fn bold(self) -> Self {
+        Markdown(markdown::bold(&self.0))
+    }
+
+    pub fn italic(self) -> Self {
+        Markdown(markdown::italic(&self.0))
+    }
+
+    /// This text as a link to `url`, which must not contain `)` or `\`.
+    pub fn link(self, url: &str) -> Self {
+        Markdown(markdown::link(url, &self.0))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Markdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Markdown> for String {
+    fn from(m: Markdown) -> Self {
+        m.0
+    }
+}
+
+/// Stands in for the variable at `index` until the translation around it is escaped.
+fn placeholder(index: usize) -> String {
+    format!("\u{E000}{}\u{E001}", index)
+}
+
+/// Translates `key`, escaping the translation itself but not the already formatted `vars`.
+pub fn translate(
+    loc: &Localization,
+    key: &str,
+    locale: &str,
+    vars: &[(&str, Markdown)],
+) -> loon::err::Result<Markdown> {
+    let opts = vars
+        .iter()
+        .enumerate()
+        .fold(Opts::default().locale(locale), |opts, (i, (name, _))| {
+            opts.var(*name, placeholder(i))
+        });
+
+    let text = vars
+        .iter()
+        .enumerate()
+        .fold(escape(&loc.t(key, opts)?), |text, (i, (_, value))| {
+            text.replace(&placeholder(i), value.as_str())
+        });
+
+    Ok(Markdown(text))
+}
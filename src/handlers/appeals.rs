@@ -0,0 +1,396 @@
+use loon::Opts;
+use teloxide::{
+    dispatching::UpdateFilterExt,
+    dptree,
+    payloads::{AnswerCallbackQuerySetters, EditMessageTextSetters, SendMessageSetters},
+    requests::Requester,
+    types::{
+        CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Me, Message, ParseMode,
+        Update, UserId,
+    },
+};
+
+use super::filters::user_privileged;
+use super::restore::restore;
+use super::strikes::take_back_strike;
+use super::utils::{get_locale, now};
+use crate::database::{Appeal, Poll};
+use crate::format::{translate, Markdown};
+use crate::storage::Store;
+use crate::types::{
+    AppealStatus, AtomicHandler, Configuration, DeleteIttBot, HandlerResult, Localization,
+};
+
+/// Longest statement, in characters, relayed to the admins.
+pub const MAX_STATEMENT_LEN: usize = 500;
+
+/// The result message of a poll, which identifies an appeal against it.
+#[derive(Debug, Clone, Copy)]
+struct AppealRef {
+    chat_id: i64,
+    poll_id: i32,
+}
+
+impl AppealRef {
+    /// Parses `{chat_id}_{poll_id}`, as used in links and buttons.
+    fn parse(text: &str) -> Option<Self> {
+        let (chat_id, poll_id) = text.split_once('_')?;
+
+        Some(AppealRef {
+            chat_id: chat_id.parse().ok()?,
+            poll_id: poll_id.parse().ok()?,
+        })
+    }
+}
+
+/// An admin's answer to an appeal, from the button they pressed.
+#[derive(Debug, Clone, Copy)]
+struct Decision {
+    approve: bool,
+    appeal: AppealRef,
+}
+
+/// Lets the author of the message `info` deleted appeal, and returns the link that opens the
+/// appeal in a private chat with the bot.
+pub async fn appeal_link(
+    me: &Me,
+    db: &Store,
+    loc: &Localization,
+    locale: &str,
+    info: &Poll,
+) -> Result<Markdown, Box<dyn std::error::Error + Send + Sync>> {
+    db.create_appeal(info.chat_id, info.poll_id, info.message_user_id, now())
+        .await?;
+
+    let url = format!(
+        "https://t.me/{}?start=appeal_{}_{}",
+        me.username(),
+        info.chat_id,
+        info.poll_id
+    );
+
+    Ok(translate(loc, "appeal.link", locale, &[])?.link(&url))
+}
+
+fn decision_markup(
+    loc: &Localization,
+    locale: &str,
+    appeal: &Appeal,
+) -> loon::err::Result<InlineKeyboardMarkup> {
+    let id = format!("{}_{}", appeal.chat_id, appeal.poll_id);
+
+    Ok(InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback(
+            loc.t("appeal.approve", Opts::default().locale(locale))?,
+            format!("appeal_approve:{}", id),
+        ),
+        InlineKeyboardButton::callback(
+            loc.t("appeal.reject", Opts::default().locale(locale))?,
+            format!("appeal_reject:{}", id),
+        ),
+    ]]))
+}
+
+/// What admins see about `appeal`.
+async fn relay_text(
+    bot: &DeleteIttBot,
+    loc: &Localization,
+    locale: &str,
+    appeal: &Appeal,
+) -> Result<Markdown, Box<dyn std::error::Error + Send + Sync>> {
+    let author = bot
+        .get_chat_member(
+            ChatId(appeal.chat_id),
+            UserId(appeal.user_id.try_into().unwrap()),
+        )
+        .await?;
+
+    Ok(translate(
+        loc,
+        "appeal.relay",
+        locale,
+        &[
+            ("from_name", Markdown::user(&author.user)),
+            (
+                "statement",
+                Markdown::text(appeal.statement.as_deref().unwrap_or_default()),
+            ),
+        ],
+    )?)
+}
+
+/// Starts the appeal a deep link points to, if it belongs to the user who opened it.
+async fn handle_start(
+    bot: DeleteIttBot,
+    msg: Message,
+    target: AppealRef,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+) -> HandlerResult {
+    let locale = get_locale(&db, &config, target.chat_id).await;
+    let user_id = msg.chat.id.0;
+
+    let appeal = match db.get_appeal(target.chat_id, target.poll_id).await? {
+        Some(appeal)
+            if appeal.user_id == user_id
+                && matches!(appeal.status(), AppealStatus::Open | AppealStatus::Awaiting) =>
+        {
+            appeal
+        }
+        _ => {
+            let response = loc.t("appeal.unavailable", Opts::default().locale(&locale))?;
+
+            bot.send_message(msg.chat.id, response).await?;
+
+            return Ok(());
+        }
+    };
+
+    // Only one appeal at a time takes the next message.
+    if let Some(other) = db.get_user_appeal(user_id, AppealStatus::Awaiting).await? {
+        db.set_appeal_status(other.id, AppealStatus::Open).await?;
+    }
+
+    db.set_appeal_status(appeal.id, AppealStatus::Awaiting)
+        .await?;
+
+    let response = loc.t(
+        "appeal.prompt",
+        Opts::default()
+            .var("length", MAX_STATEMENT_LEN)
+            .locale(&locale),
+    )?;
+
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+/// The appeal waiting for the statement `msg` may be. Commands are never statements.
+async fn awaiting_appeal(msg: &Message, db: &Store) -> Option<Appeal> {
+    if matches!(msg.text(), Some(text) if text.starts_with('/')) {
+        return None;
+    }
+
+    let user_id = msg.from()?.id.0.try_into().ok()?;
+
+    db.get_user_appeal(user_id, AppealStatus::Awaiting)
+        .await
+        .ok()?
+}
+
+/// Relays the author's statement to the chat's appeal chat, or to the chat itself.
+async fn handle_statement(
+    bot: DeleteIttBot,
+    msg: Message,
+    appeal: Appeal,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+) -> HandlerResult {
+    let locale = get_locale(&db, &config, appeal.chat_id).await;
+
+    let statement = match msg.text() {
+        Some(text) if text.chars().count() <= MAX_STATEMENT_LEN => text,
+        _ => {
+            let response = loc.t(
+                "appeal.prompt",
+                Opts::default()
+                    .var("length", MAX_STATEMENT_LEN)
+                    .locale(&locale),
+            )?;
+
+            bot.send_message(msg.chat.id, response).await?;
+
+            return Ok(());
+        }
+    };
+
+    db.set_appeal_statement(appeal.id, statement).await?;
+
+    let appeal = Appeal {
+        statement: Some(statement.into()),
+        ..appeal
+    };
+    let destination = match db.get_chat(appeal.chat_id).await? {
+        Some(chat) => chat.appeal_chat_id.unwrap_or(appeal.chat_id),
+        None => appeal.chat_id,
+    };
+
+    bot.send_message(
+        ChatId(destination),
+        relay_text(&bot, &loc, &locale, &appeal).await?,
+    )
+    .parse_mode(ParseMode::MarkdownV2)
+    .reply_markup(decision_markup(&loc, &locale, &appeal)?)
+    .await?;
+
+    let response = loc.t("appeal.sent", Opts::default().locale(&locale))?;
+
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+/// Approves or rejects a pending appeal. Approval restores the message if it was archived and
+/// takes back the strike the poll gave, along with any mute or ban that strike led to.
+async fn handle_decision(
+    bot: DeleteIttBot,
+    query: CallbackQuery,
+    decision: Decision,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+) -> HandlerResult {
+    let target = decision.appeal;
+    let locale = get_locale(&db, &config, target.chat_id).await;
+
+    // Appeals may be relayed to another chat, but only admins of the chat they are about decide.
+    if !user_privileged(&bot, ChatId(target.chat_id), query.from.id).await {
+        let response = loc.t("appeal.admins_only", Opts::default().locale(&locale))?;
+
+        bot.answer_callback_query(query.id)
+            .text(response)
+            .show_alert(true)
+            .await?;
+
+        return Ok(());
+    }
+
+    let appeal = match db.get_appeal(target.chat_id, target.poll_id).await? {
+        Some(appeal) if appeal.status() == AppealStatus::Pending => appeal,
+        _ => {
+            let response = loc.t("appeal.decided", Opts::default().locale(&locale))?;
+
+            bot.answer_callback_query(query.id)
+                .text(response)
+                .show_alert(true)
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let (status, key) = if decision.approve {
+        (AppealStatus::Approved, "appeal.approved")
+    } else {
+        (AppealStatus::Rejected, "appeal.rejected")
+    };
+
+    // Deciding comes first and only succeeds while the appeal is pending, so that an appeal is
+    // decided once even if two admins press at the same moment.
+    if !db.decide_appeal(appeal.id, status).await? {
+        let response = loc.t("appeal.decided", Opts::default().locale(&locale))?;
+
+        bot.answer_callback_query(query.id)
+            .text(response)
+            .show_alert(true)
+            .await?;
+
+        return Ok(());
+    }
+
+    // The appeal can not be decided again, so failing to take back the strike or to restore the
+    // message is reported instead of stopping the rest. A failed restore keeps the archive, so
+    // admins can still press Restore on the result message.
+    let mut failure = None;
+
+    if decision.approve {
+        if take_back_strike(&bot, &db, appeal.chat_id, appeal.user_id, appeal.poll_id)
+            .await
+            .is_err()
+        {
+            failure = Some("appeal.unstrike_failed");
+        }
+
+        let restored = match db.get_archive(appeal.chat_id, appeal.poll_id).await {
+            Ok(Some(archive)) => restore(&bot, &db, &loc, &locale, &archive, &query.from)
+                .await
+                .is_ok(),
+            Ok(None) => true,
+            Err(_) => false,
+        };
+
+        if !restored {
+            failure = failure.or(Some("appeal.restore_failed"));
+        }
+    }
+
+    if let Some(msg) = query.message {
+        let text = format!(
+            "{}\n{}",
+            relay_text(&bot, &loc, &locale, &appeal).await?,
+            translate(
+                &loc,
+                key,
+                &locale,
+                &[("admin_name", Markdown::user(&query.from))]
+            )?
+        );
+
+        bot.edit_message_text(msg.chat.id, msg.id, text)
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+    }
+
+    // The author may have blocked the bot since.
+    let notice = loc.t(
+        format!("{}_author", key).as_str(),
+        Opts::default().locale(&locale),
+    )?;
+
+    bot.send_message(ChatId(appeal.user_id), notice).await.ok();
+
+    if let Some(failure) = failure {
+        let response = loc.t(failure, Opts::default().locale(&locale))?;
+
+        bot.answer_callback_query(query.id)
+            .text(response)
+            .show_alert(true)
+            .await?;
+    } else {
+        bot.answer_callback_query(query.id).await?;
+    }
+
+    Ok(())
+}
+
+/// Lets authors of deleted messages appeal through a private chat with the bot, and admins
+/// decide on their appeals.
+pub fn appeal_handler() -> AtomicHandler {
+    let start = Update::filter_message()
+        .filter(|msg: Message| msg.chat.is_private())
+        .filter_map(|msg: Message| {
+            msg.text()?
+                .strip_prefix("/start appeal_")
+                .and_then(AppealRef::parse)
+        })
+        .endpoint(handle_start);
+
+    let statement = Update::filter_message()
+        .filter(|msg: Message| msg.chat.is_private())
+        .filter_map_async(|msg: Message, db: Store| async move { awaiting_appeal(&msg, &db).await })
+        .endpoint(handle_statement);
+
+    let decision = Update::filter_callback_query()
+        .filter_map(|query: CallbackQuery| {
+            let data = query.data?;
+            let (approve, rest) = match data.split_once(':')? {
+                ("appeal_approve", rest) => (true, rest),
+                ("appeal_reject", rest) => (false, rest),
+                _ => return None,
+            };
+
+            Some(Decision {
+                approve,
+                appeal: AppealRef::parse(rest)?,
+            })
+        })
+        .endpoint(handle_decision);
+
+    dptree::entry()
+        .branch(start)
+        .branch(statement)
+        .branch(decision)
+}
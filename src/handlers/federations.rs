@@ -0,0 +1,198 @@
+use teloxide::{
+    dispatching::UpdateFilterExt,
+    payloads::SendMessageSetters,
+    requests::Requester,
+    types::{ChatId, Message, ParseMode, Update},
+};
+
+use super::filters::sender_privileged;
+use super::setup_poll::{open_poll, PollOptions};
+use super::utils::{now, update_count};
+use crate::database::Federation;
+use crate::format::{translate, Markdown};
+use crate::sender::{self, sender, Sender};
+use crate::storage::Store;
+use crate::types::{
+    AtomicHandler, Configuration, DeleteIttBot, FederationAction, HandlerResult, Localization,
+    PollOrigin,
+};
+
+/// Longest federation name, in characters, accepted by `/fed new`.
+pub const MAX_FEDERATION_NAME_LEN: usize = 64;
+
+/// Sends the join token of `federation` to the admin who sent `msg`, in a private chat, so that
+/// nobody else in the group sees it. Returns whether that worked: anonymous admins can't be
+/// messaged, and users have to start a private chat with the bot first.
+pub async fn send_token(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    loc: &Localization,
+    locale: &str,
+    federation: &Federation,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let user_id = match sender(msg) {
+        Some(user @ Sender::User(_)) => user.id(),
+        _ => return Ok(false),
+    };
+
+    let text = translate(
+        loc,
+        "federation.token",
+        locale,
+        &[
+            ("name", Markdown::text(&federation.name)),
+            ("token", Markdown::text(&federation.token)),
+        ],
+    )?;
+
+    Ok(bot
+        .send_message(ChatId(user_id), text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await
+        .is_ok())
+}
+
+/// The federation `chat_id` is a member of, if any.
+pub async fn chat_federation(
+    db: &Store,
+    chat_id: i64,
+) -> Result<Option<Federation>, Box<dyn std::error::Error + Send + Sync>> {
+    let federation_id = match db.get_chat(chat_id).await? {
+        Some(chat) => chat.federation_id,
+        None => None,
+    };
+
+    Ok(match federation_id {
+        Some(id) => db.get_federation(id).await?,
+        None => None,
+    })
+}
+
+/// Puts `user_id` on the blocklist of the federation `chat_id` is in, and tells every other
+/// member chat according to its action: chats that ban do so right away and chats that warn get
+/// a message naming the user. Chats that poll wait for the user's next message. Returns the
+/// federation, or `None` if the chat is not in one.
+pub async fn federation_ban(
+    bot: &DeleteIttBot,
+    db: &Store,
+    loc: &Localization,
+    chat_id: i64,
+    user_id: i64,
+    name: &Markdown,
+) -> Result<Option<Federation>, Box<dyn std::error::Error + Send + Sync>> {
+    let federation = match chat_federation(db, chat_id).await? {
+        Some(federation) => federation,
+        None => return Ok(None),
+    };
+
+    db.create_federation_ban(federation.id, user_id, chat_id, now())
+        .await?;
+
+    for member in db.get_federation_chats(federation.id).await? {
+        if member.chat_id == chat_id {
+            continue;
+        }
+
+        // Failures in one chat, such as missing rights, must not keep the others from acting.
+        match member.federation_action() {
+            FederationAction::Ban => {
+                sender::ban(bot, member.chat_id, user_id).await.ok();
+            }
+            FederationAction::Warn => {
+                let text = translate(
+                    loc,
+                    "federation.warning",
+                    &member.locale,
+                    &[
+                        ("name", name.clone()),
+                        ("federation", Markdown::text(&federation.name)),
+                    ],
+                )?;
+
+                bot.send_message(ChatId(member.chat_id), text)
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await
+                    .ok();
+            }
+            FederationAction::Poll => {}
+        }
+    }
+
+    Ok(Some(federation))
+}
+
+/// What the chat wants done with `msg` if its sender is banned in the chat's federation. Admins
+/// and chats that only warn are left alone.
+async fn federation_action(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+) -> Option<FederationAction> {
+    let chat = db.get_chat(msg.chat.id.0).await.ok()??;
+    let federation_id = chat.federation_id?;
+    let action = chat.federation_action();
+    let sender_id = sender(msg)?.id();
+
+    if action == FederationAction::Warn {
+        return None;
+    }
+
+    db.get_federation_ban(federation_id, sender_id)
+        .await
+        .ok()??;
+
+    if sender_privileged(bot, msg).await {
+        return None;
+    }
+
+    Some(action)
+}
+
+async fn handle_banned_member(
+    bot: DeleteIttBot,
+    msg: Message,
+    action: FederationAction,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let user_id = sender(&msg).unwrap().id();
+
+    match action {
+        FederationAction::Ban => {
+            sender::ban(&bot, chat_id, user_id).await?;
+            bot.delete_message(msg.chat.id, msg.id).await?;
+            db.remove_recent_message(chat_id, msg.id.0).await?;
+        }
+        FederationAction::Poll => {
+            // One open poll at a time is enough to decide about a user.
+            if db.count_polls_about(chat_id, user_id).await? > 0 {
+                return Ok(());
+            }
+
+            let options = PollOptions {
+                origin: Some(PollOrigin::Federation),
+                ..PollOptions::default()
+            };
+
+            if let Some(poll) = open_poll(&bot, &msg, options, &db, &config, &loc).await? {
+                update_count(&bot, &poll, &db, &config, &loc).await?;
+            }
+        }
+        FederationAction::Warn => {}
+    }
+
+    Ok(())
+}
+
+/// Bans, or opens a poll about, users banned in the chat's federation when they post. Other
+/// messages pass through untouched.
+pub fn federation_handler() -> AtomicHandler {
+    Update::filter_message()
+        .filter(|msg: Message| msg.chat.is_group() || msg.chat.is_supergroup())
+        .filter_map_async(|bot: DeleteIttBot, msg: Message, db: Store| async move {
+            federation_action(&bot, &msg, &db).await
+        })
+        .endpoint(handle_banned_member)
+}
@@ -1,22 +1,39 @@
 use regex::Regex;
 use teloxide::{
     requests::Requester,
-    types::{CallbackQuery, Me, Message},
+    types::{CallbackQuery, ChatId, Me, Message, MessageEntity, MessageEntityKind, UserId},
 };
 
-use crate::database::Database;
-use crate::types::DeleteIttBot;
+use super::utils::topic;
+use crate::sender::{is_anonymous_admin, sender, Sender};
+use crate::storage::Store;
+use crate::types::{DeleteIttBot, Trigger};
 
-pub async fn is_privileged(bot: DeleteIttBot, msg: Message) -> bool {
-    match msg.from() {
-        Some(from) => match bot.get_chat_member(msg.chat.id, from.id).await {
-            Ok(member) => member.is_privileged(),
-            Err(_) => false,
-        },
-        None => false,
+/// Whether `user_id` is an admin of `chat_id`. Also decides who may press admin-only buttons.
+pub async fn user_privileged(bot: &DeleteIttBot, chat_id: ChatId, user_id: UserId) -> bool {
+    match bot.get_chat_member(chat_id, user_id).await {
+        Ok(member) => member.is_privileged(),
+        Err(_) => false,
+    }
+}
+
+/// Whether whoever sent `msg` is an admin of its chat. Anonymous admins are, chats posting on
+/// behalf of themselves are not.
+pub async fn sender_privileged(bot: &DeleteIttBot, msg: &Message) -> bool {
+    if is_anonymous_admin(msg) {
+        return true;
+    }
+
+    match sender(msg) {
+        Some(Sender::User(from)) => user_privileged(bot, msg.chat.id, from.id).await,
+        _ => false,
     }
 }
 
+pub async fn is_privileged(bot: DeleteIttBot, msg: Message) -> bool {
+    sender_privileged(&bot, &msg).await
+}
+
 pub fn callback_query_eq<S>(data: S) -> impl Fn(CallbackQuery) -> bool
 where
     S: Copy + Into<String>,
@@ -27,30 +44,87 @@ where
     }
 }
 
-pub async fn non_duplicate(query: CallbackQuery, db: Database) -> bool {
+pub async fn non_duplicate(query: CallbackQuery, db: Store) -> bool {
     match query.message {
-        Some(msg) => match db.get_poll(msg.chat.id.0, msg.id).await {
-            Ok(Some(poll)) => {
-                if let Some(from) = msg.from() {
-                    match db.get_voter(poll.id, from.id.0.try_into().unwrap()).await {
-                        Ok(o) => o.is_none(),
-                        _ => false,
-                    }
-                } else {
-                    false
-                }
-            }
+        Some(msg) => match db.get_poll(msg.chat.id.0, msg.id.0).await {
+            Ok(Some(poll)) => match db
+                .get_voter(poll.id, query.from.id.0.try_into().unwrap())
+                .await
+            {
+                Ok(o) => o.is_none(),
+                _ => false,
+            },
             _ => false,
         },
         _ => false,
     }
 }
 
-pub async fn target_me(me: Me, msg: Message) -> bool {
-    match msg.text() {
-        Some(txt) => Regex::new(format!("@{}(\\n|\\s|$)", me.username()).as_str())
+/// Text covered by `entity`. Entity offsets are counted in UTF-16 code units.
+pub fn entity_text(text: &str, entity: &MessageEntity) -> String {
+    let units = text
+        .encode_utf16()
+        .skip(entity.offset)
+        .take(entity.length)
+        .collect::<Vec<u16>>();
+
+    String::from_utf16_lossy(&units)
+}
+
+fn mentions_me(me: &Me, text: &str, entities: &[MessageEntity]) -> bool {
+    let by_entity = entities.iter().any(|e| match &e.kind {
+        MessageEntityKind::Mention => entity_text(text, e)
+            .trim_start_matches('@')
+            .eq_ignore_ascii_case(me.username()),
+        MessageEntityKind::TextMention { user } => user.id == me.id,
+        _ => false,
+    });
+
+    by_entity
+        || Regex::new(format!("(?i)@{}(\\n|\\s|$)", me.username()).as_str())
             .unwrap()
-            .is_match(txt),
-        None => false,
+            .is_match(text)
+}
+
+pub async fn target_me(me: Me, msg: Message) -> bool {
+    if let Some(txt) = msg.text() {
+        return mentions_me(&me, txt, msg.entities().unwrap_or_default());
     }
+
+    if let Some(caption) = msg.caption() {
+        return mentions_me(&me, caption, msg.caption_entities().unwrap_or_default());
+    }
+
+    false
+}
+
+/// Whether the chat allows starting polls with `trigger`. Chats without settings allow all.
+async fn trigger_enabled(db: &Store, chat_id: i64, trigger: Trigger) -> bool {
+    !matches!(db.get_chat_trigger(chat_id, trigger).await, Ok(Some(false)))
+}
+
+pub async fn mention_enabled(msg: Message, db: Store) -> bool {
+    trigger_enabled(&db, msg.chat.id.0, Trigger::Mention).await
+}
+
+pub async fn command_enabled(msg: Message, db: Store) -> bool {
+    trigger_enabled(&db, msg.chat.id.0, Trigger::Command).await
+}
+
+/// Whether polls may be opened in the forum topic of `msg`. Topics without settings, and
+/// messages outside topics, allow them.
+pub async fn in_enabled_topic(db: &Store, msg: &Message) -> bool {
+    let thread_id = match topic(msg) {
+        Some(thread_id) => thread_id,
+        None => return true,
+    };
+
+    !matches!(
+        db.get_topic(msg.chat.id.0, thread_id).await,
+        Ok(Some(t)) if !t.enabled
+    )
+}
+
+pub async fn topic_enabled(msg: Message, db: Store) -> bool {
+    in_enabled_topic(&db, &msg).await
 }
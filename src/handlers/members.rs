@@ -0,0 +1,32 @@
+use teloxide::types::{Update, UpdateKind};
+
+use crate::sender::{sender, Sender};
+use crate::storage::Store;
+
+/// Remembers when each sender was first seen in a group, how many messages they sent there and
+/// their username, for the voter eligibility rules and `/strikes`, and their recent message
+/// ids, for polls reaching beyond their target. Chats sending messages are tracked like users.
+/// Never stops the update from reaching other handlers.
+pub async fn track_member(update: Update, db: Store) {
+    if let UpdateKind::Message(msg) = update.kind {
+        if !(msg.chat.is_group() || msg.chat.is_supergroup()) {
+            return;
+        }
+
+        if let Some(sender) = sender(&msg) {
+            let user_id = sender.id();
+            let username = match sender {
+                Sender::User(user) => user.username.as_deref(),
+                Sender::Chat(chat) => chat.username(),
+            };
+            let timestamp = msg.date.timestamp();
+
+            let _ = db
+                .track_member(msg.chat.id.0, user_id, username, timestamp)
+                .await;
+            let _ = db
+                .track_message(msg.chat.id.0, user_id, msg.id.0, timestamp)
+                .await;
+        }
+    }
+}
@@ -1,10 +1,28 @@
-mod filters;
+mod appeals;
+mod federations;
+pub mod filters;
+mod members;
+mod my_chat_member;
+mod onboarding;
+mod patterns;
+pub mod permissions;
+mod reposts;
+mod restore;
 mod settings;
 mod setup_poll;
-mod utils;
+mod strikes;
+pub mod utils;
 mod vote_no;
 mod vote_yes;
 
+pub use appeals::appeal_handler;
+pub use federations::federation_handler;
+pub use members::track_member;
+pub use my_chat_member::my_chat_member_handler;
+pub use onboarding::quick_setup_handler;
+pub use patterns::{pattern_handler, PatternCache};
+pub use reposts::repost_handler;
+pub use restore::restore_handler;
 pub use settings::settings_handler;
 pub use setup_poll::setup_poll_handler;
 pub use vote_no::vote_no_handler;
@@ -0,0 +1,50 @@
+use teloxide::{
+    dispatching::UpdateFilterExt,
+    types::{ChatMemberUpdated, Update},
+};
+
+use super::onboarding::send_welcome;
+use super::patterns::PatternCache;
+use super::permissions::{lacking_rights, report_rights};
+use crate::storage::Store;
+use crate::types::{
+    AtomicHandler, Configuration, DeleteIttBot, HandlerResult, Locale, Localization,
+};
+
+/// Welcomes the chat when the bot joins it, checks the bot's rights whenever they change and
+/// forgets the chat once the bot is removed.
+async fn handle_my_chat_member(
+    bot: DeleteIttBot,
+    update: ChatMemberUpdated,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+    locales: Vec<Locale>,
+    cache: PatternCache,
+) -> HandlerResult {
+    let chat_id = update.chat.id;
+    cache.forget(chat_id.0);
+
+    if !update.new_chat_member.is_present() {
+        db.remove_chat(chat_id.0).await?;
+
+        return Ok(());
+    }
+
+    if !update.old_chat_member.is_present() {
+        send_welcome(&bot, chat_id, &db, &config, &loc, &locales).await?;
+    }
+
+    let chat = db.get_chat(chat_id.0).await?;
+    let missing = lacking_rights(&update.new_chat_member.kind, chat.as_ref());
+
+    report_rights(&bot, chat_id, None, &db, &config, &loc, missing).await?;
+
+    Ok(())
+}
+
+pub fn my_chat_member_handler() -> AtomicHandler {
+    Update::filter_my_chat_member()
+        .filter(|update: ChatMemberUpdated| update.chat.is_group() || update.chat.is_supergroup())
+        .endpoint(handle_my_chat_member)
+}
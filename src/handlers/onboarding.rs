@@ -0,0 +1,174 @@
+use loon::Opts;
+use teloxide::{
+    dispatching::UpdateFilterExt,
+    payloads::{AnswerCallbackQuerySetters, SendMessageSetters},
+    requests::Requester,
+    types::{CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Update},
+};
+
+use super::filters::user_privileged;
+use super::utils::{ensure_chat, get_locale};
+use crate::storage::Store;
+use crate::types::{
+    AtomicHandler, Configuration, DeleteIttBot, HandlerResult, Locale, Localization,
+};
+
+/// Thresholds offered by the quick-setup keyboard, where the configuration allows them.
+const VOTE_COUNT_PRESETS: &[i64] = &[3, 5, 7, 10];
+
+/// Poll delete delays, in seconds, offered by the quick-setup keyboard, where the
+/// configuration allows them.
+const POLL_DELETE_DELAY_PRESETS: &[i64] = &[5, 30, 60];
+
+/// Prefix of the callback data sent by the quick-setup keyboard.
+const SETUP_PREFIX: &str = "setup:";
+
+fn setup_row<T>(
+    loc: &Localization,
+    locale: &str,
+    setting: &str,
+    values: impl Iterator<Item = T>,
+) -> loon::err::Result<Vec<InlineKeyboardButton>>
+where
+    T: ToString,
+{
+    values
+        .map(|v| {
+            let label = loc.t(
+                format!("welcome.{}", setting).as_str(),
+                Opts::default().var("value", v.to_string()).locale(locale),
+            )?;
+
+            Ok(InlineKeyboardButton::callback(
+                label,
+                format!("{}{}:{}", SETUP_PREFIX, setting, v.to_string()),
+            ))
+        })
+        .collect()
+}
+
+fn setup_markup(
+    loc: &Localization,
+    locale: &str,
+    config: &Configuration,
+    locales: &[Locale],
+) -> loon::err::Result<InlineKeyboardMarkup> {
+    let vote_counts = VOTE_COUNT_PRESETS
+        .iter()
+        .filter(|c| config.vote_count.contains(**c));
+    let delays = POLL_DELETE_DELAY_PRESETS
+        .iter()
+        .filter(|d| config.poll_delete_delay.contains(**d));
+
+    let mut markup = InlineKeyboardMarkup::default().append_row(setup_row(
+        loc,
+        locale,
+        "vote_count",
+        vote_counts,
+    )?);
+
+    for chunk in locales.chunks(4) {
+        markup = markup.append_row(setup_row(loc, locale, "language", chunk.iter())?);
+    }
+
+    Ok(markup.append_row(setup_row(loc, locale, "poll_delete_delay", delays)?))
+}
+
+/// Creates the chat's settings and introduces the bot, with a keyboard for the basic settings.
+pub async fn send_welcome(
+    bot: &DeleteIttBot,
+    chat_id: ChatId,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    locales: &[Locale],
+) -> HandlerResult {
+    ensure_chat(db, config, chat_id.0).await?;
+
+    let locale = get_locale(db, config, chat_id.0).await;
+    let response = loc.t("welcome.text", Opts::default().locale(&locale))?;
+
+    bot.send_message(chat_id, response)
+        .reply_markup(setup_markup(loc, &locale, config, locales)?)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_quick_setup(
+    bot: DeleteIttBot,
+    query: CallbackQuery,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+    locales: Vec<Locale>,
+) -> HandlerResult {
+    let msg = match &query.message {
+        Some(msg) => msg,
+        None => return Ok(()),
+    };
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(&db, &config, chat_id).await;
+
+    if !user_privileged(&bot, msg.chat.id, query.from.id).await {
+        let response = loc.t("welcome.admins_only", Opts::default().locale(&locale))?;
+
+        bot.answer_callback_query(query.id)
+            .text(response)
+            .show_alert(true)
+            .await?;
+
+        return Ok(());
+    }
+
+    ensure_chat(&db, &config, chat_id).await?;
+
+    let data = query.data.as_deref().unwrap_or_default();
+    let mut parts = data.trim_start_matches(SETUP_PREFIX).splitn(2, ':');
+    let setting = parts.next().unwrap_or_default();
+    let value = parts.next().unwrap_or_default();
+
+    let response = match (setting, value.parse::<i64>()) {
+        ("vote_count", Ok(count)) if config.vote_count.contains(count) => {
+            db.set_chat_votes(chat_id, count).await?;
+
+            Some(loc.t(
+                "vote_count.updated",
+                Opts::default().var("count", count).locale(&locale),
+            )?)
+        }
+        ("poll_delete_delay", Ok(delay)) if config.poll_delete_delay.contains(delay) => {
+            db.set_chat_poll_delete_delay(chat_id, delay).await?;
+
+            Some(loc.t(
+                "poll_delete_delay.updated",
+                Opts::default().var("delay", delay).locale(&locale),
+            )?)
+        }
+        ("language", _) if locales.iter().any(|l| l == value) => {
+            db.set_chat_locale(chat_id, value).await?;
+
+            Some(loc.t(
+                "language.updated",
+                Opts::default().var("language", value).locale(value),
+            )?)
+        }
+        _ => None,
+    };
+
+    match response {
+        Some(response) => bot.answer_callback_query(query.id).text(response).await?,
+        None => bot.answer_callback_query(query.id).await?,
+    };
+
+    Ok(())
+}
+
+pub fn quick_setup_handler() -> AtomicHandler {
+    Update::filter_callback_query()
+        .filter(|query: CallbackQuery| match query.data {
+            Some(data) => data.starts_with(SETUP_PREFIX),
+            None => false,
+        })
+        .endpoint(handle_quick_setup)
+}
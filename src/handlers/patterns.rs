@@ -0,0 +1,142 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use regex::{Regex, RegexBuilder};
+use teloxide::{
+    dispatching::UpdateFilterExt,
+    requests::Requester,
+    types::{Message, Update},
+};
+
+use super::filters::sender_privileged;
+use super::setup_poll::{open_poll, PollOptions};
+use super::utils::update_count;
+use crate::database::Pattern;
+use crate::storage::Store;
+use crate::types::{
+    AtomicHandler, Configuration, DeleteIttBot, HandlerResult, Localization, PollOrigin,
+};
+
+/// Longest pattern, in characters, accepted by `/filter`.
+pub const MAX_PATTERN_LEN: usize = 200;
+
+/// Most patterns a chat can have.
+pub const MAX_PATTERNS: usize = 50;
+
+/// Upper bound for the compiled size of a pattern, so that a single pattern can not make every
+/// message expensive to check.
+const PATTERN_SIZE_LIMIT: usize = 1 << 16;
+
+/// `pattern` as a case-insensitive regex, or `None` if it is not a valid or reasonably sized
+/// one.
+pub fn compile(pattern: &str) -> Option<Regex> {
+    if pattern.is_empty() || pattern.chars().count() > MAX_PATTERN_LEN {
+        return None;
+    }
+
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(PATTERN_SIZE_LIMIT)
+        .build()
+        .ok()
+}
+
+/// The compiled patterns of one chat.
+type Compiled = Arc<Vec<(Pattern, Regex)>>;
+
+/// Every chat's patterns, compiled once instead of for every message. `/filter` forgets a
+/// chat's entry whenever it changes the patterns, as does the bot joining or leaving the chat.
+#[derive(Clone, Default)]
+pub struct PatternCache(Arc<Mutex<HashMap<i64, Compiled>>>);
+
+impl PatternCache {
+    async fn get(&self, db: &Store, chat_id: i64) -> Option<Compiled> {
+        if let Some(compiled) = self.0.lock().unwrap().get(&chat_id) {
+            return Some(compiled.clone());
+        }
+
+        let compiled: Compiled = Arc::new(
+            db.get_patterns(chat_id)
+                .await
+                .ok()?
+                .into_iter()
+                .filter_map(|p| compile(&p.pattern).map(|r| (p, r)))
+                .collect(),
+        );
+
+        self.0.lock().unwrap().insert(chat_id, compiled.clone());
+
+        Some(compiled)
+    }
+
+    pub fn forget(&self, chat_id: i64) {
+        self.0.lock().unwrap().remove(&chat_id);
+    }
+}
+
+/// The pattern `msg` matches, preferring strict ones. Messages from admins, including anonymous
+/// ones, are left alone.
+async fn matching_pattern(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    cache: &PatternCache,
+) -> Option<Pattern> {
+    let text = msg.text().or_else(|| msg.caption())?;
+
+    let compiled = cache.get(db, msg.chat.id.0).await?;
+    let pattern = compiled
+        .iter()
+        .filter(|(_, r)| r.is_match(text))
+        .map(|(p, _)| p)
+        .min_by_key(|p| !p.strict)?
+        .clone();
+
+    if sender_privileged(bot, msg).await {
+        return None;
+    }
+
+    Some(pattern)
+}
+
+async fn handle_match(
+    bot: DeleteIttBot,
+    msg: Message,
+    pattern: Pattern,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+) -> HandlerResult {
+    if pattern.strict {
+        bot.delete_message(msg.chat.id, msg.id).await?;
+        db.remove_recent_message(msg.chat.id.0, msg.id.0).await?;
+
+        return Ok(());
+    }
+
+    let options = PollOptions {
+        origin: Some(PollOrigin::Filter),
+        ..PollOptions::default()
+    };
+
+    if let Some(poll) = open_poll(&bot, &msg, options, &db, &config, &loc).await? {
+        update_count(&bot, &poll, &db, &config, &loc).await?;
+    }
+
+    Ok(())
+}
+
+/// Opens a poll about messages matching one of the chat's `/filter` patterns, or deletes them
+/// if the pattern is strict. Other messages pass through untouched.
+pub fn pattern_handler() -> AtomicHandler {
+    Update::filter_message()
+        .filter(|msg: Message| msg.chat.is_group() || msg.chat.is_supergroup())
+        .filter_map_async(
+            |bot: DeleteIttBot, msg: Message, db: Store, cache: PatternCache| async move {
+                matching_pattern(&bot, &msg, &db, &cache).await
+            },
+        )
+        .endpoint(handle_match)
+}
@@ -0,0 +1,119 @@
+use loon::Opts;
+use teloxide::{
+    requests::{HasPayload, Request, Requester},
+    types::{ChatId, ChatMemberKind},
+};
+
+use super::utils::get_locale;
+use crate::database::Chat;
+use crate::storage::Store;
+use crate::types::{Configuration, DeleteIttBot, FederationAction, Localization};
+
+/// Whether a chat member has one particular right.
+type RightCheck = fn(&ChatMemberKind) -> bool;
+
+/// Locale key of the right to delete messages, without which no poll can be resolved.
+pub const DELETE_MESSAGES: &str = "permissions.delete_messages";
+
+/// Admin rights the bot needs in every group, each with the locale key naming it.
+const REQUIRED_RIGHTS: &[(&str, RightCheck)] =
+    &[(DELETE_MESSAGES, ChatMemberKind::can_delete_messages)];
+
+/// Admin rights the bot needs only in groups that escalate strikes or ban federation members.
+const BAN_RIGHTS: &[(&str, RightCheck)] = &[(
+    "permissions.ban_users",
+    ChatMemberKind::can_restrict_members,
+)];
+
+/// Whether the settings of `chat` have the bot mute or ban users.
+fn bans_users(chat: &Chat) -> bool {
+    chat.mute_after > 0
+        || chat.ban_after > 0
+        || (chat.federation_id.is_some() && chat.federation_action() == FederationAction::Ban)
+}
+
+/// Locale keys of the rights a bot with `member` status lacks in a chat with the settings in
+/// `chat`, if it has any yet.
+pub fn lacking_rights(member: &ChatMemberKind, chat: Option<&Chat>) -> Vec<&'static str> {
+    let optional = match chat {
+        Some(chat) if bans_users(chat) => BAN_RIGHTS,
+        _ => &[],
+    };
+
+    REQUIRED_RIGHTS
+        .iter()
+        .chain(optional)
+        .filter(|(_, has)| !has(member))
+        .map(|(key, _)| *key)
+        .collect()
+}
+
+/// Locale keys of the rights the bot lacks in `chat_id`.
+pub async fn missing_rights(
+    bot: &DeleteIttBot,
+    chat_id: ChatId,
+    db: &Store,
+) -> Result<Vec<&'static str>, Box<dyn std::error::Error + Send + Sync>> {
+    let me = bot.get_me().send().await?;
+    let member = bot.get_chat_member(chat_id, me.id).await?;
+    let chat = db.get_chat(chat_id.0).await?;
+
+    Ok(lacking_rights(&member.kind, chat.as_ref()))
+}
+
+/// The names of the rights in `missing`, ready to be put in a message.
+pub fn rights_list(
+    loc: &Localization,
+    locale: &str,
+    missing: &[&'static str],
+) -> loon::err::Result<String> {
+    Ok(missing
+        .iter()
+        .map(|key| loc.t(*key, Opts::default().locale(locale)))
+        .collect::<loon::err::Result<Vec<String>>>()?
+        .join(", "))
+}
+
+/// Tells the chat, in the forum topic `thread_id` if given, which rights the bot lacks there,
+/// if any. Returns whether something was missing.
+pub async fn report_missing_rights(
+    bot: &DeleteIttBot,
+    chat_id: ChatId,
+    thread_id: Option<i32>,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let missing = missing_rights(bot, chat_id, db).await?;
+
+    report_rights(bot, chat_id, thread_id, db, config, loc, missing).await
+}
+
+/// Tells the chat that the rights in `missing` are needed, unless it is empty. Returns whether
+/// something was missing.
+pub async fn report_rights(
+    bot: &DeleteIttBot,
+    chat_id: ChatId,
+    thread_id: Option<i32>,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    missing: Vec<&'static str>,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    if missing.is_empty() {
+        return Ok(false);
+    }
+
+    let locale = get_locale(db, config, chat_id.0).await;
+    let rights = rights_list(loc, &locale, &missing)?;
+    let response = loc.t(
+        "permissions.missing",
+        Opts::default().var("rights", rights).locale(&locale),
+    )?;
+
+    bot.send_message(chat_id, response)
+        .with_payload_mut(|p| p.message_thread_id = thread_id)
+        .await?;
+
+    Ok(true)
+}
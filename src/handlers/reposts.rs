@@ -0,0 +1,82 @@
+use teloxide::{
+    dispatching::UpdateFilterExt,
+    requests::Requester,
+    types::{Message, Update},
+};
+
+use super::filters::sender_privileged;
+use super::setup_poll::{open_poll, PollOptions};
+use super::utils::update_count;
+use crate::fingerprint::fingerprints;
+use crate::storage::Store;
+use crate::types::{
+    AtomicHandler, Configuration, DeleteIttBot, FingerprintAction, HandlerResult, Localization,
+    PollOrigin,
+};
+
+/// What the chat wants done with `msg` if it reposts content deleted by vote before. Messages
+/// from admins, including anonymous ones, are left alone.
+async fn repost_action(bot: &DeleteIttBot, msg: &Message, db: &Store) -> Option<FingerprintAction> {
+    let action = db
+        .get_chat(msg.chat.id.0)
+        .await
+        .ok()??
+        .fingerprint_action()?;
+
+    let mut known = false;
+    for print in fingerprints(msg) {
+        if let Ok(true) = db.has_fingerprint(msg.chat.id.0, &print).await {
+            known = true;
+            break;
+        }
+    }
+
+    if !known {
+        return None;
+    }
+
+    if sender_privileged(bot, msg).await {
+        return None;
+    }
+
+    Some(action)
+}
+
+async fn handle_repost(
+    bot: DeleteIttBot,
+    msg: Message,
+    action: FingerprintAction,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+) -> HandlerResult {
+    match action {
+        FingerprintAction::Delete => {
+            bot.delete_message(msg.chat.id, msg.id).await?;
+            db.remove_recent_message(msg.chat.id.0, msg.id.0).await?;
+        }
+        FingerprintAction::Poll => {
+            let options = PollOptions {
+                origin: Some(PollOrigin::Repost),
+                ..PollOptions::default()
+            };
+
+            if let Some(poll) = open_poll(&bot, &msg, options, &db, &config, &loc).await? {
+                update_count(&bot, &poll, &db, &config, &loc).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes, or opens a poll about, messages repeating content that was deleted by vote, if the
+/// chat opted in with `/fingerprints`. Other messages pass through untouched.
+pub fn repost_handler() -> AtomicHandler {
+    Update::filter_message()
+        .filter(|msg: Message| msg.chat.is_group() || msg.chat.is_supergroup())
+        .filter_map_async(|bot: DeleteIttBot, msg: Message, db: Store| async move {
+            repost_action(&bot, &msg, &db).await
+        })
+        .endpoint(handle_repost)
+}
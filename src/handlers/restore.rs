@@ -0,0 +1,284 @@
+use loon::Opts;
+use teloxide::{
+    dispatching::UpdateFilterExt,
+    payloads::{
+        AnswerCallbackQuerySetters, SendAnimationSetters, SendAudioSetters, SendDocumentSetters,
+        SendMessageSetters, SendPhotoSetters, SendStickerSetters, SendVideoNoteSetters,
+        SendVideoSetters, SendVoiceSetters,
+    },
+    requests::{HasPayload, Requester},
+    types::{
+        CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, Message,
+        MessageId, ParseMode, Update, User,
+    },
+    RequestError,
+};
+
+use super::filters::{callback_query_eq, user_privileged};
+use super::utils::{get_locale, now, topic};
+use crate::content;
+use crate::database::Archive;
+use crate::format::{translate, Markdown};
+use crate::sender::{self, sender};
+use crate::storage::Store;
+use crate::types::{
+    AtomicHandler, Configuration, ContentKind, DeleteIttBot, HandlerResult, Localization,
+};
+
+/// Keeps a copy of `target`, the target of the poll `poll_id`, if its chat has an undo window.
+pub async fn archive(db: &Store, target: &Message, poll_id: i32) -> HandlerResult {
+    let chat_id = target.chat.id.0;
+
+    match db.get_chat(chat_id).await? {
+        Some(chat) if chat.undo_window > 0 => {}
+        _ => return Ok(()),
+    }
+
+    let (kind, sender) = match (content::kind(target), sender(target)) {
+        (Some(kind), Some(sender)) => (kind, sender),
+        _ => return Ok(()),
+    };
+
+    db.create_archive(
+        chat_id,
+        poll_id,
+        sender.id(),
+        kind,
+        content::file_id(target),
+        content::text(target),
+    )
+    .await?;
+
+    if let (Some(thread_id), Some(copy)) = (topic(target), db.get_archive(chat_id, poll_id).await?)
+    {
+        db.set_archive_thread(copy.id, thread_id).await?;
+    }
+
+    Ok(())
+}
+
+/// The keyboard of a result message whose target admins can still restore.
+pub fn restore_markup(loc: &Localization, locale: &str) -> loon::err::Result<InlineKeyboardMarkup> {
+    let text = loc.t("restore.button", Opts::default().locale(locale))?;
+
+    Ok(InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback(text, "restore"),
+    ]]))
+}
+
+/// Sends the archived content again, as a reply to `reply_to`, which also puts it in the forum
+/// topic of `reply_to`. Formatting of the original text is not kept.
+async fn repost(
+    bot: &DeleteIttBot,
+    chat_id: ChatId,
+    archive: &Archive,
+    reply_to: MessageId,
+) -> Result<(), RequestError> {
+    let file = InputFile::file_id(archive.file_id.clone().unwrap_or_default());
+    let text = archive.text.clone().unwrap_or_default();
+
+    match archive.kind() {
+        ContentKind::Text => {
+            bot.send_message(chat_id, text)
+                .reply_to_message_id(reply_to)
+                .await?;
+        }
+        ContentKind::Photo => {
+            bot.send_photo(chat_id, file)
+                .caption(text)
+                .reply_to_message_id(reply_to)
+                .await?;
+        }
+        ContentKind::Video => {
+            bot.send_video(chat_id, file)
+                .caption(text)
+                .reply_to_message_id(reply_to)
+                .await?;
+        }
+        ContentKind::Animation => {
+            bot.send_animation(chat_id, file)
+                .caption(text)
+                .reply_to_message_id(reply_to)
+                .await?;
+        }
+        ContentKind::Document => {
+            bot.send_document(chat_id, file)
+                .caption(text)
+                .reply_to_message_id(reply_to)
+                .await?;
+        }
+        ContentKind::Audio => {
+            bot.send_audio(chat_id, file)
+                .caption(text)
+                .reply_to_message_id(reply_to)
+                .await?;
+        }
+        ContentKind::Voice => {
+            bot.send_voice(chat_id, file)
+                .caption(text)
+                .reply_to_message_id(reply_to)
+                .await?;
+        }
+        ContentKind::VideoNote => {
+            bot.send_video_note(chat_id, file)
+                .reply_to_message_id(reply_to)
+                .await?;
+        }
+        ContentKind::Sticker => {
+            bot.send_sticker(chat_id, file)
+                .reply_to_message_id(reply_to.0)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Posts the attribution and then the content of `archive`, in the forum topic it came from.
+/// The attribution is taken back if the content can't be posted.
+async fn post_restored(
+    bot: &DeleteIttBot,
+    loc: &Localization,
+    locale: &str,
+    archive: &Archive,
+    admin: &User,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let chat_id = ChatId(archive.chat_id);
+    let attribution = translate(
+        loc,
+        "restore.attribution",
+        locale,
+        &[
+            (
+                "from_name",
+                sender::name(bot, archive.chat_id, archive.user_id).await?,
+            ),
+            ("admin_name", Markdown::user(admin)),
+        ],
+    )?;
+
+    let sent = bot
+        .send_message(chat_id, attribution)
+        .parse_mode(ParseMode::MarkdownV2)
+        .with_payload_mut(|p| p.message_thread_id = archive.thread_id)
+        .await?;
+
+    if let Err(e) = repost(bot, chat_id, archive, sent.id).await {
+        bot.delete_message(chat_id, sent.id).await.ok();
+
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Stores `archive` again after it could not be restored, so that admins can retry while the
+/// undo window lasts.
+async fn put_back(db: &Store, archive: &Archive) -> HandlerResult {
+    db.create_archive(
+        archive.chat_id,
+        archive.poll_id,
+        archive.user_id,
+        archive.kind(),
+        archive.file_id.as_deref(),
+        archive.text.as_deref(),
+    )
+    .await?;
+
+    if let Some(copy) = db.get_archive(archive.chat_id, archive.poll_id).await? {
+        if let Some(expires) = archive.expires {
+            db.set_archive_expiry(copy.id, expires).await?;
+        }
+
+        if let Some(thread_id) = archive.thread_id {
+            db.set_archive_thread(copy.id, thread_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Posts the content of `archive` again on behalf of `admin` and takes the restore button off
+/// the result message. Returns `false` if the undo window has passed or the archive was
+/// restored already. If posting fails, the archive is kept for another try.
+pub async fn restore(
+    bot: &DeleteIttBot,
+    db: &Store,
+    loc: &Localization,
+    locale: &str,
+    archive: &Archive,
+    admin: &User,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    if !matches!(archive.expires, Some(e) if e >= now()) {
+        return Ok(false);
+    }
+
+    // Whoever removes the archive first restores it, so that two quick presses post it once.
+    if !db.remove_archive(archive.id).await? {
+        return Ok(false);
+    }
+
+    if let Err(e) = post_restored(bot, loc, locale, archive, admin).await {
+        put_back(db, archive).await?;
+
+        return Err(e);
+    }
+
+    bot.edit_message_reply_markup(ChatId(archive.chat_id), MessageId(archive.poll_id))
+        .await
+        .ok();
+
+    Ok(true)
+}
+
+async fn handle_restore(
+    bot: DeleteIttBot,
+    query: CallbackQuery,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+) -> HandlerResult {
+    let msg = match query.message {
+        Some(msg) => msg,
+        None => return Ok(()),
+    };
+    let locale = get_locale(&db, &config, msg.chat.id.0).await;
+
+    if !user_privileged(&bot, msg.chat.id, query.from.id).await {
+        let response = loc.t("restore.admins_only", Opts::default().locale(&locale))?;
+
+        bot.answer_callback_query(query.id)
+            .text(response)
+            .show_alert(true)
+            .await?;
+
+        return Ok(());
+    }
+
+    let restored = match db.get_archive(msg.chat.id.0, msg.id.0).await? {
+        Some(archive) => restore(&bot, &db, &loc, &locale, &archive, &query.from).await,
+        None => Ok(false),
+    };
+
+    let key = match restored {
+        Ok(true) => "restore.done",
+        Ok(false) => "restore.expired",
+        Err(_) => "restore.failed",
+    };
+    let response = loc.t(key, Opts::default().locale(&locale))?;
+
+    bot.answer_callback_query(query.id)
+        .text(response)
+        .show_alert(!matches!(restored, Ok(true)))
+        .await?;
+
+    Ok(())
+}
+
+/// Lets admins press "Restore" on a result message to post the deleted content again, while
+/// the chat's undo window lasts.
+pub fn restore_handler() -> AtomicHandler {
+    Update::filter_callback_query()
+        .filter(callback_query_eq("restore"))
+        .endpoint(handle_restore)
+}
@@ -2,19 +2,29 @@ use loon::Opts;
 use teloxide::{
     dispatching::{HandlerExt, UpdateFilterExt},
     payloads::SendMessageSetters,
-    requests::Requester,
-    types::{Me, Message, ParseMode, Update},
-    utils::{command::BotCommands, markdown::escape as markdown_escape},
+    requests::{HasPayload, Request, Requester},
+    types::{ChatId, Me, Message, ParseMode, Update},
+    utils::command::BotCommands,
 };
 
-use crate::database::Database;
-use crate::types::{AtomicHandler, DeleteIttBot, HandlerResult, Locale, Localization};
+use crate::database::Federation;
+use crate::format::{escape, translate, Markdown};
+use crate::reason::REASONS;
+use crate::storage::{new_token, Store};
+use crate::types::{
+    AtomicHandler, Configuration, ContentKind, DeleteIttBot, Escalation, FederationAction,
+    FingerprintAction, HandlerResult, Locale, Localization, Trigger,
+};
 
-use super::filters::is_privileged;
-use super::utils::{delete_message, get_locale};
+use super::federations::{chat_federation, federation_ban, send_token, MAX_FEDERATION_NAME_LEN};
+use super::filters::{is_privileged, user_privileged};
+use super::patterns::{compile, PatternCache, MAX_PATTERNS, MAX_PATTERN_LEN};
+use super::permissions::report_missing_rights;
+use super::strikes::{strike_window, strikes_target};
+use super::utils::{delete_message, ensure_chat, get_locale, now, topic};
 
 #[derive(BotCommands, Clone)]
-#[command(rename = "snake_case")]
+#[command(rename_rule = "snake_case")]
 enum GroupCmd {
     #[command()]
     Help,
@@ -30,10 +40,85 @@ enum GroupCmd {
 
     #[command()]
     PollDeleteDelay { delay: i64 },
+
+    #[command(parse_with = "split")]
+    Trigger { trigger: String, state: String },
+
+    #[command(parse_with = "split")]
+    ReasonVoteCount { reason: String, count: i64 },
+
+    #[command(parse_with = "split")]
+    TypeVoteCount { kind: String, count: i64 },
+
+    #[command()]
+    TopicVoteCount { count: i64 },
+
+    #[command()]
+    Topic { state: String },
+
+    #[command()]
+    InitiatorVotes { state: String },
+
+    #[command()]
+    ExcludeBots { state: String },
+
+    #[command()]
+    VoterMinAge { hours: i64 },
+
+    #[command()]
+    VoterMinMessages { count: i64 },
+
+    #[command()]
+    Check,
+
+    #[command(parse_with = "split")]
+    PollRate { count: i64, minutes: i64 },
+
+    #[command()]
+    MaxOpenPolls { count: i64 },
+
+    #[command()]
+    Strikes { args: String },
+
+    #[command()]
+    StrikeWindow { days: i64 },
+
+    #[command(parse_with = "split")]
+    Escalation { step: String, count: i64 },
+
+    #[command()]
+    Fingerprints { action: String },
+
+    #[command()]
+    ShareFingerprints { state: String },
+
+    #[command()]
+    Filter { args: String },
+
+    #[command()]
+    Fed { args: String },
+
+    #[command()]
+    UndoWindow { minutes: i64 },
+
+    #[command()]
+    AppealChat { target: String },
 }
 
+/// Longest wait, in hours, accepted by `/voter_min_age`.
+const MAX_VOTER_MIN_AGE_HOURS: i64 = 365 * 24;
+
+/// Longest window, in minutes, accepted by `/poll_rate`.
+const MAX_POLL_RATE_MINUTES: i64 = 24 * 60;
+
+/// Longest window, in days, accepted by `/strike_window`.
+const MAX_STRIKE_WINDOW_DAYS: i64 = 365;
+
+/// Longest window, in minutes, accepted by `/undo_window`.
+const MAX_UNDO_WINDOW_MINUTES: i64 = 24 * 60;
+
 #[derive(BotCommands, Clone)]
-#[command(rename = "snake_case")]
+#[command(rename_rule = "snake_case")]
 enum PersonalCmd {
     #[command()]
     Start,
@@ -51,21 +136,18 @@ where
         )
         .unwrap_or_else(|_| "".into());
 
-    format!(
-        "/{} — _{}_",
-        markdown_escape(&command.into()),
-        markdown_escape(&response)
-    )
+    format!("/{} — _{}_", escape(&command.into()), escape(&response))
 }
 
 async fn help_handler(
     bot: &DeleteIttBot,
     msg: &Message,
-    db: &Database,
+    db: &Store,
+    config: &Configuration,
     loc: &Localization,
 ) -> HandlerResult {
     let chat_id = msg.chat.id.0;
-    let locale = &get_locale(db, chat_id).await;
+    let locale = &get_locale(db, config, chat_id).await;
 
     let response = vec![
         "help",
@@ -73,6 +155,28 @@ async fn help_handler(
         "language",
         "languages",
         "poll_delete_delay",
+        "trigger",
+        "reason_vote_count",
+        "type_vote_count",
+        "topic_vote_count",
+        "topic",
+        "initiator_votes",
+        "exclude_bots",
+        "voter_min_age",
+        "voter_min_messages",
+        "poll_rate",
+        "max_open_polls",
+        "strikes",
+        "strike_window",
+        "escalation",
+        "fingerprints",
+        "share_fingerprints",
+        "filter",
+        "fed",
+        "undo_window",
+        "appeal_chat",
+        "check",
+        "delete",
     ]
     .into_iter()
     .map(|s| format_help_command(locale, s, loc))
@@ -89,12 +193,18 @@ async fn help_handler(
 async fn votes_count_handler(
     bot: &DeleteIttBot,
     msg: &Message,
-    db: &Database,
+    db: &Store,
+    config: &Configuration,
     loc: &Localization,
     count: i64,
 ) -> HandlerResult {
-    if count <= 0 || count > 10 {
-        bot.send_message(msg.chat.id, "Count must be in range of 1 to 10")
+    if !config.vote_count.contains(count) {
+        let response = format!(
+            "Count must be in range of {} to {}",
+            config.vote_count.min, config.vote_count.max
+        );
+
+        bot.send_message(msg.chat.id, response)
             .reply_to_message_id(msg.id)
             .await?;
 
@@ -103,16 +213,14 @@ async fn votes_count_handler(
 
     let chat_id = msg.chat.id.0;
 
-    if let Ok(None) = db.get_chat(chat_id).await {
-        db.create_chat(chat_id).await?;
-    }
+    ensure_chat(db, config, chat_id).await?;
 
     if let Ok(true) = db.set_chat_votes(chat_id, count).await {
         let response = loc.t(
             "vote_count.updated",
             Opts::default()
                 .var("count", count)
-                .locale(&get_locale(db, chat_id).await),
+                .locale(&get_locale(db, config, chat_id).await),
         )?;
 
         bot.send_message(msg.chat.id, response).await?;
@@ -124,7 +232,8 @@ async fn votes_count_handler(
 async fn language_handler(
     bot: &DeleteIttBot,
     msg: &Message,
-    db: &Database,
+    db: &Store,
+    config: &Configuration,
     loc: &Localization,
     lang: Locale,
     locales: &[Locale],
@@ -138,16 +247,14 @@ async fn language_handler(
 
     let chat_id = msg.chat.id.0;
 
-    if let Ok(None) = db.get_chat(chat_id).await {
-        db.create_chat(chat_id).await?;
-    }
+    ensure_chat(db, config, chat_id).await?;
 
     if let Ok(true) = db.set_chat_locale(chat_id, &lang).await {
-        let response = loc.t(
+        let response = translate(
+            loc,
             "language.updated",
-            Opts::default()
-                .var("language", lang)
-                .locale(&get_locale(db, chat_id).await),
+            &get_locale(db, config, chat_id).await,
+            &[("language", Markdown::text(lang))],
         )?;
 
         bot.send_message(msg.chat.id, response)
@@ -161,16 +268,21 @@ async fn language_handler(
 async fn languages_handler(
     bot: &DeleteIttBot,
     msg: &Message,
-    db: &Database,
+    db: &Store,
+    config: &Configuration,
     loc: &Localization,
     locales: &[Locale],
 ) -> HandlerResult {
     let title = loc.t(
         "language.list_title",
-        Opts::default().locale(&get_locale(db, msg.chat.id.0).await),
+        Opts::default().locale(&get_locale(db, config, msg.chat.id.0).await),
     )?;
 
-    let response = format!("*{}*\n{}", title, locales.join(" "));
+    let response = format!(
+        "{}\n{}",
+        Markdown::text(title).bold(),
+        Markdown::text(locales.join(" "))
+    );
 
     bot.send_message(msg.chat.id, response)
         .parse_mode(ParseMode::MarkdownV2)
@@ -182,17 +294,20 @@ async fn languages_handler(
 async fn poll_delete_delay_handler(
     bot: &DeleteIttBot,
     msg: &Message,
-    db: &Database,
+    db: &Store,
+    config: &Configuration,
     loc: &Localization,
     delay: i64,
 ) -> HandlerResult {
     let chat_id = msg.chat.id.0;
-    let locale = get_locale(db, chat_id).await;
+    let locale = get_locale(db, config, chat_id).await;
 
-    if delay > 60 {
+    if delay > config.poll_delete_delay.max {
         let response = loc.t(
             "poll_delete_delay.should_maximum",
-            Opts::default().var("delay", 60).locale(&locale),
+            Opts::default()
+                .var("delay", config.poll_delete_delay.max)
+                .locale(&locale),
         )?;
 
         bot.send_message(msg.chat.id, response).await?;
@@ -200,10 +315,12 @@ async fn poll_delete_delay_handler(
         return Ok(());
     }
 
-    if delay < 5 {
+    if delay < config.poll_delete_delay.min {
         let response = loc.t(
             "poll_delete_delay.should_minimum",
-            Opts::default().var("delay", 5).locale(&locale),
+            Opts::default()
+                .var("delay", config.poll_delete_delay.min)
+                .locale(&locale),
         )?;
 
         bot.send_message(msg.chat.id, response).await?;
@@ -211,14 +328,14 @@ async fn poll_delete_delay_handler(
         return Ok(());
     }
 
-    if let Ok(None) = db.get_chat(chat_id).await {
-        db.create_chat(chat_id).await?;
-    }
+    ensure_chat(db, config, chat_id).await?;
 
     if let Ok(true) = db.set_chat_poll_delete_delay(chat_id, delay).await {
-        let response = loc.t(
+        let response = translate(
+            loc,
             "poll_delete_delay.updated",
-            Opts::default().var("delay", delay).locale(&locale),
+            &locale,
+            &[("delay", Markdown::text(delay.to_string()))],
         )?;
 
         bot.send_message(msg.chat.id, response)
@@ -229,68 +346,1396 @@ async fn poll_delete_delay_handler(
     Ok(())
 }
 
-async fn group_handler(
-    bot: DeleteIttBot,
-    msg: Message,
-    command: GroupCmd,
-    db: Database,
-    loc: Localization,
-    locales: Vec<Locale>,
+fn parse_switch(state: &str) -> Option<bool> {
+    match state {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn format_switch(loc: &Localization, locale: &str, enabled: bool) -> loon::err::Result<String> {
+    let key = if enabled { "switch.on" } else { "switch.off" };
+
+    loc.t(key, Opts::default().locale(locale))
+}
+
+async fn trigger_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    trigger: String,
+    state: String,
 ) -> HandlerResult {
-    match command {
-        GroupCmd::Help => help_handler(&bot, &msg, &db, &loc).await,
-        GroupCmd::VoteCount { count } => votes_count_handler(&bot, &msg, &db, &loc, count).await,
-        GroupCmd::Language { lang } => {
-            language_handler(&bot, &msg, &db, &loc, lang, &locales).await
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    let t = match trigger.as_str() {
+        "mention" => Some(Trigger::Mention),
+        "command" => Some(Trigger::Command),
+        _ => None,
+    };
+
+    let (t, enabled) = match (t, parse_switch(&state)) {
+        (Some(t), Some(enabled)) => (t, enabled),
+        _ => {
+            let response = loc.t("trigger.invalid", Opts::default().locale(&locale))?;
+
+            bot.send_message(msg.chat.id, response).await?;
+
+            return Ok(());
         }
-        GroupCmd::Languages => languages_handler(&bot, &msg, &db, &loc, &locales).await,
-        GroupCmd::PollDeleteDelay { delay } => {
-            poll_delete_delay_handler(&bot, &msg, &db, &loc, delay).await
+    };
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_trigger(chat_id, t, enabled).await {
+        let response = loc.t(
+            "trigger.updated",
+            Opts::default()
+                .var("trigger", trigger)
+                .var("state", format_switch(loc, &locale, enabled)?)
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+async fn reason_vote_count_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    reason: String,
+    count: i64,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+    let reason = reason.to_lowercase();
+
+    if !REASONS.contains(&reason.as_str()) {
+        let response = loc.t(
+            "reason.invalid",
+            Opts::default()
+                .var("reason", reason)
+                .var("reasons", REASONS.join(", "))
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+
+        return Ok(());
+    }
+
+    if count == 0 {
+        db.remove_reason_votes(chat_id, &reason).await?;
+
+        let response = loc.t(
+            "reason.vote_count_removed",
+            Opts::default().var("reason", reason).locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+
+        return Ok(());
+    }
+
+    if !config.vote_count.contains(count) {
+        let response = format!(
+            "Count must be in range of {} to {}",
+            config.vote_count.min, config.vote_count.max
+        );
+
+        bot.send_message(msg.chat.id, response)
+            .reply_to_message_id(msg.id)
+            .await?;
+
+        return Ok(());
+    }
+
+    if let Ok(true) = db.set_reason_votes(chat_id, &reason, count).await {
+        let response = loc.t(
+            "reason.vote_count_updated",
+            Opts::default()
+                .var("reason", reason)
+                .var("count", count)
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+async fn type_vote_count_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    kind: String,
+    count: i64,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    let kind = match ContentKind::parse(&kind.to_lowercase()) {
+        Some(kind) => kind,
+        None => {
+            let response = loc.t("content_type.invalid", Opts::default().locale(&locale))?;
+
+            bot.send_message(msg.chat.id, response).await?;
+
+            return Ok(());
         }
+    };
+
+    if count == 0 {
+        db.remove_type_votes(chat_id, kind).await?;
+
+        let response = loc.t(
+            "content_type.vote_count_removed",
+            Opts::default().var("type", kind.as_str()).locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+
+        return Ok(());
+    }
+
+    if !config.vote_count.contains(count) {
+        let response = format!(
+            "Count must be in range of {} to {}",
+            config.vote_count.min, config.vote_count.max
+        );
+
+        bot.send_message(msg.chat.id, response)
+            .reply_to_message_id(msg.id)
+            .await?;
+
+        return Ok(());
+    }
+
+    if let Ok(true) = db.set_type_votes(chat_id, kind, count).await {
+        let response = loc.t(
+            "content_type.vote_count_updated",
+            Opts::default()
+                .var("type", kind.as_str())
+                .var("count", count)
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
     }
+
+    Ok(())
 }
 
-async fn start_handler(bot: &DeleteIttBot, me: &Me, msg: &Message) -> HandlerResult {
-    let start_msg = markdown_escape(&format!(
-        "Hello! I'm {}. I can help you keep your chats clean. Mention me (@{}) in reply to \
-        the message you want to delete. I will then set up a poll, which is used to take a \
-        decision. These decision parameters can be configured on a per-chat basis. See \
-        /help (in a group). \n\n\
-        My code is free and open source, hosted at https://github.com/ssiyad/delete-itt.",
-        me.full_name(),
-        me.username()
-    ));
+/// The forum topic a topic setting was sent in. Elsewhere, tells the sender to use a topic.
+async fn setting_topic(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    loc: &Localization,
+    locale: &str,
+) -> Result<Option<i32>, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(thread_id) = topic(msg) {
+        return Ok(Some(thread_id));
+    }
 
-    bot.send_message(msg.chat.id, start_msg)
-        .disable_web_page_preview(true)
-        .parse_mode(ParseMode::MarkdownV2)
-        .await?;
+    let response = loc.t("topic.outside", Opts::default().locale(locale))?;
+
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(None)
+}
+
+async fn topic_vote_count_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    count: i64,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    let thread_id = match setting_topic(bot, msg, loc, &locale).await? {
+        Some(thread_id) => thread_id,
+        None => return Ok(()),
+    };
+
+    if count != 0 && !config.vote_count.contains(count) {
+        let response = format!(
+            "Count must be in range of {} to {}",
+            config.vote_count.min, config.vote_count.max
+        );
+
+        bot.send_message(msg.chat.id, response)
+            .reply_to_message_id(msg.id)
+            .message_thread_id(thread_id)
+            .await?;
+
+        return Ok(());
+    }
+
+    if let Ok(true) = db.set_topic_votes(chat_id, thread_id, count).await {
+        let response = match count {
+            0 => loc.t("topic.vote_count_removed", Opts::default().locale(&locale))?,
+            _ => loc.t(
+                "topic.vote_count_updated",
+                Opts::default().var("count", count).locale(&locale),
+            )?,
+        };
+
+        bot.send_message(msg.chat.id, response)
+            .message_thread_id(thread_id)
+            .await?;
+    }
 
     Ok(())
 }
 
-async fn peronal_handler(
-    bot: DeleteIttBot,
-    me: Me,
-    msg: Message,
-    command: PersonalCmd,
+async fn topic_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    state: String,
 ) -> HandlerResult {
-    match command {
-        PersonalCmd::Start => start_handler(&bot, &me, &msg).await,
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    let thread_id = match setting_topic(bot, msg, loc, &locale).await? {
+        Some(thread_id) => thread_id,
+        None => return Ok(()),
+    };
+
+    let enabled = match parse_switch(&state) {
+        Some(enabled) => enabled,
+        None => {
+            let response = loc.t("topic.invalid", Opts::default().locale(&locale))?;
+
+            bot.send_message(msg.chat.id, response)
+                .message_thread_id(thread_id)
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    if let Ok(true) = db.set_topic_enabled(chat_id, thread_id, enabled).await {
+        let response = loc.t(
+            "topic.updated",
+            Opts::default()
+                .var("state", format_switch(loc, &locale, enabled)?)
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response)
+            .message_thread_id(thread_id)
+            .await?;
     }
+
+    Ok(())
 }
 
-pub fn settings_handler() -> AtomicHandler {
-    let private_commands = Update::filter_message()
-        .filter(|msg: Message| msg.chat.is_private())
-        .filter_command::<PersonalCmd>()
-        .endpoint(peronal_handler);
+async fn initiator_votes_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    state: String,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
 
-    let group_commands = Update::filter_message()
-        .filter(|msg: Message| msg.chat.is_group() || msg.chat.is_supergroup())
-        .filter_command::<GroupCmd>()
-        .filter_async(is_privileged)
-        .map_async(delete_message)
+    let enabled = match parse_switch(&state) {
+        Some(enabled) => enabled,
+        None => {
+            let response = loc.t("initiator_votes.invalid", Opts::default().locale(&locale))?;
+
+            bot.send_message(msg.chat.id, response).await?;
+
+            return Ok(());
+        }
+    };
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_initiator_votes(chat_id, enabled).await {
+        let response = loc.t(
+            "initiator_votes.updated",
+            Opts::default()
+                .var("state", format_switch(loc, &locale, enabled)?)
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+async fn exclude_bots_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    state: String,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    let enabled = match parse_switch(&state) {
+        Some(enabled) => enabled,
+        None => {
+            let response = loc.t("exclude_bots.invalid", Opts::default().locale(&locale))?;
+
+            bot.send_message(msg.chat.id, response).await?;
+
+            return Ok(());
+        }
+    };
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_exclude_bots(chat_id, enabled).await {
+        let response = loc.t(
+            "exclude_bots.updated",
+            Opts::default()
+                .var("state", format_switch(loc, &locale, enabled)?)
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+async fn voter_min_age_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    hours: i64,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    if !(0..=MAX_VOTER_MIN_AGE_HOURS).contains(&hours) {
+        let response = loc.t(
+            "voter_min_age.invalid",
+            Opts::default()
+                .var("hours", MAX_VOTER_MIN_AGE_HOURS)
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+
+        return Ok(());
+    }
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_voter_min_age(chat_id, hours * 3600).await {
+        let response = loc.t(
+            "voter_min_age.updated",
+            Opts::default().var("hours", hours).locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+async fn voter_min_messages_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    count: i64,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    if count < 0 {
+        let response = loc.t(
+            "voter_min_messages.invalid",
+            Opts::default().locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+
+        return Ok(());
+    }
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_voter_min_messages(chat_id, count).await {
+        let response = loc.t(
+            "voter_min_messages.updated",
+            Opts::default().var("count", count).locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+async fn poll_rate_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    count: i64,
+    minutes: i64,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    if count < 0 || !(1..=MAX_POLL_RATE_MINUTES).contains(&minutes) {
+        let response = loc.t(
+            "poll_rate.invalid",
+            Opts::default()
+                .var("minutes", MAX_POLL_RATE_MINUTES)
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+
+        return Ok(());
+    }
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_poll_rate(chat_id, count, minutes * 60).await {
+        let response = if count == 0 {
+            loc.t("poll_rate.removed", Opts::default().locale(&locale))?
+        } else {
+            loc.t(
+                "poll_rate.updated",
+                Opts::default()
+                    .var("count", count)
+                    .var("minutes", minutes)
+                    .locale(&locale),
+            )?
+        };
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+async fn max_open_polls_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    count: i64,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    if count < 0 {
+        let response = loc.t("max_open_polls.invalid", Opts::default().locale(&locale))?;
+
+        bot.send_message(msg.chat.id, response).await?;
+
+        return Ok(());
+    }
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_max_open_polls(chat_id, count).await {
+        let response = loc.t(
+            "max_open_polls.updated",
+            Opts::default().var("count", count).locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+async fn strikes_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    args: String,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    let (user_id, name) = match strikes_target(db, msg, &args).await {
+        Some(target) => target,
+        None => {
+            let response = loc.t("strikes.invalid", Opts::default().locale(&locale))?;
+
+            bot.send_message(msg.chat.id, response).await?;
+
+            return Ok(());
+        }
+    };
+
+    let response = if args.split_whitespace().any(|a| a == "reset") {
+        db.remove_strikes(chat_id, user_id).await?;
+
+        translate(loc, "strikes.reset", &locale, &[("name", name)])?
+    } else {
+        let window = strike_window(db, chat_id).await;
+        let count = db.count_strikes(chat_id, user_id, now() - window).await?;
+
+        translate(
+            loc,
+            "strikes.count",
+            &locale,
+            &[
+                ("name", name),
+                ("count", Markdown::text(count.to_string())),
+                ("days", Markdown::text((window / 86400).to_string())),
+            ],
+        )?
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn strike_window_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    days: i64,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    if !(1..=MAX_STRIKE_WINDOW_DAYS).contains(&days) {
+        let response = loc.t(
+            "strike_window.invalid",
+            Opts::default()
+                .var("days", MAX_STRIKE_WINDOW_DAYS)
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+
+        return Ok(());
+    }
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_strike_window(chat_id, days * 86400).await {
+        let response = loc.t(
+            "strike_window.updated",
+            Opts::default().var("days", days).locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+async fn undo_window_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    minutes: i64,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    if !(0..=MAX_UNDO_WINDOW_MINUTES).contains(&minutes) {
+        let response = loc.t(
+            "undo_window.invalid",
+            Opts::default()
+                .var("minutes", MAX_UNDO_WINDOW_MINUTES)
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+
+        return Ok(());
+    }
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_undo_window(chat_id, minutes * 60).await {
+        let response = if minutes > 0 {
+            loc.t(
+                "undo_window.updated",
+                Opts::default().var("minutes", minutes).locale(&locale),
+            )?
+        } else {
+            loc.t("undo_window.disabled", Opts::default().locale(&locale))?
+        };
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+/// Whether both the sender of `msg` and the bot are admins of `chat_id`, so that appeals can
+/// be sent there.
+async fn can_relay_to(bot: &DeleteIttBot, msg: &Message, chat_id: i64) -> bool {
+    let (from, me) = match (msg.from(), bot.get_me().send().await) {
+        (Some(from), Ok(me)) => (from, me),
+        _ => return false,
+    };
+
+    user_privileged(bot, ChatId(chat_id), from.id).await
+        && user_privileged(bot, ChatId(chat_id), me.id).await
+}
+
+async fn appeal_chat_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    target: String,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    ensure_chat(db, config, chat_id).await?;
+
+    let response = match target.trim() {
+        "off" => {
+            db.set_chat_appeal_chat(chat_id, None).await?;
+
+            loc.t("appeal_chat.removed", Opts::default().locale(&locale))?
+        }
+        target => match target.parse::<i64>() {
+            Ok(appeal_chat_id) if can_relay_to(bot, msg, appeal_chat_id).await => {
+                db.set_chat_appeal_chat(chat_id, Some(appeal_chat_id))
+                    .await?;
+
+                loc.t(
+                    "appeal_chat.updated",
+                    Opts::default().var("chat", appeal_chat_id).locale(&locale),
+                )?
+            }
+            _ => loc.t("appeal_chat.invalid", Opts::default().locale(&locale))?,
+        },
+    };
+
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+async fn escalation_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    step: String,
+    count: i64,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    let escalation = match Escalation::parse(&step) {
+        Some(e) if count >= 0 => e,
+        _ => {
+            let response = loc.t("escalation.invalid", Opts::default().locale(&locale))?;
+
+            bot.send_message(msg.chat.id, response).await?;
+
+            return Ok(());
+        }
+    };
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_escalation(chat_id, escalation, count).await {
+        let step = loc.t(
+            format!("escalation.{}", step).as_str(),
+            Opts::default().locale(&locale),
+        )?;
+        let response = if count == 0 {
+            loc.t(
+                "escalation.removed",
+                Opts::default().var("step", step).locale(&locale),
+            )?
+        } else {
+            loc.t(
+                "escalation.updated",
+                Opts::default()
+                    .var("step", step)
+                    .var("count", count)
+                    .locale(&locale),
+            )?
+        };
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+async fn fingerprints_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    action: String,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    let action = match action.as_str() {
+        "off" => None,
+        name => match FingerprintAction::parse(name) {
+            Some(a) => Some(a),
+            None => {
+                let response = loc.t("fingerprints.invalid", Opts::default().locale(&locale))?;
+
+                bot.send_message(msg.chat.id, response).await?;
+
+                return Ok(());
+            }
+        },
+    };
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_fingerprint_action(chat_id, action).await {
+        let response = match action {
+            Some(a) => loc.t(
+                "fingerprints.updated",
+                Opts::default()
+                    .var(
+                        "action",
+                        loc.t(
+                            format!("fingerprints.{}", a.as_str()).as_str(),
+                            Opts::default().locale(&locale),
+                        )?,
+                    )
+                    .locale(&locale),
+            )?,
+            None => loc.t("fingerprints.removed", Opts::default().locale(&locale))?,
+        };
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+async fn share_fingerprints_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    state: String,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    let enabled = match parse_switch(&state) {
+        Some(enabled) => enabled,
+        None => {
+            let response = loc.t(
+                "share_fingerprints.invalid",
+                Opts::default().locale(&locale),
+            )?;
+
+            bot.send_message(msg.chat.id, response).await?;
+
+            return Ok(());
+        }
+    };
+
+    ensure_chat(db, config, chat_id).await?;
+
+    if let Ok(true) = db.set_chat_share_fingerprints(chat_id, enabled).await {
+        let response = loc.t(
+            "share_fingerprints.updated",
+            Opts::default()
+                .var("state", format_switch(loc, &locale, enabled)?)
+                .locale(&locale),
+        )?;
+
+        bot.send_message(msg.chat.id, response).await?;
+    }
+
+    Ok(())
+}
+
+/// Handles `/filter`, which gets a branch of its own as it also needs the pattern cache.
+async fn filter_handler(
+    bot: DeleteIttBot,
+    msg: Message,
+    args: String,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+    cache: PatternCache,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(&db, &config, chat_id).await;
+
+    let (action, pattern) = match args.trim().split_once(char::is_whitespace) {
+        Some((action, pattern)) => (action, pattern.trim()),
+        None => (args.trim(), ""),
+    };
+
+    let response = match (action, pattern) {
+        ("list", _) => {
+            let patterns = db.get_patterns(chat_id).await?;
+
+            if patterns.is_empty() {
+                loc.t("filter.empty", Opts::default().locale(&locale))?
+            } else {
+                let strict = loc.t("filter.strict", Opts::default().locale(&locale))?;
+                let lines = patterns
+                    .iter()
+                    .map(|p| {
+                        if p.strict {
+                            format!("{} ({})", p.pattern, strict)
+                        } else {
+                            p.pattern.clone()
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n");
+
+                format!(
+                    "{}\n{}",
+                    loc.t("filter.list_title", Opts::default().locale(&locale))?,
+                    lines
+                )
+            }
+        }
+        ("add" | "strict", pattern) if !pattern.is_empty() => {
+            let known = db.get_patterns(chat_id).await?;
+
+            if compile(pattern).is_none() {
+                loc.t(
+                    "filter.bad_pattern",
+                    Opts::default()
+                        .var("length", MAX_PATTERN_LEN)
+                        .locale(&locale),
+                )?
+            } else if known.len() >= MAX_PATTERNS && !known.iter().any(|p| p.pattern == pattern) {
+                loc.t(
+                    "filter.too_many",
+                    Opts::default().var("count", MAX_PATTERNS).locale(&locale),
+                )?
+            } else {
+                let strict = action == "strict";
+
+                db.create_pattern(chat_id, pattern, strict).await?;
+                cache.forget(chat_id);
+
+                loc.t(
+                    if strict {
+                        "filter.added_strict"
+                    } else {
+                        "filter.added"
+                    },
+                    Opts::default().var("pattern", pattern).locale(&locale),
+                )?
+            }
+        }
+        ("remove", pattern) if !pattern.is_empty() => {
+            let key = if db.remove_pattern(chat_id, pattern).await? {
+                cache.forget(chat_id);
+
+                "filter.removed"
+            } else {
+                "filter.not_found"
+            };
+
+            loc.t(key, Opts::default().var("pattern", pattern).locale(&locale))?
+        }
+        _ => loc.t("filter.invalid", Opts::default().locale(&locale))?,
+    };
+
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+fn format_federation_action(
+    loc: &Localization,
+    locale: &str,
+    action: FederationAction,
+) -> loon::err::Result<String> {
+    loc.t(
+        format!("federation.action_{}", action.as_str()).as_str(),
+        Opts::default().locale(locale),
+    )
+}
+
+/// Sends the federation's join token to the admin who sent `msg` and says whether that worked.
+async fn token_notice(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    loc: &Localization,
+    locale: &str,
+    federation: &Federation,
+) -> Result<Markdown, Box<dyn std::error::Error + Send + Sync>> {
+    let key = if send_token(bot, msg, loc, locale, federation).await? {
+        "federation.token_sent"
+    } else {
+        "federation.token_failed"
+    };
+
+    Ok(translate(
+        loc,
+        key,
+        locale,
+        &[("name", Markdown::text(&federation.name))],
+    )?)
+}
+
+async fn fed_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    args: String,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let locale = get_locale(db, config, chat_id).await;
+
+    let (action, rest) = match args.trim().split_once(char::is_whitespace) {
+        Some((action, rest)) => (action, rest.trim()),
+        None => (args.trim(), ""),
+    };
+
+    ensure_chat(db, config, chat_id).await?;
+    let federation = chat_federation(db, chat_id).await?;
+
+    let response = match (action, rest, federation) {
+        ("", _, None) => translate(loc, "federation.none", &locale, &[])?,
+        ("", _, Some(federation)) => {
+            let chat = db.get_chat(chat_id).await?.unwrap();
+            let handled = format_federation_action(loc, &locale, chat.federation_action())?;
+            let key = if federation.owner_chat_id == chat_id {
+                "federation.info_owner"
+            } else {
+                "federation.info"
+            };
+
+            translate(
+                loc,
+                key,
+                &locale,
+                &[
+                    ("name", Markdown::text(&federation.name)),
+                    ("action", Markdown::text(handled)),
+                ],
+            )?
+        }
+        ("new" | "join", _, Some(federation)) => translate(
+            loc,
+            "federation.already",
+            &locale,
+            &[("name", Markdown::text(&federation.name))],
+        )?,
+        ("new", name, None) if !name.is_empty() => {
+            if name.chars().count() > MAX_FEDERATION_NAME_LEN {
+                translate(
+                    loc,
+                    "federation.name_too_long",
+                    &locale,
+                    &[(
+                        "length",
+                        Markdown::text(MAX_FEDERATION_NAME_LEN.to_string()),
+                    )],
+                )?
+            } else {
+                let token = new_token();
+
+                db.create_federation(name, chat_id, &token).await?;
+
+                let federation = db.get_federation_by_token(&token).await?.unwrap();
+                db.set_chat_federation(chat_id, Some(federation.id)).await?;
+
+                translate(
+                    loc,
+                    "federation.created",
+                    &locale,
+                    &[
+                        ("name", Markdown::text(name)),
+                        (
+                            "notice",
+                            token_notice(bot, msg, loc, &locale, &federation).await?,
+                        ),
+                    ],
+                )?
+            }
+        }
+        ("join", token, None) if !token.is_empty() => {
+            match db.get_federation_by_token(token).await? {
+                Some(federation) => {
+                    db.set_chat_federation(chat_id, Some(federation.id)).await?;
+
+                    // Tell the owner, who can remove chats that should not have joined.
+                    let owner_locale = get_locale(db, config, federation.owner_chat_id).await;
+                    let notice = translate(
+                        loc,
+                        "federation.member_joined",
+                        &owner_locale,
+                        &[
+                            ("chat", Markdown::chat(&msg.chat)),
+                            ("id", Markdown::text(chat_id.to_string())),
+                            ("name", Markdown::text(&federation.name)),
+                        ],
+                    )?;
+
+                    bot.send_message(ChatId(federation.owner_chat_id), notice)
+                        .parse_mode(ParseMode::MarkdownV2)
+                        .await
+                        .ok();
+
+                    translate(
+                        loc,
+                        "federation.joined",
+                        &locale,
+                        &[("name", Markdown::text(&federation.name))],
+                    )?
+                }
+                None => translate(loc, "federation.not_found", &locale, &[])?,
+            }
+        }
+        ("leave", _, Some(federation)) => {
+            db.set_chat_federation(chat_id, None).await?;
+
+            // Nobody would be left to hand out or rotate the token.
+            if federation.owner_chat_id == chat_id {
+                db.set_federation_token(federation.id, &new_token()).await?;
+            }
+
+            translate(
+                loc,
+                "federation.left",
+                &locale,
+                &[("name", Markdown::text(&federation.name))],
+            )?
+        }
+        ("action", name, Some(_)) if FederationAction::parse(name).is_some() => {
+            let action = FederationAction::parse(name).unwrap();
+
+            db.set_chat_federation_action(chat_id, action).await?;
+
+            translate(
+                loc,
+                "federation.action_updated",
+                &locale,
+                &[(
+                    "action",
+                    Markdown::text(format_federation_action(loc, &locale, action)?),
+                )],
+            )?
+        }
+        ("token" | "rotate" | "kick" | "ban" | "unban", _, Some(federation))
+            if federation.owner_chat_id != chat_id =>
+        {
+            translate(
+                loc,
+                "federation.owner_only",
+                &locale,
+                &[("name", Markdown::text(&federation.name))],
+            )?
+        }
+        ("token", _, Some(federation)) => token_notice(bot, msg, loc, &locale, &federation).await?,
+        ("rotate", _, Some(federation)) => {
+            let federation = Federation {
+                token: new_token(),
+                ..federation
+            };
+
+            db.set_federation_token(federation.id, &federation.token)
+                .await?;
+
+            translate(
+                loc,
+                "federation.rotated",
+                &locale,
+                &[
+                    ("name", Markdown::text(&federation.name)),
+                    (
+                        "notice",
+                        token_notice(bot, msg, loc, &locale, &federation).await?,
+                    ),
+                ],
+            )?
+        }
+        ("kick", target, Some(federation)) => {
+            let member = match target.parse::<i64>() {
+                Ok(target) if target != chat_id => db.get_chat(target).await?,
+                _ => None,
+            };
+
+            match member {
+                Some(member) if member.federation_id == Some(federation.id) => {
+                    db.set_chat_federation(member.chat_id, None).await?;
+
+                    let notice = translate(
+                        loc,
+                        "federation.kicked_notice",
+                        &member.locale,
+                        &[("name", Markdown::text(&federation.name))],
+                    )?;
+
+                    bot.send_message(ChatId(member.chat_id), notice)
+                        .parse_mode(ParseMode::MarkdownV2)
+                        .await
+                        .ok();
+
+                    translate(
+                        loc,
+                        "federation.kicked",
+                        &locale,
+                        &[
+                            ("id", Markdown::text(member.chat_id.to_string())),
+                            ("name", Markdown::text(&federation.name)),
+                        ],
+                    )?
+                }
+                _ => translate(
+                    loc,
+                    "federation.not_member",
+                    &locale,
+                    &[("name", Markdown::text(&federation.name))],
+                )?,
+            }
+        }
+        ("ban" | "unban", target, Some(federation)) => {
+            match strikes_target(db, msg, target).await {
+                Some((user_id, name)) if action == "ban" => {
+                    federation_ban(bot, db, loc, chat_id, user_id, &name).await?;
+
+                    translate(
+                        loc,
+                        "federation.banned",
+                        &locale,
+                        &[
+                            ("name", name),
+                            ("federation", Markdown::text(&federation.name)),
+                        ],
+                    )?
+                }
+                Some((user_id, name)) => {
+                    let key = if db.remove_federation_ban(federation.id, user_id).await? {
+                        "federation.unbanned"
+                    } else {
+                        "federation.not_banned"
+                    };
+
+                    translate(
+                        loc,
+                        key,
+                        &locale,
+                        &[
+                            ("name", name),
+                            ("federation", Markdown::text(&federation.name)),
+                        ],
+                    )?
+                }
+                None => translate(loc, "federation.invalid", &locale, &[])?,
+            }
+        }
+        ("leave" | "action" | "token" | "rotate" | "kick" | "ban" | "unban", _, None) => {
+            translate(loc, "federation.none", &locale, &[])?
+        }
+        _ => translate(loc, "federation.invalid", &locale, &[])?,
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn check_handler(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+) -> HandlerResult {
+    if !report_missing_rights(bot, msg.chat.id, topic(msg), db, config, loc).await? {
+        let response = loc.t(
+            "permissions.ok",
+            Opts::default().locale(&get_locale(db, config, msg.chat.id.0).await),
+        )?;
+
+        bot.send_message(msg.chat.id, response)
+            .with_payload_mut(|p| p.message_thread_id = topic(msg))
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn group_handler(
+    bot: DeleteIttBot,
+    msg: Message,
+    command: GroupCmd,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+    locales: Vec<Locale>,
+) -> HandlerResult {
+    match command {
+        GroupCmd::Help => help_handler(&bot, &msg, &db, &config, &loc).await,
+        GroupCmd::VoteCount { count } => {
+            votes_count_handler(&bot, &msg, &db, &config, &loc, count).await
+        }
+        GroupCmd::Language { lang } => {
+            language_handler(&bot, &msg, &db, &config, &loc, lang, &locales).await
+        }
+        GroupCmd::Languages => languages_handler(&bot, &msg, &db, &config, &loc, &locales).await,
+        GroupCmd::PollDeleteDelay { delay } => {
+            poll_delete_delay_handler(&bot, &msg, &db, &config, &loc, delay).await
+        }
+        GroupCmd::Trigger { trigger, state } => {
+            trigger_handler(&bot, &msg, &db, &config, &loc, trigger, state).await
+        }
+        GroupCmd::ReasonVoteCount { reason, count } => {
+            reason_vote_count_handler(&bot, &msg, &db, &config, &loc, reason, count).await
+        }
+        GroupCmd::TypeVoteCount { kind, count } => {
+            type_vote_count_handler(&bot, &msg, &db, &config, &loc, kind, count).await
+        }
+        GroupCmd::TopicVoteCount { count } => {
+            topic_vote_count_handler(&bot, &msg, &db, &config, &loc, count).await
+        }
+        GroupCmd::Topic { state } => topic_handler(&bot, &msg, &db, &config, &loc, state).await,
+        GroupCmd::InitiatorVotes { state } => {
+            initiator_votes_handler(&bot, &msg, &db, &config, &loc, state).await
+        }
+        GroupCmd::ExcludeBots { state } => {
+            exclude_bots_handler(&bot, &msg, &db, &config, &loc, state).await
+        }
+        GroupCmd::VoterMinAge { hours } => {
+            voter_min_age_handler(&bot, &msg, &db, &config, &loc, hours).await
+        }
+        GroupCmd::VoterMinMessages { count } => {
+            voter_min_messages_handler(&bot, &msg, &db, &config, &loc, count).await
+        }
+        GroupCmd::Check => check_handler(&bot, &msg, &db, &config, &loc).await,
+        GroupCmd::PollRate { count, minutes } => {
+            poll_rate_handler(&bot, &msg, &db, &config, &loc, count, minutes).await
+        }
+        GroupCmd::MaxOpenPolls { count } => {
+            max_open_polls_handler(&bot, &msg, &db, &config, &loc, count).await
+        }
+        GroupCmd::Strikes { args } => strikes_handler(&bot, &msg, &db, &config, &loc, args).await,
+        GroupCmd::StrikeWindow { days } => {
+            strike_window_handler(&bot, &msg, &db, &config, &loc, days).await
+        }
+        GroupCmd::Escalation { step, count } => {
+            escalation_handler(&bot, &msg, &db, &config, &loc, step, count).await
+        }
+        GroupCmd::Fingerprints { action } => {
+            fingerprints_handler(&bot, &msg, &db, &config, &loc, action).await
+        }
+        GroupCmd::ShareFingerprints { state } => {
+            share_fingerprints_handler(&bot, &msg, &db, &config, &loc, state).await
+        }
+        // Taken by `filter_handler` before it gets here.
+        GroupCmd::Filter { .. } => Ok(()),
+        GroupCmd::Fed { args } => fed_handler(&bot, &msg, &db, &config, &loc, args).await,
+        GroupCmd::UndoWindow { minutes } => {
+            undo_window_handler(&bot, &msg, &db, &config, &loc, minutes).await
+        }
+        GroupCmd::AppealChat { target } => {
+            appeal_chat_handler(&bot, &msg, &db, &config, &loc, target).await
+        }
+    }
+}
+
+async fn start_handler(bot: &DeleteIttBot, me: &Me, msg: &Message) -> HandlerResult {
+    let start_msg = escape(&format!(
+        "Hello! I'm {}. I can help you keep your chats clean. Mention me (@{}) or send /delete in \
+        reply to the message you want to delete. I will then set up a poll, which is used to take a \
+        decision. These decision parameters can be configured on a per-chat basis. See \
+        /help (in a group). \n\n\
+        My code is free and open source, hosted at https://github.com/ssiyad/delete-itt.",
+        me.full_name(),
+        me.username()
+    ));
+
+    bot.send_message(msg.chat.id, start_msg)
+        .disable_web_page_preview(true)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn peronal_handler(
+    bot: DeleteIttBot,
+    me: Me,
+    msg: Message,
+    command: PersonalCmd,
+) -> HandlerResult {
+    match command {
+        PersonalCmd::Start => start_handler(&bot, &me, &msg).await,
+    }
+}
+
+pub fn settings_handler() -> AtomicHandler {
+    let private_commands = Update::filter_message()
+        .filter(|msg: Message| msg.chat.is_private())
+        .filter_command::<PersonalCmd>()
+        .endpoint(peronal_handler);
+
+    let group_commands = Update::filter_message()
+        .filter(|msg: Message| msg.chat.is_group() || msg.chat.is_supergroup())
+        .filter_command::<GroupCmd>()
+        .filter_async(is_privileged)
+        .map_async(delete_message)
+        .branch(
+            teloxide::dptree::filter_map(|command: GroupCmd| match command {
+                GroupCmd::Filter { args } => Some(args),
+                _ => None,
+            })
+            .endpoint(filter_handler),
+        )
         .endpoint(group_handler);
 
     teloxide::dptree::entry()
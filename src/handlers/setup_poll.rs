@@ -1,72 +1,468 @@
 use loon::Opts;
 use teloxide::{
-    dispatching::UpdateFilterExt,
+    dispatching::{HandlerExt, UpdateFilterExt},
+    dptree,
     payloads::SendMessageSetters,
-    requests::Requester,
-    types::{Message, ParseMode, Update},
+    requests::{HasPayload, Requester},
+    types::{Me, Message, MessageId, ParseMode, Update},
+    utils::command::BotCommands,
 };
 
-use super::filters::target_me;
-use super::utils::{get_locale, update_count};
+use super::filters::{
+    command_enabled, in_enabled_topic, mention_enabled, sender_privileged, target_me, topic_enabled,
+};
+use super::permissions::{missing_rights, rights_list, DELETE_MESSAGES};
+use super::restore::archive;
+use super::utils::{
+    delete_voted_message, format_reason, get_locale, get_poll_delete_delay, get_poll_vote_count,
+    now, topic, update_count, voter_ineligibility,
+};
+
+use crate::content;
+use crate::database::Poll;
+use crate::fingerprint::fingerprints;
+use crate::format::{translate, Markdown};
+use crate::reason;
+use crate::sender::{sender, Sender};
+use crate::storage::Store;
+use crate::types::{
+    AtomicHandler, Configuration, ContentKind, DeleteIttBot, HandlerResult, Localization,
+    PollOrigin, Scope, VoteType,
+};
+
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "snake_case")]
+enum PollCmd {
+    #[command()]
+    Delete,
+}
+
+/// Why `user_id` may not start another poll in `chat_id` right now, ready to be sent, or
+/// `None` if they may.
+async fn poll_refusal(
+    db: &Store,
+    loc: &Localization,
+    locale: &str,
+    chat_id: i64,
+    user_id: i64,
+) -> loon::err::Result<Option<String>> {
+    let chat = match db.get_chat(chat_id).await {
+        Ok(Some(chat)) => chat,
+        _ => return Ok(None),
+    };
+
+    if chat.max_open_polls > 0 {
+        if let Ok(open) = db.count_polls(chat_id).await {
+            if open >= chat.max_open_polls {
+                return loc
+                    .t(
+                        "limits.open_polls",
+                        Opts::default().var("count", open).locale(locale),
+                    )
+                    .map(Some);
+            }
+        }
+    }
+
+    if chat.poll_rate_count > 0 {
+        let since = now() - chat.poll_rate_window;
+
+        if let Ok(started) = db.count_poll_starts(chat_id, user_id, since).await {
+            if started >= chat.poll_rate_count {
+                return loc
+                    .t(
+                        "limits.user_rate",
+                        Opts::default()
+                            .var("count", chat.poll_rate_count)
+                            .var("minutes", chat.poll_rate_window / 60)
+                            .locale(locale),
+                    )
+                    .map(Some);
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Answers a trigger message past the chat's limits, then has both messages removed after the
+/// chat's poll delete delay.
+async fn refuse_poll(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    response: String,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let refusal = bot
+        .send_message(msg.chat.id, response)
+        .reply_to_message_id(msg.id)
+        .with_payload_mut(|p| p.message_thread_id = topic(msg))
+        .await?;
+    let at = now() + get_poll_delete_delay(db, config, chat_id).await;
+
+    db.schedule_message_delete(chat_id, msg.id.0.into(), at)
+        .await?;
+    db.schedule_message_delete(chat_id, refusal.id.0.into(), at)
+        .await?;
+
+    Ok(())
+}
+
+/// Replies to `msg` with `warning`, which is removed after the poll delete delay. The poll is
+/// opened all the same.
+async fn warn_poll(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    warning: String,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let warning = bot
+        .send_message(msg.chat.id, warning)
+        .reply_to_message_id(msg.id)
+        .with_payload_mut(|p| p.message_thread_id = topic(msg))
+        .await?;
+    let at = now() + get_poll_delete_delay(db, config, chat_id).await;
+
+    db.schedule_message_delete(chat_id, warning.id.0.into(), at)
+        .await?;
+
+    Ok(())
+}
+
+/// Points the user at the open poll about the message they replied to instead of starting a
+/// second one. The pointer and the trigger message are removed after the poll delete delay.
+async fn point_at_poll(
+    bot: &DeleteIttBot,
+    msg: &Message,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+    locale: &str,
+    poll_id: i32,
+) -> HandlerResult {
+    let chat_id = msg.chat.id.0;
+    let response = loc.t("vote.exists", Opts::default().locale(locale))?;
+    let pointer = bot
+        .send_message(msg.chat.id, response)
+        .reply_to_message_id(MessageId(poll_id))
+        .with_payload_mut(|p| p.message_thread_id = topic(msg))
+        .await?;
+    let at = now() + get_poll_delete_delay(db, config, chat_id).await;
+
+    bot.delete_message(msg.chat.id, msg.id).await?;
+    db.schedule_message_delete(chat_id, pointer.id.0.into(), at)
+        .await?;
+
+    Ok(())
+}
+
+/// A line naming what `target` is, for anything but text written in the chat itself.
+fn target_line(
+    loc: &Localization,
+    locale: &str,
+    target: &Message,
+) -> loon::err::Result<Option<Markdown>> {
+    let kind = content::kind(target);
+    let source = content::forward_source(target);
+
+    if kind == Some(ContentKind::Text) && source.is_none() {
+        return Ok(None);
+    }
 
-use crate::types::{AtomicHandler, DeleteIttBot, HandlerResult, Localization};
-use crate::Database;
+    let name = loc.t(
+        format!("content_type.{}", kind.map_or("other", |k| k.as_str())).as_str(),
+        Opts::default().locale(locale),
+    )?;
+    let line = match source {
+        Some(source) => translate(
+            loc,
+            "vote.target_forwarded",
+            locale,
+            &[
+                ("type", Markdown::text(name)),
+                ("source", Markdown::text(source)),
+            ],
+        )?,
+        None => translate(
+            loc,
+            "vote.target",
+            locale,
+            &[("type", Markdown::text(name))],
+        )?,
+    };
+
+    Ok(Some(line))
+}
+
+/// What a new poll covers besides its target message, and why it was opened.
+#[derive(Default)]
+pub struct PollOptions<'a> {
+    pub scope: Option<Scope>,
+    pub reason: Option<&'a str>,
+    /// Set for polls the bot opened by itself.
+    pub origin: Option<PollOrigin>,
+}
+
+/// Sends a poll about `target` into its forum topic and stores it along with its options and
+/// the fingerprints of the target. Returns `None` if another poll about `target` was opened in
+/// the meantime, or if polls are off in the topic.
+pub async fn open_poll(
+    bot: &DeleteIttBot,
+    target: &Message,
+    options: PollOptions<'_>,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+) -> Result<Option<Poll>, Box<dyn std::error::Error + Send + Sync>> {
+    let chat_id = target.chat.id.0;
+    let sender = match sender(target) {
+        Some(sender) => sender,
+        None => return Ok(None),
+    };
+    let PollOptions {
+        scope,
+        reason,
+        origin,
+    } = options;
+
+    if !in_enabled_topic(db, target).await {
+        return Ok(None);
+    }
+
+    let locale = get_locale(db, config, chat_id).await;
+    let thread_id = topic(target);
+
+    let kind = content::kind(target);
+    let min_vote_count = get_poll_vote_count(db, config, chat_id, reason, kind, thread_id).await;
+
+    let mut vars = vec![
+        ("count", Markdown::text(min_vote_count.to_string())),
+        ("from_name", sender.name()),
+    ];
+    let key = match reason {
+        Some(r) => {
+            vars.push(("reason", format_reason(loc, &locale, r)));
+            "vote.title_with_reason"
+        }
+        None => "vote.title",
+    };
+
+    let mut response = translate(loc, key, &locale, &vars)?.bold().to_string();
+
+    if let Some(target_line) = target_line(loc, &locale, target)? {
+        response = format!("{}\n{}", response, target_line);
+    }
+
+    if let Some(scope) = scope {
+        let scope_line = match scope {
+            Scope::Messages(count) => translate(
+                loc,
+                "vote.scope_messages",
+                &locale,
+                &[("count", Markdown::text(count.to_string()))],
+            )?,
+            Scope::Minutes(minutes) => translate(
+                loc,
+                "vote.scope_minutes",
+                &locale,
+                &[("minutes", Markdown::text(minutes.to_string()))],
+            )?,
+        };
+
+        response = format!("{}\n{}", response, scope_line);
+    }
+
+    if let Some(origin) = origin {
+        let origin_line = translate(
+            loc,
+            format!("vote.origin_{}", origin.as_str()).as_str(),
+            &locale,
+            &[],
+        )?;
+
+        response = format!("{}\n{}", response, origin_line.italic());
+    }
+
+    let poll_msg = bot
+        .send_message(target.chat.id, response)
+        .reply_to_message_id(target.id)
+        .parse_mode(ParseMode::MarkdownV2)
+        .protect_content(true)
+        .with_payload_mut(|p| p.message_thread_id = thread_id)
+        .await?;
+
+    if let Err(e) = db
+        .create_poll(
+            chat_id,
+            poll_msg.id.0,
+            target.id.0,
+            sender.id(),
+            min_vote_count,
+            reason,
+        )
+        .await
+    {
+        // Another trigger for the same message won the race.
+        bot.delete_message(target.chat.id, poll_msg.id).await?;
+
+        return match db.get_poll_by_message(chat_id, target.id.0).await {
+            Ok(Some(_)) => Ok(None),
+            _ => Err(e.into()),
+        };
+    }
+
+    let poll = match db.get_poll(chat_id, poll_msg.id.0).await? {
+        Some(poll) => poll,
+        None => return Ok(None),
+    };
+
+    if let Some(scope) = scope {
+        db.set_poll_scope(poll.id, scope, target.date.timestamp())
+            .await?;
+    }
+
+    if let Some(origin) = origin {
+        db.set_poll_origin(poll.id, origin).await?;
+    }
+
+    if let Some(thread_id) = thread_id {
+        db.set_poll_thread(poll.id, thread_id).await?;
+    }
+
+    let prints = fingerprints(target);
+    if !prints.is_empty() {
+        db.set_poll_fingerprints(poll.id, &prints).await?;
+    }
+
+    archive(db, target, poll.poll_id).await?;
+
+    Ok(db.get_poll(chat_id, poll_msg.id.0).await?)
+}
 
 async fn setup_poll(
     bot: DeleteIttBot,
+    me: Me,
     msg: Message,
-    db: Database,
+    db: Store,
+    config: Configuration,
     loc: Localization,
 ) -> HandlerResult {
     if let Some(reply_to_message_id) = msg.reply_to_message() {
-        if let Some(from) = reply_to_message_id.from() {
-            if from.is_anonymous() || from.is_channel() {
+        if sender(reply_to_message_id).is_some() {
+            // Admins can not be voted on, even anonymous ones. Chats posting into the group can.
+            if sender_privileged(&bot, reply_to_message_id).await {
                 return Ok(());
             }
 
-            let member = bot.get_chat_member(msg.chat.id, from.id).await?;
-            if member.is_privileged() {
-                return Ok(());
-            };
+            let locale = get_locale(&db, &config, msg.chat.id.0).await;
 
-            bot.delete_message(msg.chat.id, msg.id).await?;
+            let missing = missing_rights(&bot, msg.chat.id, &db).await?;
+            if !missing.is_empty() {
+                let rights = rights_list(&loc, &locale, &missing)?;
 
-            let min_vote_count = db
-                .get_chat_votes(msg.chat.id.0)
+                // Polls can't be resolved without deleting messages. The other rights are only
+                // needed for escalation and federation bans, so the poll goes ahead without them.
+                if missing.contains(&DELETE_MESSAGES) {
+                    let response = loc.t(
+                        "permissions.missing",
+                        Opts::default().var("rights", rights).locale(&locale),
+                    )?;
+
+                    return refuse_poll(&bot, &msg, &db, &config, response).await;
+                }
+
+                let warning = loc.t(
+                    "permissions.missing_ban",
+                    Opts::default().var("rights", rights).locale(&locale),
+                )?;
+                warn_poll(&bot, &msg, &db, &config, warning).await?;
+            }
+
+            if let Ok(Some(existing)) = db
+                .get_poll_by_message(msg.chat.id.0, reply_to_message_id.id.0)
                 .await
-                .unwrap_or(Some(5))
-                .unwrap_or(5);
-
-            let response = loc.t(
-                "vote.title",
-                Opts::default()
-                    .var("count", min_vote_count)
-                    .var(
-                        "from_name",
-                        format!("[{}]({})", from.full_name(), from.url()),
-                    )
-                    .locale(&get_locale(&db, msg.chat.id.0).await),
-            )?;
-
-            let poll_msg = bot
-                .send_message(msg.chat.id, format!("*{}*", response))
-                .reply_to_message_id(reply_to_message_id.id)
-                .parse_mode(ParseMode::MarkdownV2)
-                .protect_content(true)
-                .await?;
-
-            db.create_poll(
-                msg.chat.id.0,
-                poll_msg.id,
-                reply_to_message_id.id,
-                from.id.0.try_into().unwrap(),
-                min_vote_count,
-            )
-            .await?;
+            {
+                return point_at_poll(&bot, &msg, &db, &config, &loc, &locale, existing.poll_id)
+                    .await;
+            }
+
+            if let Some(initiator) = sender(&msg) {
+                if let Some(response) =
+                    poll_refusal(&db, &loc, &locale, msg.chat.id.0, initiator.id()).await?
+                {
+                    if !sender_privileged(&bot, &msg).await {
+                        return refuse_poll(&bot, &msg, &db, &config, response).await;
+                    }
+                }
+            }
+
+            bot.delete_message(msg.chat.id, msg.id).await?;
+
+            let (scope, reason) = match msg
+                .text()
+                .or_else(|| msg.caption())
+                .and_then(|t| reason::parse(me.username(), t))
+            {
+                Some(r) => reason::split_scope(&r),
+                None => (None, None),
+            };
 
-            if let Ok(Some(e)) = db.get_poll(msg.chat.id.0, poll_msg.id).await {
-                update_count(&bot, &e, &db, &loc).await?;
+            let options = PollOptions {
+                scope,
+                reason: reason.as_deref(),
+                origin: None,
+            };
+
+            let poll =
+                match open_poll(&bot, reply_to_message_id, options, &db, &config, &loc).await? {
+                    Some(poll) => poll,
+                    None => return Ok(()),
+                };
+
+            if let Some(initiator) = sender(&msg) {
+                let ts = now();
+                let window = match db.get_chat(msg.chat.id.0).await {
+                    Ok(Some(chat)) => chat.poll_rate_window,
+                    _ => 0,
+                };
+
+                db.create_poll_start(msg.chat.id.0, initiator.id(), ts)
+                    .await?;
+                db.remove_poll_starts(msg.chat.id.0, ts - window).await?;
+            }
+
+            // Only users vote. Chats and anonymous admins starting a poll can not be told apart
+            // from others sending as the same chat.
+            if let (Some(Sender::User(initiator)), Ok(Some(true))) = (
+                sender(&msg),
+                db.get_chat_initiator_votes(msg.chat.id.0).await,
+            ) {
+                if voter_ineligibility(&db, &poll, initiator).await.is_some() {
+                    return update_count(&bot, &poll, &db, &config, &loc).await;
+                }
+
+                db.create_voter(poll.id, initiator.id.0.try_into().unwrap())
+                    .await?;
+                db.register_vote(poll.id, VoteType::Yes).await?;
             }
+
+            if let Ok(Some(e)) = db.get_poll(poll.chat_id, poll.poll_id).await {
+                if e.vote_count_yes >= e.minimum_vote_count {
+                    delete_voted_message(&bot, &me, &e, &db, &config, &loc).await?;
+                } else {
+                    update_count(&bot, &e, &db, &config, &loc).await?;
+                }
+            }
+        } else {
+            // Service messages carry no author to hold a poll about.
+            let locale = get_locale(&db, &config, msg.chat.id.0).await;
+            let response = loc.t("vote.service_message", Opts::default().locale(&locale))?;
+
+            return refuse_poll(&bot, &msg, &db, &config, response).await;
         }
     }
 
@@ -74,8 +470,26 @@ async fn setup_poll(
 }
 
 pub fn setup_poll_handler() -> AtomicHandler {
+    let by_mention = dptree::filter_async(target_me)
+        .filter_async(mention_enabled)
+        .endpoint(setup_poll);
+
+    let by_command = dptree::entry()
+        .filter_command::<PollCmd>()
+        .filter_async(command_enabled)
+        .endpoint(setup_poll);
+
+    // Commands are only read from text, but media can be sent with /delete as its caption.
+    let by_caption_command = dptree::filter_map(|msg: Message, me: Me| {
+        PollCmd::parse(msg.caption()?, me.username()).ok()
+    })
+    .filter_async(command_enabled)
+    .endpoint(setup_poll);
+
     Update::filter_message()
         .filter(|msg: Message| msg.chat.is_group() || msg.chat.is_supergroup())
-        .filter_async(target_me)
-        .endpoint(setup_poll)
+        .filter_async(topic_enabled)
+        .branch(by_command)
+        .branch(by_caption_command)
+        .branch(by_mention)
 }
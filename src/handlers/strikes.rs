@@ -0,0 +1,161 @@
+use chrono::{TimeZone, Utc};
+use teloxide::{
+    payloads::{RestrictChatMemberSetters, UnbanChatMemberSetters},
+    requests::Requester,
+    types::{ChatId, ChatPermissions, Message, MessageEntityKind, UserId},
+};
+
+use super::utils::now;
+use crate::format::Markdown;
+use crate::sender;
+use crate::storage::{Store, DEFAULT_STRIKE_WINDOW};
+use crate::types::{DeleteIttBot, Escalation};
+
+/// Seconds an automatic mute lasts.
+pub const MUTE_DURATION: i64 = 24 * 60 * 60;
+
+/// Seconds strikes in `chat_id` count towards escalation.
+pub async fn strike_window(db: &Store, chat_id: i64) -> i64 {
+    match db.get_chat(chat_id).await {
+        Ok(Some(chat)) => chat.strike_window,
+        _ => DEFAULT_STRIKE_WINDOW,
+    }
+}
+
+/// Gives `user_id` a strike for the message the poll `poll_id` deleted and applies the chat's
+/// escalation rules. Returns the step taken, if any, along with the strike count that led to it.
+/// Chats sending messages can be banned but not muted. A poll that gave its strike already
+/// gives none again.
+pub async fn strike(
+    bot: &DeleteIttBot,
+    db: &Store,
+    chat_id: i64,
+    user_id: i64,
+    poll_id: i32,
+) -> Result<Option<(Escalation, i64)>, Box<dyn std::error::Error + Send + Sync>> {
+    let ts = now();
+    let chat = db.get_chat(chat_id).await?;
+    let window = chat
+        .as_ref()
+        .map_or(DEFAULT_STRIKE_WINDOW, |c| c.strike_window);
+
+    if !db.create_strike(chat_id, user_id, poll_id, ts).await? {
+        return Ok(None);
+    }
+    db.remove_strikes_before(chat_id, ts - window).await?;
+
+    let chat = match chat {
+        Some(chat) => chat,
+        None => return Ok(None),
+    };
+    let count = db.count_strikes(chat_id, user_id, ts - window).await?;
+
+    let step = if chat.ban_after > 0 && count >= chat.ban_after {
+        sender::ban(bot, chat_id, user_id)
+            .await
+            .ok()
+            .map(|_| Escalation::Ban)
+    } else if chat.mute_after > 0 && count >= chat.mute_after && !sender::is_chat(user_id) {
+        let until = Utc.timestamp(ts + MUTE_DURATION, 0);
+        let user = UserId(user_id.try_into().unwrap());
+
+        bot.restrict_chat_member(ChatId(chat_id), user, ChatPermissions::empty())
+            .until_date(until)
+            .await
+            .ok()
+            .map(|_| Escalation::Mute)
+    } else {
+        None
+    };
+
+    // Remembered so that an approved appeal can lift it again.
+    if let Some(step) = step {
+        db.set_strike_escalation(chat_id, poll_id, step).await?;
+    }
+
+    Ok(step.map(|step| (step, count)))
+}
+
+/// Takes back the strike the poll `poll_id` gave `user_id`, and lifts the mute or ban it led to
+/// unless the strikes left still call for it. Only users appeal, so `user_id` is never a chat.
+pub async fn take_back_strike(
+    bot: &DeleteIttBot,
+    db: &Store,
+    chat_id: i64,
+    user_id: i64,
+    poll_id: i32,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let step = match db.remove_poll_strike(chat_id, poll_id).await? {
+        Some(step) => step,
+        None => return Ok(()),
+    };
+
+    let chat = db.get_chat(chat_id).await?;
+    let (window, threshold) = match &chat {
+        Some(chat) => (
+            chat.strike_window,
+            match step {
+                Escalation::Mute => chat.mute_after,
+                Escalation::Ban => chat.ban_after,
+            },
+        ),
+        None => (DEFAULT_STRIKE_WINDOW, 0),
+    };
+    let count = db.count_strikes(chat_id, user_id, now() - window).await?;
+
+    if threshold > 0 && count >= threshold {
+        return Ok(());
+    }
+
+    let user = UserId(user_id.try_into().unwrap());
+
+    match step {
+        Escalation::Mute => {
+            bot.restrict_chat_member(ChatId(chat_id), user, ChatPermissions::all())
+                .await?;
+        }
+        Escalation::Ban => {
+            bot.unban_chat_member(ChatId(chat_id), user)
+                .only_if_banned(true)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The user a `/strikes` command is about, with how to show them: the author of the message it
+/// replies to, a mentioned user, a `@username` seen in the chat or a numeric id.
+pub async fn strikes_target(db: &Store, msg: &Message, args: &str) -> Option<(i64, Markdown)> {
+    if let Some(from) = msg.reply_to_message().and_then(|m| m.from()) {
+        return Some((from.id.0.try_into().unwrap(), Markdown::user(from)));
+    }
+
+    let mentioned = msg
+        .entities()
+        .unwrap_or_default()
+        .iter()
+        .find_map(|e| match &e.kind {
+            MessageEntityKind::TextMention { user } => Some(user.clone()),
+            _ => None,
+        });
+
+    if let Some(user) = mentioned {
+        return Some((user.id.0.try_into().unwrap(), Markdown::user(&user)));
+    }
+
+    for arg in args.split_whitespace() {
+        if let Some(username) = arg.strip_prefix('@') {
+            return match db.get_member_by_username(msg.chat.id.0, username).await {
+                Ok(Some(member)) => Some((member.user_id, Markdown::text(arg))),
+                _ => None,
+            };
+        }
+
+        if let Ok(user_id) = arg.parse::<i64>() {
+            return Some((user_id, Markdown::text(arg)));
+        }
+    }
+
+    None
+}
@@ -1,12 +1,27 @@
 use loon::Opts;
 use teloxide::{
-    payloads::EditMessageReplyMarkupSetters,
+    payloads::{EditMessageReplyMarkupSetters, EditMessageTextSetters},
     requests::Requester,
-    types::{InlineKeyboardButton, InlineKeyboardMarkup, Message},
+    types::{
+        ChatId, ChatKind, ChatPublic, InlineKeyboardButton, InlineKeyboardMarkup, Me, Message,
+        MessageId, ParseMode, PublicChatKind, PublicChatSupergroup, User,
+    },
+    ApiError, RequestError,
 };
 
-use crate::database::{Database, Poll};
-use crate::types::{DeleteIttBot, HandlerResult, Localization};
+use super::appeals::appeal_link;
+use super::federations::federation_ban;
+use super::permissions::report_missing_rights;
+use super::restore::restore_markup;
+use super::strikes::strike;
+use crate::database::Poll;
+use crate::format::{translate, Markdown};
+use crate::reason;
+use crate::sender;
+use crate::storage::{Store, APPEAL_TTL, MAX_TRACKED_MESSAGES};
+use crate::types::{
+    Configuration, ContentKind, DeleteIttBot, Escalation, HandlerResult, Localization, Scope,
+};
 
 fn format_vote_button(text: &str, count: i64) -> String {
     format!("{} ({})", text, count)
@@ -24,44 +39,399 @@ pub fn gen_markup(
     ])
 }
 
+/// Whether `e` means the message a request was about no longer exists.
+pub fn message_gone(e: &RequestError) -> bool {
+    matches!(
+        e,
+        RequestError::Api(
+            ApiError::MessageToEditNotFound
+                | ApiError::MessageToDeleteNotFound
+                | ApiError::MessageIdInvalid
+        )
+    )
+}
+
+/// Forgets a poll and its voters, and deletes the poll message if it is still there.
+pub async fn close_poll(bot: &DeleteIttBot, info: &Poll, db: &Store) -> HandlerResult {
+    db.remove_voters(info.id).await?;
+    db.remove_poll(info.id).await?;
+
+    bot.delete_message(info.chat_id.to_string(), MessageId(info.poll_id))
+        .await
+        .ok();
+
+    Ok(())
+}
+
+/// Shows the current counts on the poll's keyboard. A poll whose message was deleted in the
+/// meantime is closed.
 pub async fn update_count(
     bot: &DeleteIttBot,
     info: &Poll,
-    db: &Database,
+    db: &Store,
+    config: &Configuration,
     loc: &Localization,
 ) -> HandlerResult {
-    let locale = &get_locale(db, info.chat_id).await;
+    let locale = &get_locale(db, config, info.chat_id).await;
 
     let yes_txt = loc.t("vote.yes", Opts::default().locale(locale))?;
 
     let no_txt = loc.t("vote.no", Opts::default().locale(locale))?;
 
-    bot.edit_message_reply_markup(info.chat_id.to_string(), info.poll_id)
+    let edited = bot
+        .edit_message_reply_markup(info.chat_id.to_string(), MessageId(info.poll_id))
         .reply_markup(gen_markup(
             info.vote_count_yes,
             info.vote_count_no,
             &yes_txt,
             &no_txt,
         ))
+        .await;
+
+    match edited {
+        Err(e) if message_gone(&e) => close_poll(bot, info, db).await,
+        Err(RequestError::Api(ApiError::MessageNotModified)) | Ok(_) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Deletes the author's other recent messages a poll reaches, as far as they were tracked.
+/// The target itself has already been forgotten by the time this runs.
+async fn delete_scope(bot: &DeleteIttBot, info: &Poll, db: &Store) -> HandlerResult {
+    let (since, limit) = match info.scope() {
+        Some(Scope::Messages(count)) => (0, count),
+        Some(Scope::Minutes(_)) if info.scope_since > 0 => (info.scope_since, MAX_TRACKED_MESSAGES),
+        // Polls opened before the window start was stored.
+        Some(Scope::Minutes(minutes)) => (now() - minutes * 60, MAX_TRACKED_MESSAGES),
+        None => return Ok(()),
+    };
+
+    let messages = db
+        .get_recent_messages(info.chat_id, info.message_user_id, since, limit)
         .await?;
 
+    for m in messages {
+        bot.delete_message(info.chat_id.to_string(), MessageId(m.message_id))
+            .await
+            .ok();
+        db.remove_recent_message(info.chat_id, m.message_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Deletes the target of a poll that reached its threshold, remembers its fingerprints, gives
+/// its author a strike, turns the poll into a result message and schedules that for deletion.
+/// The poll is closed even if the target can not be deleted, and the chat is told about any
+/// missing rights.
+pub async fn delete_voted_message(
+    bot: &DeleteIttBot,
+    me: &Me,
+    info: &Poll,
+    db: &Store,
+    config: &Configuration,
+    loc: &Localization,
+) -> HandlerResult {
+    let locale = get_locale(db, config, info.chat_id).await;
+
+    let deleted = match bot
+        .delete_message(info.chat_id.to_string(), MessageId(info.message_id))
+        .await
+    {
+        Ok(_) => true,
+        Err(e) => message_gone(&e),
+    };
+
+    if deleted {
+        db.remove_recent_message(info.chat_id, info.message_id)
+            .await?;
+        delete_scope(bot, info, db).await?;
+
+        for print in info.fingerprints() {
+            db.create_fingerprint(info.chat_id, print, now()).await?;
+        }
+    }
+
+    let undo_window = match db.get_archive(info.chat_id, info.poll_id).await? {
+        Some(archive) => {
+            let window = match db.get_chat(info.chat_id).await? {
+                Some(chat) if deleted => chat.undo_window,
+                _ => 0,
+            };
+
+            if window > 0 {
+                db.set_archive_expiry(archive.id, now() + window).await?;
+            } else {
+                db.remove_archive(archive.id).await?;
+            }
+
+            window
+        }
+        None => 0,
+    };
+
+    // Everything that can fail goes before the poll is removed, so that a failure leaves the poll
+    // open rather than closed behind a live vote keyboard. A poll gives its author one strike, so
+    // deciding it again after such a failure does not strike twice.
+    let from_name = sender::name(bot, info.chat_id, info.message_user_id).await?;
+
+    // Chats can not be written to privately, so only users can appeal. The result goes out
+    // without the link if the appeal can't be stored.
+    let link = if deleted && !sender::is_chat(info.message_user_id) {
+        appeal_link(me, db, loc, &locale, info).await.ok()
+    } else {
+        None
+    };
+    let appeal_window = if link.is_some() { APPEAL_TTL } else { 0 };
+
+    let escalation = if deleted {
+        strike(bot, db, info.chat_id, info.message_user_id, info.poll_id).await?
+    } else {
+        None
+    };
+
+    let mut txt_result = translate(
+        loc,
+        if deleted {
+            "result.deleted"
+        } else {
+            "result.failed"
+        },
+        &locale,
+        &[("from_name", from_name.clone())],
+    )?
+    .to_string();
+
+    if let Some(link) = link {
+        txt_result = format!("{} {}", txt_result, link);
+    }
+
+    // Polls the bot opened by itself stay tagged as such once decided.
+    if let Some(origin) = info.origin() {
+        let origin_line = translate(
+            loc,
+            format!("vote.origin_{}", origin.as_str()).as_str(),
+            &locale,
+            &[],
+        )?;
+
+        txt_result = format!("{}\n{}", txt_result, origin_line.italic());
+    }
+
+    if let Some((step, count)) = escalation {
+        let key = match step {
+            Escalation::Mute => "strikes.muted",
+            Escalation::Ban => "strikes.banned",
+        };
+        let line = translate(
+            loc,
+            key,
+            &locale,
+            &[
+                ("from_name", from_name.clone()),
+                ("count", Markdown::text(count.to_string())),
+            ],
+        )?;
+
+        txt_result = format!("{}\n{}", txt_result, line);
+
+        if step == Escalation::Ban {
+            federation_ban(bot, db, loc, info.chat_id, info.message_user_id, &from_name).await?;
+        }
+    }
+
+    let restore_button = if undo_window > 0 {
+        Some(restore_markup(loc, &locale)?)
+    } else {
+        None
+    };
+
+    db.remove_voters(info.id).await?;
+    db.remove_poll(info.id).await?;
+
+    // The poll is closed now, so failed edits no longer stop the rest. Editing the markup takes
+    // the vote keyboard away even if the text could not be changed.
+    bot.edit_message_text(
+        info.chat_id.to_string(),
+        MessageId(info.poll_id),
+        txt_result,
+    )
+    .parse_mode(ParseMode::MarkdownV2)
+    .await
+    .ok();
+
+    let markup = bot.edit_message_reply_markup(info.chat_id.to_string(), MessageId(info.poll_id));
+
+    match restore_button {
+        Some(button) => markup.reply_markup(button).await.ok(),
+        None => markup.await.ok(),
+    };
+
+    let delay = get_poll_delete_delay(db, config, info.chat_id).await;
+
+    // The result message carries the restore button and the appeal link, so it has to outlive
+    // the undo window and the appeal.
+    db.schedule_message_delete(
+        info.chat_id,
+        info.poll_id.into(),
+        now() + delay.max(undo_window).max(appeal_window),
+    )
+    .await?;
+
+    if !deleted {
+        report_missing_rights(bot, ChatId(info.chat_id), info.thread_id, db, config, loc).await?;
+    }
+
     Ok(())
 }
 
-pub async fn get_locale(db: &Database, chat_id: i64) -> String {
+/// The forum topic `msg` was posted in, or `None` outside forums and in their general topic.
+/// Replies in other chats carry a thread id too, but can't be sent into it.
+pub fn topic(msg: &Message) -> Option<i32> {
+    match &msg.chat.kind {
+        ChatKind::Public(ChatPublic {
+            kind: PublicChatKind::Supergroup(PublicChatSupergroup { is_forum: true, .. }),
+            ..
+        }) => msg.thread_id,
+        _ => None,
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .try_into()
+        .unwrap()
+}
+
+/// Why `user` may not vote on `info`, as a locale key, or `None` if they may.
+pub async fn voter_ineligibility(db: &Store, info: &Poll, user: &User) -> Option<&'static str> {
+    let user_id: i64 = user.id.0.try_into().unwrap();
+
+    if user_id == info.message_user_id {
+        return Some("eligibility.target");
+    }
+
+    let chat = match db.get_chat(info.chat_id).await {
+        Ok(Some(chat)) => chat,
+        _ => return None,
+    };
+
+    if chat.exclude_bots && user.is_bot {
+        return Some("eligibility.bot");
+    }
+
+    if chat.voter_min_age == 0 && chat.voter_min_messages == 0 {
+        return None;
+    }
+
+    let member = db.get_member(info.chat_id, user_id).await.ok().flatten();
+
+    if chat.voter_min_age > 0 {
+        match &member {
+            Some(m) if now() - m.first_seen >= chat.voter_min_age => {}
+            _ => return Some("eligibility.too_new"),
+        }
+    }
+
+    if chat.voter_min_messages > 0 {
+        match &member {
+            Some(m) if m.message_count >= chat.voter_min_messages => {}
+            _ => return Some("eligibility.too_few_messages"),
+        }
+    }
+
+    None
+}
+
+pub async fn get_locale(db: &Store, config: &Configuration, chat_id: i64) -> String {
     match db.get_chat_locale(chat_id).await {
         Ok(Some(lang)) => lang,
-        _ => "en".to_string(),
+        _ => config.default_locale.clone(),
+    }
+}
+
+pub async fn get_vote_count(db: &Store, config: &Configuration, chat_id: i64) -> i64 {
+    match db.get_chat_votes(chat_id).await {
+        Ok(Some(count)) => count,
+        _ => config.vote_count.default,
     }
 }
 
-pub async fn get_poll_delete_delay(db: &Database, chat_id: i64) -> i64 {
+/// The threshold for a poll started with `reason` about a message of `kind` in the forum topic
+/// `thread_id`: the chat's override for the reason's keyword if there is one, then its override
+/// for the kind, then the topic's count, then the chat's minimum vote count.
+pub async fn get_poll_vote_count(
+    db: &Store,
+    config: &Configuration,
+    chat_id: i64,
+    reason: Option<&str>,
+    kind: Option<ContentKind>,
+    thread_id: Option<i32>,
+) -> i64 {
+    if let Some(category) = reason.and_then(reason::category) {
+        if let Ok(Some(count)) = db.get_reason_votes(chat_id, category).await {
+            return count;
+        }
+    }
+
+    if let Some(kind) = kind {
+        if let Ok(Some(count)) = db.get_type_votes(chat_id, kind).await {
+            return count;
+        }
+    }
+
+    if let Some(thread_id) = thread_id {
+        if let Ok(Some(topic)) = db.get_topic(chat_id, thread_id).await {
+            if topic.minimum_vote_count > 0 {
+                return topic.minimum_vote_count;
+            }
+        }
+    }
+
+    get_vote_count(db, config, chat_id).await
+}
+
+/// A reason for a poll title: a bare keyword is translated, anything else is shown as typed.
+pub fn format_reason(loc: &Localization, locale: &str, reason: &str) -> Markdown {
+    let translated = match reason::category(reason) {
+        Some(category) if category.eq_ignore_ascii_case(reason) => loc
+            .t(
+                format!("reason.{}", category).as_str(),
+                Opts::default().locale(locale),
+            )
+            .ok(),
+        _ => None,
+    };
+
+    Markdown::text(translated.unwrap_or_else(|| reason.into()))
+}
+
+pub async fn get_poll_delete_delay(db: &Store, config: &Configuration, chat_id: i64) -> i64 {
     match db.get_chat_poll_delete_delay(chat_id).await {
         Ok(Some(delay)) => delay,
-        _ => 5,
+        _ => config.poll_delete_delay.default,
     }
 }
 
+/// Creates the `chats` row with configured defaults if it does not exist yet.
+pub async fn ensure_chat(db: &Store, config: &Configuration, chat_id: i64) -> HandlerResult {
+    if let Ok(None) = db.get_chat(chat_id).await {
+        db.create_chat(
+            chat_id,
+            config.vote_count.default,
+            &config.default_locale,
+            config.poll_delete_delay.default,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
 pub async fn delete_message(bot: DeleteIttBot, msg: Message) -> HandlerResult {
     bot.delete_message(msg.chat.id, msg.id).await?;
 
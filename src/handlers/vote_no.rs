@@ -3,45 +3,67 @@ use teloxide::{
     dispatching::UpdateFilterExt,
     payloads::AnswerCallbackQuerySetters,
     requests::Requester,
-    types::{CallbackQuery, Update},
+    types::{CallbackQuery, MessageId, Update},
 };
 
 use super::{
     filters::{callback_query_eq, non_duplicate},
     utils::get_locale,
     utils::update_count,
+    utils::voter_ineligibility,
+};
+use crate::storage::Store;
+use crate::types::{
+    AtomicHandler, Configuration, DeleteIttBot, HandlerResult, Localization, VoteType,
 };
-use crate::types::{AtomicHandler, DeleteIttBot, HandlerResult, Localization, VoteType};
-use crate::Database;
 
 async fn handle_vote_no(
     bot: DeleteIttBot,
     query: CallbackQuery,
-    db: Database,
+    db: Store,
+    config: Configuration,
     loc: Localization,
 ) -> HandlerResult {
     if let Some(msg) = query.message {
-        if let Ok(Some(mut info)) = db.get_poll(msg.chat.id.0, msg.id).await {
+        if let Ok(Some(mut info)) = db.get_poll(msg.chat.id.0, msg.id.0).await {
+            let locale = get_locale(&db, &config, msg.chat.id.0).await;
+
+            if let Some(key) = voter_ineligibility(&db, &info, &query.from).await {
+                let response = loc.t(key, Opts::default().locale(&locale))?;
+
+                bot.answer_callback_query(query.id)
+                    .text(response)
+                    .show_alert(true)
+                    .await?;
+
+                return Ok(());
+            }
+
             info.vote_count_no += 1;
 
-            let response = loc.t(
-                "vote.voted_to_not_delete",
-                Opts::default().locale(&get_locale(&db, msg.chat.id.0).await),
-            )?;
+            let response = loc.t("vote.voted_to_not_delete", Opts::default().locale(&locale))?;
 
             bot.answer_callback_query(query.id).text(response).await?;
 
             if info.vote_count_no == info.minimum_vote_count {
-                bot.delete_message(info.chat_id.to_string(), info.message_id)
-                    .await?;
-
-                bot.delete_message(info.chat_id.to_string(), info.poll_id)
-                    .await?;
-
                 db.remove_voters(info.id).await?;
                 db.remove_poll(info.id).await?;
+
+                // Without the right to delete messages, at least stop the poll taking votes.
+                if bot
+                    .delete_message(info.chat_id.to_string(), MessageId(info.poll_id))
+                    .await
+                    .is_err()
+                {
+                    bot.edit_message_reply_markup(
+                        info.chat_id.to_string(),
+                        MessageId(info.poll_id),
+                    )
+                    .await
+                    .ok();
+                }
             } else {
-                update_count(&bot, &info, &db, &loc).await?;
+                update_count(&bot, &info, &db, &config, &loc).await?;
                 db.create_voter(info.id, query.from.id.0.try_into().unwrap())
                     .await?;
                 db.register_vote(info.id, VoteType::No).await?;
@@ -1,83 +1,53 @@
 use loon::Opts;
 use teloxide::{
     dispatching::UpdateFilterExt,
-    payloads::{AnswerCallbackQuerySetters, EditMessageTextSetters},
+    payloads::AnswerCallbackQuerySetters,
     requests::Requester,
-    types::{CallbackQuery, ParseMode, Update, UserId},
+    types::{CallbackQuery, Me, Update},
 };
 
 use super::{
     filters::{callback_query_eq, non_duplicate},
-    utils::{get_locale, get_poll_delete_delay, update_count},
+    utils::{delete_voted_message, get_locale, update_count, voter_ineligibility},
+};
+use crate::storage::Store;
+use crate::types::{
+    AtomicHandler, Configuration, DeleteIttBot, HandlerResult, Localization, VoteType,
 };
-use crate::types::{AtomicHandler, DeleteIttBot, HandlerResult, Localization, VoteType};
-use crate::Database;
 
 async fn handle_vote_yes(
     bot: DeleteIttBot,
+    me: Me,
     query: CallbackQuery,
-    db: Database,
+    db: Store,
+    config: Configuration,
     loc: Localization,
 ) -> HandlerResult {
     if let Some(msg) = query.message {
-        if let Ok(Some(mut info)) = db.get_poll(msg.chat.id.0, msg.id).await {
-            info.vote_count_yes += 1;
-
-            let locale = get_locale(&db, msg.chat.id.0).await;
-
-            let response = loc.t("vote.voted_to_delete", Opts::default().locale(&locale))?;
+        if let Ok(Some(mut info)) = db.get_poll(msg.chat.id.0, msg.id.0).await {
+            let locale = get_locale(&db, &config, msg.chat.id.0).await;
 
-            bot.answer_callback_query(query.id).text(response).await?;
+            if let Some(key) = voter_ineligibility(&db, &info, &query.from).await {
+                let response = loc.t(key, Opts::default().locale(&locale))?;
 
-            if info.vote_count_yes >= info.minimum_vote_count {
-                bot.delete_message(info.chat_id.to_string(), info.message_id)
+                bot.answer_callback_query(query.id)
+                    .text(response)
+                    .show_alert(true)
                     .await?;
 
-                let from = bot
-                    .get_chat_member(
-                        info.chat_id.to_string(),
-                        UserId(info.message_user_id.try_into().unwrap()),
-                    )
-                    .await?;
+                return Ok(());
+            }
 
-                let txt_result = loc.t(
-                    "result.deleted",
-                    Opts::default()
-                        .var(
-                            "from_name",
-                            format!("[{}]({})", from.user.full_name(), from.user.url()),
-                        )
-                        .locale(&locale),
-                )?;
+            info.vote_count_yes += 1;
 
-                bot.edit_message_text(info.chat_id.to_string(), info.poll_id, txt_result)
-                    .parse_mode(ParseMode::MarkdownV2)
-                    .await?;
+            let response = loc.t("vote.voted_to_delete", Opts::default().locale(&locale))?;
 
-                bot.edit_message_reply_markup(info.chat_id.to_string(), info.poll_id)
-                    .await?;
+            bot.answer_callback_query(query.id).text(response).await?;
 
-                db.remove_voters(info.id).await?;
-                db.remove_poll(info.id).await?;
-                db.schedule_message_delete(
-                    info.chat_id,
-                    info.poll_id.into(),
-                    (std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        + std::time::Duration::from_secs(
-                            get_poll_delete_delay(&db, info.chat_id)
-                                .await
-                                .try_into()
-                                .unwrap(),
-                        ))
-                    .as_secs()
-                    .try_into()
-                    .unwrap(),
-                )
-                .await?;
+            if info.vote_count_yes >= info.minimum_vote_count {
+                delete_voted_message(&bot, &me, &info, &db, &config, &loc).await?;
             } else {
-                update_count(&bot, &info, &db, &loc).await?;
+                update_count(&bot, &info, &db, &config, &loc).await?;
                 db.create_voter(info.id, query.from.id.0.try_into().unwrap())
                     .await?;
                 db.register_vote(info.id, VoteType::Yes).await?;
@@ -0,0 +1,120 @@
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        Arc,
+    },
+};
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use teloxide::requests::Requester;
+
+use crate::storage::Store;
+use crate::types::DeleteIttBot;
+
+/// Seconds without a scheduler tick after which the bot is reported as not ready.
+const SCHEDULER_STALE_AFTER: i64 = 30;
+
+/// Seconds between two `get_me` probes.
+const GET_ME_INTERVAL: u64 = 60;
+
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    scheduler_tick: Arc<AtomicI64>,
+    get_me_ok: Arc<AtomicBool>,
+}
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .try_into()
+        .unwrap()
+}
+
+impl Health {
+    pub fn scheduler_ticked(&self) {
+        self.scheduler_tick.store(now(), Ordering::Relaxed);
+    }
+
+    pub fn set_get_me(&self, ok: bool) {
+        self.get_me_ok.store(ok, Ordering::Relaxed);
+    }
+
+    fn scheduler_alive(&self) -> bool {
+        now() - self.scheduler_tick.load(Ordering::Relaxed) <= SCHEDULER_STALE_AFTER
+    }
+
+    async fn check(&self, db: &Store) -> Vec<(&'static str, bool)> {
+        vec![
+            ("database", db.ping().await.is_ok()),
+            ("scheduler", self.scheduler_alive()),
+            ("get_me", self.get_me_ok.load(Ordering::Relaxed)),
+        ]
+    }
+}
+
+async fn handle(req: Request<Body>, health: Health, db: Store) -> Response<Body> {
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/healthz") => Response::new(Body::from("ok")),
+        (&Method::GET, "/readyz") => {
+            let checks = health.check(&db).await;
+
+            let status = if checks.iter().all(|(_, ok)| *ok) {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+
+            let body = checks
+                .into_iter()
+                .map(|(name, ok)| format!("{}: {}", name, if ok { "ok" } else { "failing" }))
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            Response::builder()
+                .status(status)
+                .body(Body::from(body))
+                .unwrap()
+        }
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap(),
+    }
+}
+
+/// Periodically calls `get_me`, bypassing the cache, and records whether it succeeded.
+pub fn spawn_get_me_probe(bot: DeleteIttBot, health: Health) {
+    tokio::spawn(async move {
+        loop {
+            health.set_get_me(bot.inner().get_me().await.is_ok());
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(GET_ME_INTERVAL)).await;
+        }
+    });
+}
+
+/// Serves `/healthz` (liveness) and `/readyz` (readiness) on `addr`.
+pub async fn serve(addr: SocketAddr, health: Health, db: Store) -> Result<(), hyper::Error> {
+    let make_svc = make_service_fn(move |_| {
+        let health = health.clone();
+        let db = db.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let health = health.clone();
+                let db = db.clone();
+
+                async move { Ok::<_, Infallible>(handle(req, health, db).await) }
+            }))
+        }
+    });
+
+    Server::bind(&addr).serve(make_svc).await
+}
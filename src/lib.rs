@@ -0,0 +1,76 @@
+//! Clean your chats with a little bit of democracy.
+//!
+//! [`schema`] returns the complete handler tree. Bots that only want some of the behaviour
+//! can mount the individual branches from [`handlers`] into their own `dptree` instead. Every
+//! branch expects a [`storage::Store`], a [`types::Configuration`], a [`types::Localization`], a
+//! `Vec<`[`types::Locale`]`>` and a [`handlers::PatternCache`] in the dependency map. Such bots
+//! should also run [`handlers::track_member`] on every update, or the voter age and message count
+//! rules will reject everyone.
+
+use std::{fs, sync::Arc};
+
+use teloxide::dispatching::UpdateHandler;
+
+pub mod config;
+pub mod content;
+pub mod database;
+pub mod fingerprint;
+pub mod format;
+pub mod handlers;
+pub mod health;
+pub mod memory;
+pub mod reason;
+pub mod reconcile;
+pub mod scheduler;
+pub mod sender;
+pub mod storage;
+pub mod types;
+
+pub use crate::config::Config;
+pub use crate::database::Database;
+pub use crate::memory::MemoryStorage;
+pub use crate::storage::{Storage, Store};
+
+use crate::handlers::{
+    appeal_handler, federation_handler, my_chat_member_handler, pattern_handler,
+    quick_setup_handler, repost_handler, restore_handler, settings_handler, setup_poll_handler,
+    track_member, vote_no_handler, vote_yes_handler,
+};
+use crate::types::{Locale, Localization};
+
+pub fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
+    teloxide::dptree::entry()
+        .inspect_async(track_member)
+        .branch(my_chat_member_handler())
+        .branch(quick_setup_handler())
+        .branch(federation_handler())
+        .branch(appeal_handler())
+        // Commands and poll triggers go before the content checks, so that a filter or a
+        // fingerprint matching their text can not swallow them.
+        .branch(settings_handler())
+        .branch(setup_poll_handler())
+        .branch(repost_handler())
+        .branch(pattern_handler())
+        .branch(vote_yes_handler())
+        .branch(vote_no_handler())
+        .branch(restore_handler())
+}
+
+/// Loads every `*.yml` file in `dir`, returning the dictionary and the available locale names.
+pub fn load_localization(dir: &str) -> (Localization, Vec<Locale>) {
+    let loc_dict = Arc::new(
+        loon::Config::default()
+            .with_path_pattern(format!("{}/*.yml", dir.trim_end_matches('/')))
+            .finish()
+            .expect("Can not load localization"),
+    );
+    let locales = fs::read_dir(dir)
+        .expect("Can not open locales directory")
+        .filter(|p| p.is_ok())
+        .map(|p| p.unwrap().file_name().into_string().unwrap())
+        .filter(|s| s.contains(".yml"))
+        .map(|s| s.split(".yml").next().unwrap().into())
+        .collect::<Vec<Locale>>();
+
+    (loc_dict, locales)
+}
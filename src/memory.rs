@@ -0,0 +1,1441 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use sqlx::Error;
+
+use crate::database::{
+    Appeal, Archive, Chat, Federation, FederationBan, Member, MessageToDelete, Pattern, Poll,
+    RecentMessage, Topic, Voter,
+};
+use crate::storage::{new_token, Storage, DEFAULT_STRIKE_WINDOW, MAX_TRACKED_MESSAGES};
+use crate::types::{
+    AppealStatus, ContentKind, Escalation, FederationAction, FingerprintAction, PollOrigin, Scope,
+    Trigger, VoteType,
+};
+
+#[derive(Debug, Clone)]
+struct ReasonVoteCount {
+    chat_id: i64,
+    reason: String,
+    minimum_vote_count: i64,
+}
+
+#[derive(Debug, Clone)]
+struct TypeVoteCount {
+    chat_id: i64,
+    kind: ContentKind,
+    minimum_vote_count: i64,
+}
+
+#[derive(Debug, Clone)]
+struct PollStart {
+    chat_id: i64,
+    user_id: i64,
+    timestamp: i64,
+}
+
+#[derive(Debug, Clone)]
+struct Strike {
+    chat_id: i64,
+    user_id: i64,
+    poll_id: i32,
+    timestamp: i64,
+    escalation: Option<Escalation>,
+}
+
+#[derive(Debug, Clone)]
+struct Fingerprint {
+    chat_id: i64,
+    value: String,
+    timestamp: i64,
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    last_id: i64,
+    polls: Vec<Poll>,
+    voters: Vec<Voter>,
+    chats: Vec<Chat>,
+    members: Vec<Member>,
+    recent_messages: Vec<RecentMessage>,
+    poll_starts: Vec<PollStart>,
+    strikes: Vec<Strike>,
+    fingerprints: Vec<Fingerprint>,
+    patterns: Vec<Pattern>,
+    archives: Vec<Archive>,
+    appeals: Vec<Appeal>,
+    federations: Vec<Federation>,
+    federation_bans: Vec<FederationBan>,
+    reason_vote_counts: Vec<ReasonVoteCount>,
+    type_vote_counts: Vec<TypeVoteCount>,
+    topics: Vec<Topic>,
+    scheduled_to_delete: Vec<MessageToDelete>,
+}
+
+impl Tables {
+    fn next_id(&mut self) -> i64 {
+        self.last_id += 1;
+        self.last_id
+    }
+}
+
+/// Keeps everything in process memory. Useful for tests and ephemeral deployments; all state
+/// is lost when the process exits.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn chat_mut<F>(&self, chat_id: i64, f: F) -> bool
+    where
+        F: FnOnce(&mut Chat),
+    {
+        let mut t = self.tables.lock().unwrap();
+
+        match t.chats.iter_mut().find(|c| c.chat_id == chat_id) {
+            Some(c) => {
+                f(c);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies `f` to the settings of a forum topic, adding them first if the topic had none.
+    fn topic_mut<F>(&self, chat_id: i64, thread_id: i32, f: F)
+    where
+        F: FnOnce(&mut Topic),
+    {
+        let mut t = self.tables.lock().unwrap();
+
+        if !t
+            .topics
+            .iter()
+            .any(|r| r.chat_id == chat_id && r.thread_id == thread_id)
+        {
+            let id = t.next_id();
+
+            t.topics.push(Topic {
+                id,
+                chat_id,
+                thread_id,
+                minimum_vote_count: 0,
+                enabled: true,
+            });
+        }
+
+        if let Some(r) = t
+            .topics
+            .iter_mut()
+            .find(|r| r.chat_id == chat_id && r.thread_id == thread_id)
+        {
+            f(r);
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn ping(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn create_poll(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+        message_id: i32,
+        message_user_id: i64,
+        minimum_vote_count: i64,
+        reason: Option<&str>,
+    ) -> Result<(), Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        if t.polls
+            .iter()
+            .any(|p| p.chat_id == chat_id && p.message_id == message_id)
+        {
+            return Err(Error::Protocol(format!(
+                "a poll about message {} in chat {} already exists",
+                message_id, chat_id
+            )));
+        }
+
+        let id = t.next_id();
+
+        t.polls.push(Poll {
+            id,
+            chat_id,
+            poll_id,
+            message_id,
+            message_user_id,
+            minimum_vote_count,
+            vote_count_yes: 0,
+            vote_count_no: 0,
+            reason: reason.map(Into::into),
+            scope_messages: 0,
+            scope_minutes: 0,
+            scope_since: 0,
+            fingerprints: None,
+            origin: None,
+            thread_id: None,
+        });
+
+        Ok(())
+    }
+
+    async fn get_poll(&self, chat_id: i64, poll_id: i32) -> Result<Option<Poll>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.polls
+            .iter()
+            .find(|p| p.chat_id == chat_id && p.poll_id == poll_id)
+            .cloned())
+    }
+
+    async fn get_poll_by_message(
+        &self,
+        chat_id: i64,
+        message_id: i32,
+    ) -> Result<Option<Poll>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.polls
+            .iter()
+            .find(|p| p.chat_id == chat_id && p.message_id == message_id)
+            .cloned())
+    }
+
+    async fn set_poll_scope(&self, poll_id: i64, scope: Scope, date: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t.polls.iter_mut().find(|p| p.id == poll_id) {
+            Some(p) => {
+                (p.scope_messages, p.scope_minutes, p.scope_since) = match scope {
+                    Scope::Messages(count) => (count, 0, 0),
+                    Scope::Minutes(minutes) => (0, minutes, date - minutes * 60),
+                };
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_poll_fingerprints(
+        &self,
+        poll_id: i64,
+        fingerprints: &[String],
+    ) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t.polls.iter_mut().find(|p| p.id == poll_id) {
+            Some(p) => {
+                p.fingerprints = Some(fingerprints.join(" "));
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_poll_origin(&self, poll_id: i64, origin: PollOrigin) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t.polls.iter_mut().find(|p| p.id == poll_id) {
+            Some(p) => {
+                p.origin = Some(origin.as_str().into());
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_poll_thread(&self, poll_id: i64, thread_id: i32) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t.polls.iter_mut().find(|p| p.id == poll_id) {
+            Some(p) => {
+                p.thread_id = Some(thread_id);
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn register_vote(&self, poll_id: i64, v: VoteType) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t.polls.iter_mut().find(|p| p.id == poll_id) {
+            Some(p) => {
+                match v {
+                    VoteType::Yes => p.vote_count_yes += 1,
+                    VoteType::No => p.vote_count_no += 1,
+                }
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn remove_poll(&self, poll_id: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.polls.len();
+
+        t.polls.retain(|p| p.id != poll_id);
+
+        Ok(t.polls.len() < before)
+    }
+
+    async fn get_polls(&self) -> Result<Vec<Poll>, Error> {
+        Ok(self.tables.lock().unwrap().polls.clone())
+    }
+
+    async fn count_polls(&self, chat_id: i64) -> Result<i64, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.polls.iter().filter(|p| p.chat_id == chat_id).count() as i64)
+    }
+
+    async fn count_polls_about(&self, chat_id: i64, message_user_id: i64) -> Result<i64, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.polls
+            .iter()
+            .filter(|p| p.chat_id == chat_id && p.message_user_id == message_user_id)
+            .count() as i64)
+    }
+
+    async fn create_poll_start(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        self.tables.lock().unwrap().poll_starts.push(PollStart {
+            chat_id,
+            user_id,
+            timestamp,
+        });
+
+        Ok(())
+    }
+
+    async fn count_poll_starts(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        since: i64,
+    ) -> Result<i64, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.poll_starts
+            .iter()
+            .filter(|s| s.chat_id == chat_id && s.user_id == user_id && s.timestamp >= since)
+            .count() as i64)
+    }
+
+    async fn remove_poll_starts(&self, chat_id: i64, before: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before_len = t.poll_starts.len();
+
+        t.poll_starts
+            .retain(|s| !(s.chat_id == chat_id && s.timestamp < before));
+
+        Ok(t.poll_starts.len() < before_len)
+    }
+
+    async fn create_voter(&self, poll_id: i64, user_id: i64) -> Result<(), Error> {
+        let mut t = self.tables.lock().unwrap();
+        let id = t.next_id();
+
+        t.voters.push(Voter {
+            id,
+            poll_id,
+            user_id,
+        });
+
+        Ok(())
+    }
+
+    async fn get_voter(&self, poll_id: i64, user_id: i64) -> Result<Option<Voter>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.voters
+            .iter()
+            .find(|v| v.poll_id == poll_id && v.user_id == user_id)
+            .cloned())
+    }
+
+    async fn remove_voter(&self, voter_id: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.voters.len();
+
+        t.voters.retain(|v| v.id != voter_id);
+
+        Ok(t.voters.len() < before)
+    }
+
+    async fn remove_voters(&self, poll_id: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.voters.len();
+
+        t.voters.retain(|v| v.poll_id != poll_id);
+
+        Ok(t.voters.len() < before)
+    }
+
+    async fn remove_orphan_voters(&self) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.voters.len();
+        let polls = t.polls.iter().map(|p| p.id).collect::<Vec<i64>>();
+
+        t.voters.retain(|v| polls.contains(&v.poll_id));
+
+        Ok(t.voters.len() < before)
+    }
+
+    async fn get_chat_ids(&self) -> Result<Vec<i64>, Error> {
+        let t = self.tables.lock().unwrap();
+        let mut ids = t
+            .chats
+            .iter()
+            .map(|c| c.chat_id)
+            .chain(t.polls.iter().map(|p| p.chat_id))
+            .collect::<Vec<i64>>();
+
+        ids.sort_unstable();
+        ids.dedup();
+
+        Ok(ids)
+    }
+
+    async fn create_chat(
+        &self,
+        chat_id: i64,
+        minimum_vote_count: i64,
+        locale: &str,
+        poll_delete_delay: i64,
+    ) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let id = t.next_id();
+
+        t.chats.push(Chat {
+            id,
+            chat_id,
+            minimum_vote_count,
+            locale: locale.into(),
+            poll_delete_delay,
+            trigger_mention: true,
+            trigger_command: true,
+            initiator_votes: false,
+            exclude_bots: false,
+            voter_min_age: 0,
+            voter_min_messages: 0,
+            poll_rate_count: 0,
+            poll_rate_window: 0,
+            max_open_polls: 0,
+            strike_window: DEFAULT_STRIKE_WINDOW,
+            mute_after: 0,
+            ban_after: 0,
+            fingerprint_action: None,
+            share_fingerprints: false,
+            federation_id: None,
+            federation_action: FederationAction::Warn.as_str().into(),
+            undo_window: 0,
+            appeal_chat_id: None,
+        });
+
+        Ok(true)
+    }
+
+    async fn get_chat(&self, chat_id: i64) -> Result<Option<Chat>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.chats.iter().find(|c| c.chat_id == chat_id).cloned())
+    }
+
+    async fn get_chat_votes(&self, chat_id: i64) -> Result<Option<i64>, Error> {
+        Ok(self.get_chat(chat_id).await?.map(|c| c.minimum_vote_count))
+    }
+
+    async fn set_chat_votes(&self, chat_id: i64, votes_count: i64) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.minimum_vote_count = votes_count))
+    }
+
+    async fn get_chat_locale(&self, chat_id: i64) -> Result<Option<String>, Error> {
+        Ok(self.get_chat(chat_id).await?.map(|c| c.locale))
+    }
+
+    async fn set_chat_locale(&self, chat_id: i64, locale: &str) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.locale = locale.into()))
+    }
+
+    async fn get_chat_poll_delete_delay(&self, chat_id: i64) -> Result<Option<i64>, Error> {
+        Ok(self.get_chat(chat_id).await?.map(|c| c.poll_delete_delay))
+    }
+
+    async fn set_chat_poll_delete_delay(
+        &self,
+        chat_id: i64,
+        poll_delete_delay: i64,
+    ) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.poll_delete_delay = poll_delete_delay))
+    }
+
+    async fn get_chat_trigger(
+        &self,
+        chat_id: i64,
+        trigger: Trigger,
+    ) -> Result<Option<bool>, Error> {
+        Ok(self.get_chat(chat_id).await?.map(|c| match trigger {
+            Trigger::Mention => c.trigger_mention,
+            Trigger::Command => c.trigger_command,
+        }))
+    }
+
+    async fn set_chat_trigger(
+        &self,
+        chat_id: i64,
+        trigger: Trigger,
+        enabled: bool,
+    ) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| match trigger {
+            Trigger::Mention => c.trigger_mention = enabled,
+            Trigger::Command => c.trigger_command = enabled,
+        }))
+    }
+
+    async fn get_chat_initiator_votes(&self, chat_id: i64) -> Result<Option<bool>, Error> {
+        Ok(self.get_chat(chat_id).await?.map(|c| c.initiator_votes))
+    }
+
+    async fn set_chat_initiator_votes(&self, chat_id: i64, enabled: bool) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.initiator_votes = enabled))
+    }
+
+    async fn set_chat_exclude_bots(&self, chat_id: i64, enabled: bool) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.exclude_bots = enabled))
+    }
+
+    async fn set_chat_voter_min_age(&self, chat_id: i64, seconds: i64) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.voter_min_age = seconds))
+    }
+
+    async fn set_chat_voter_min_messages(&self, chat_id: i64, count: i64) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.voter_min_messages = count))
+    }
+
+    async fn set_chat_poll_rate(
+        &self,
+        chat_id: i64,
+        count: i64,
+        window: i64,
+    ) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| {
+            c.poll_rate_count = count;
+            c.poll_rate_window = window;
+        }))
+    }
+
+    async fn set_chat_max_open_polls(&self, chat_id: i64, count: i64) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.max_open_polls = count))
+    }
+
+    async fn set_chat_strike_window(&self, chat_id: i64, seconds: i64) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.strike_window = seconds))
+    }
+
+    async fn set_chat_escalation(
+        &self,
+        chat_id: i64,
+        escalation: Escalation,
+        count: i64,
+    ) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| match escalation {
+            Escalation::Mute => c.mute_after = count,
+            Escalation::Ban => c.ban_after = count,
+        }))
+    }
+
+    async fn set_chat_fingerprint_action(
+        &self,
+        chat_id: i64,
+        action: Option<FingerprintAction>,
+    ) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| {
+            c.fingerprint_action = action.map(|a| a.as_str().into())
+        }))
+    }
+
+    async fn set_chat_share_fingerprints(
+        &self,
+        chat_id: i64,
+        enabled: bool,
+    ) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.share_fingerprints = enabled))
+    }
+
+    async fn set_chat_undo_window(&self, chat_id: i64, seconds: i64) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.undo_window = seconds))
+    }
+
+    async fn set_chat_appeal_chat(
+        &self,
+        chat_id: i64,
+        appeal_chat_id: Option<i64>,
+    ) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.appeal_chat_id = appeal_chat_id))
+    }
+
+    async fn set_chat_federation(
+        &self,
+        chat_id: i64,
+        federation_id: Option<i64>,
+    ) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.federation_id = federation_id))
+    }
+
+    async fn set_chat_federation_action(
+        &self,
+        chat_id: i64,
+        action: FederationAction,
+    ) -> Result<bool, Error> {
+        Ok(self.chat_mut(chat_id, |c| c.federation_action = action.as_str().into()))
+    }
+
+    async fn track_member(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        username: Option<&str>,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t
+            .members
+            .iter_mut()
+            .find(|m| m.chat_id == chat_id && m.user_id == user_id)
+        {
+            Some(m) => {
+                m.message_count += 1;
+                m.username = username.map(Into::into);
+            }
+            None => {
+                let id = t.next_id();
+
+                t.members.push(Member {
+                    id,
+                    chat_id,
+                    user_id,
+                    first_seen: timestamp,
+                    message_count: 1,
+                    username: username.map(Into::into),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn track_message(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        message_id: i32,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        let mut t = self.tables.lock().unwrap();
+        let id = t.next_id();
+
+        t.recent_messages.push(RecentMessage {
+            id,
+            chat_id,
+            user_id,
+            message_id,
+            timestamp,
+        });
+
+        let kept = t
+            .recent_messages
+            .iter()
+            .filter(|m| m.chat_id == chat_id && m.user_id == user_id)
+            .count() as i64;
+
+        if kept > MAX_TRACKED_MESSAGES {
+            let oldest = t
+                .recent_messages
+                .iter()
+                .position(|m| m.chat_id == chat_id && m.user_id == user_id)
+                .unwrap();
+
+            t.recent_messages.remove(oldest);
+        }
+
+        Ok(())
+    }
+
+    async fn get_recent_messages(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        since: i64,
+        limit: i64,
+    ) -> Result<Vec<RecentMessage>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.recent_messages
+            .iter()
+            .rev()
+            .filter(|m| m.chat_id == chat_id && m.user_id == user_id && m.timestamp >= since)
+            .take(limit.try_into().unwrap_or(0))
+            .cloned()
+            .collect())
+    }
+
+    async fn remove_recent_message(&self, chat_id: i64, message_id: i32) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.recent_messages.len();
+
+        t.recent_messages
+            .retain(|m| !(m.chat_id == chat_id && m.message_id == message_id));
+
+        Ok(t.recent_messages.len() < before)
+    }
+
+    async fn remove_recent_messages_before(&self, timestamp: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.recent_messages.len();
+
+        t.recent_messages.retain(|m| m.timestamp >= timestamp);
+
+        Ok(t.recent_messages.len() < before)
+    }
+
+    async fn get_member(&self, chat_id: i64, user_id: i64) -> Result<Option<Member>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.members
+            .iter()
+            .find(|m| m.chat_id == chat_id && m.user_id == user_id)
+            .cloned())
+    }
+
+    async fn get_member_by_username(
+        &self,
+        chat_id: i64,
+        username: &str,
+    ) -> Result<Option<Member>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.members
+            .iter()
+            .find(|m| {
+                m.chat_id == chat_id
+                    && matches!(&m.username, Some(u) if u.eq_ignore_ascii_case(username))
+            })
+            .cloned())
+    }
+
+    async fn create_strike(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        poll_id: i32,
+        timestamp: i64,
+    ) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        if t.strikes
+            .iter()
+            .any(|s| s.chat_id == chat_id && s.poll_id == poll_id)
+        {
+            return Ok(false);
+        }
+
+        t.strikes.push(Strike {
+            chat_id,
+            user_id,
+            poll_id,
+            timestamp,
+            escalation: None,
+        });
+
+        Ok(true)
+    }
+
+    async fn set_strike_escalation(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+        escalation: Escalation,
+    ) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t
+            .strikes
+            .iter_mut()
+            .find(|s| s.chat_id == chat_id && s.poll_id == poll_id)
+        {
+            Some(s) => {
+                s.escalation = Some(escalation);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn count_strikes(&self, chat_id: i64, user_id: i64, since: i64) -> Result<i64, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.strikes
+            .iter()
+            .filter(|s| s.chat_id == chat_id && s.user_id == user_id && s.timestamp >= since)
+            .count() as i64)
+    }
+
+    async fn remove_strikes(&self, chat_id: i64, user_id: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.strikes.len();
+
+        t.strikes
+            .retain(|s| !(s.chat_id == chat_id && s.user_id == user_id));
+
+        Ok(t.strikes.len() < before)
+    }
+
+    async fn remove_strikes_before(&self, chat_id: i64, before: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let count = t.strikes.len();
+
+        t.strikes
+            .retain(|s| !(s.chat_id == chat_id && s.timestamp < before));
+
+        Ok(t.strikes.len() < count)
+    }
+
+    async fn create_archive(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+        user_id: i64,
+        kind: ContentKind,
+        file_id: Option<&str>,
+        text: Option<&str>,
+    ) -> Result<(), Error> {
+        let mut t = self.tables.lock().unwrap();
+        let id = t.next_id();
+
+        t.archives.push(Archive {
+            id,
+            chat_id,
+            poll_id,
+            user_id,
+            kind: kind.as_str().into(),
+            file_id: file_id.map(Into::into),
+            text: text.map(Into::into),
+            expires: None,
+            thread_id: None,
+        });
+
+        Ok(())
+    }
+
+    async fn get_archive(&self, chat_id: i64, poll_id: i32) -> Result<Option<Archive>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.archives
+            .iter()
+            .find(|a| a.chat_id == chat_id && a.poll_id == poll_id)
+            .cloned())
+    }
+
+    async fn set_archive_expiry(&self, archive_id: i64, expires: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t.archives.iter_mut().find(|a| a.id == archive_id) {
+            Some(a) => {
+                a.expires = Some(expires);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_archive_thread(&self, archive_id: i64, thread_id: i32) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t.archives.iter_mut().find(|a| a.id == archive_id) {
+            Some(a) => {
+                a.thread_id = Some(thread_id);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn remove_archive(&self, archive_id: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.archives.len();
+
+        t.archives.retain(|a| a.id != archive_id);
+
+        Ok(t.archives.len() < before)
+    }
+
+    async fn remove_stale_archives(&self, timestamp: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.archives.len();
+        let polls = t
+            .polls
+            .iter()
+            .map(|p| (p.chat_id, p.poll_id))
+            .collect::<Vec<(i64, i32)>>();
+
+        t.archives.retain(|a| match a.expires {
+            Some(expires) => expires >= timestamp,
+            None => polls.contains(&(a.chat_id, a.poll_id)),
+        });
+
+        Ok(t.archives.len() < before)
+    }
+
+    async fn create_federation(
+        &self,
+        name: &str,
+        owner_chat_id: i64,
+        token: &str,
+    ) -> Result<(), Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        if t.federations.iter().any(|f| f.token == token) {
+            return Err(Error::Protocol(format!(
+                "a federation with token {} already exists",
+                token
+            )));
+        }
+
+        let id = t.next_id();
+
+        t.federations.push(Federation {
+            id,
+            name: name.into(),
+            owner_chat_id,
+            token: token.into(),
+        });
+
+        Ok(())
+    }
+
+    async fn get_federation(&self, federation_id: i64) -> Result<Option<Federation>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.federations
+            .iter()
+            .find(|f| f.id == federation_id)
+            .cloned())
+    }
+
+    async fn get_federation_by_token(&self, token: &str) -> Result<Option<Federation>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.federations.iter().find(|f| f.token == token).cloned())
+    }
+
+    async fn set_federation_token(&self, federation_id: i64, token: &str) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t.federations.iter_mut().find(|f| f.id == federation_id) {
+            Some(f) => {
+                f.token = token.into();
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn get_federation_chats(&self, federation_id: i64) -> Result<Vec<Chat>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.chats
+            .iter()
+            .filter(|c| c.federation_id == Some(federation_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn remove_empty_federations(&self) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.federations.len();
+        let used = t
+            .chats
+            .iter()
+            .filter_map(|c| c.federation_id)
+            .collect::<Vec<i64>>();
+
+        t.federations.retain(|f| used.contains(&f.id));
+        t.federation_bans
+            .retain(|b| used.contains(&b.federation_id));
+
+        Ok(t.federations.len() < before)
+    }
+
+    async fn create_federation_ban(
+        &self,
+        federation_id: i64,
+        user_id: i64,
+        chat_id: i64,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t
+            .federation_bans
+            .iter_mut()
+            .find(|b| b.federation_id == federation_id && b.user_id == user_id)
+        {
+            Some(b) => {
+                b.chat_id = chat_id;
+                b.timestamp = timestamp;
+            }
+            None => {
+                let id = t.next_id();
+
+                t.federation_bans.push(FederationBan {
+                    id,
+                    federation_id,
+                    user_id,
+                    chat_id,
+                    timestamp,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_federation_ban(
+        &self,
+        federation_id: i64,
+        user_id: i64,
+    ) -> Result<Option<FederationBan>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.federation_bans
+            .iter()
+            .find(|b| b.federation_id == federation_id && b.user_id == user_id)
+            .cloned())
+    }
+
+    async fn remove_federation_ban(&self, federation_id: i64, user_id: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.federation_bans.len();
+
+        t.federation_bans
+            .retain(|b| !(b.federation_id == federation_id && b.user_id == user_id));
+
+        Ok(t.federation_bans.len() < before)
+    }
+
+    async fn remove_poll_strike(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+    ) -> Result<Option<Escalation>, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t
+            .strikes
+            .iter()
+            .position(|s| s.chat_id == chat_id && s.poll_id == poll_id)
+        {
+            Some(i) => Ok(t.strikes.remove(i).escalation),
+            None => Ok(None),
+        }
+    }
+
+    async fn create_appeal(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+        user_id: i64,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        let mut t = self.tables.lock().unwrap();
+        let id = t.next_id();
+
+        t.appeals.push(Appeal {
+            id,
+            chat_id,
+            poll_id,
+            user_id,
+            statement: None,
+            status: AppealStatus::Open.as_str().into(),
+            timestamp,
+        });
+
+        Ok(())
+    }
+
+    async fn get_appeal(&self, chat_id: i64, poll_id: i32) -> Result<Option<Appeal>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.appeals
+            .iter()
+            .find(|a| a.chat_id == chat_id && a.poll_id == poll_id)
+            .cloned())
+    }
+
+    async fn get_user_appeal(
+        &self,
+        user_id: i64,
+        status: AppealStatus,
+    ) -> Result<Option<Appeal>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.appeals
+            .iter()
+            .rev()
+            .find(|a| a.user_id == user_id && a.status() == status)
+            .cloned())
+    }
+
+    async fn set_appeal_status(&self, appeal_id: i64, status: AppealStatus) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t.appeals.iter_mut().find(|a| a.id == appeal_id) {
+            Some(a) => {
+                a.status = status.as_str().into();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn decide_appeal(&self, appeal_id: i64, status: AppealStatus) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t
+            .appeals
+            .iter_mut()
+            .find(|a| a.id == appeal_id && a.status == AppealStatus::Pending.as_str())
+        {
+            Some(a) => {
+                a.status = status.as_str().into();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_appeal_statement(&self, appeal_id: i64, statement: &str) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t.appeals.iter_mut().find(|a| a.id == appeal_id) {
+            Some(a) => {
+                a.statement = Some(statement.into());
+                a.status = AppealStatus::Pending.as_str().into();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn remove_appeals_before(&self, timestamp: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.appeals.len();
+
+        t.appeals.retain(|a| a.timestamp >= timestamp);
+
+        Ok(t.appeals.len() < before)
+    }
+
+    async fn create_pattern(&self, chat_id: i64, pattern: &str, strict: bool) -> Result<(), Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t
+            .patterns
+            .iter_mut()
+            .find(|p| p.chat_id == chat_id && p.pattern == pattern)
+        {
+            Some(p) => p.strict = strict,
+            None => {
+                let id = t.next_id();
+
+                t.patterns.push(Pattern {
+                    id,
+                    chat_id,
+                    pattern: pattern.into(),
+                    strict,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_patterns(&self, chat_id: i64) -> Result<Vec<Pattern>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.patterns
+            .iter()
+            .filter(|p| p.chat_id == chat_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn remove_pattern(&self, chat_id: i64, pattern: &str) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.patterns.len();
+
+        t.patterns
+            .retain(|p| !(p.chat_id == chat_id && p.pattern == pattern));
+
+        Ok(t.patterns.len() < before)
+    }
+
+    async fn create_fingerprint(
+        &self,
+        chat_id: i64,
+        value: &str,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        let mut t = self.tables.lock().unwrap();
+
+        match t
+            .fingerprints
+            .iter_mut()
+            .find(|f| f.chat_id == chat_id && f.value == value)
+        {
+            Some(f) => f.timestamp = timestamp,
+            None => t.fingerprints.push(Fingerprint {
+                chat_id,
+                value: value.into(),
+                timestamp,
+            }),
+        }
+
+        Ok(())
+    }
+
+    async fn has_fingerprint(&self, chat_id: i64, value: &str) -> Result<bool, Error> {
+        let t = self.tables.lock().unwrap();
+        let sharing = t
+            .chats
+            .iter()
+            .filter(|c| c.share_fingerprints)
+            .map(|c| c.chat_id)
+            .collect::<Vec<i64>>();
+        let shares = sharing.contains(&chat_id);
+
+        Ok(t.fingerprints.iter().any(|f| {
+            f.value == value && (f.chat_id == chat_id || (shares && sharing.contains(&f.chat_id)))
+        }))
+    }
+
+    async fn remove_fingerprints_before(&self, timestamp: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.fingerprints.len();
+
+        t.fingerprints.retain(|f| f.timestamp >= timestamp);
+
+        Ok(t.fingerprints.len() < before)
+    }
+
+    async fn get_reason_votes(&self, chat_id: i64, reason: &str) -> Result<Option<i64>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.reason_vote_counts
+            .iter()
+            .find(|r| r.chat_id == chat_id && r.reason == reason)
+            .map(|r| r.minimum_vote_count))
+    }
+
+    async fn set_reason_votes(
+        &self,
+        chat_id: i64,
+        reason: &str,
+        votes_count: i64,
+    ) -> Result<bool, Error> {
+        self.remove_reason_votes(chat_id, reason).await?;
+
+        self.tables
+            .lock()
+            .unwrap()
+            .reason_vote_counts
+            .push(ReasonVoteCount {
+                chat_id,
+                reason: reason.into(),
+                minimum_vote_count: votes_count,
+            });
+
+        Ok(true)
+    }
+
+    async fn remove_reason_votes(&self, chat_id: i64, reason: &str) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.reason_vote_counts.len();
+
+        t.reason_vote_counts
+            .retain(|r| !(r.chat_id == chat_id && r.reason == reason));
+
+        Ok(t.reason_vote_counts.len() < before)
+    }
+
+    async fn get_type_votes(&self, chat_id: i64, kind: ContentKind) -> Result<Option<i64>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.type_vote_counts
+            .iter()
+            .find(|r| r.chat_id == chat_id && r.kind == kind)
+            .map(|r| r.minimum_vote_count))
+    }
+
+    async fn set_type_votes(
+        &self,
+        chat_id: i64,
+        kind: ContentKind,
+        votes_count: i64,
+    ) -> Result<bool, Error> {
+        self.remove_type_votes(chat_id, kind).await?;
+
+        self.tables
+            .lock()
+            .unwrap()
+            .type_vote_counts
+            .push(TypeVoteCount {
+                chat_id,
+                kind,
+                minimum_vote_count: votes_count,
+            });
+
+        Ok(true)
+    }
+
+    async fn remove_type_votes(&self, chat_id: i64, kind: ContentKind) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.type_vote_counts.len();
+
+        t.type_vote_counts
+            .retain(|r| !(r.chat_id == chat_id && r.kind == kind));
+
+        Ok(t.type_vote_counts.len() < before)
+    }
+
+    async fn get_topic(&self, chat_id: i64, thread_id: i32) -> Result<Option<Topic>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.topics
+            .iter()
+            .find(|r| r.chat_id == chat_id && r.thread_id == thread_id)
+            .cloned())
+    }
+
+    async fn set_topic_votes(
+        &self,
+        chat_id: i64,
+        thread_id: i32,
+        votes_count: i64,
+    ) -> Result<bool, Error> {
+        self.topic_mut(chat_id, thread_id, |r| r.minimum_vote_count = votes_count);
+
+        Ok(true)
+    }
+
+    async fn set_topic_enabled(
+        &self,
+        chat_id: i64,
+        thread_id: i32,
+        enabled: bool,
+    ) -> Result<bool, Error> {
+        self.topic_mut(chat_id, thread_id, |r| r.enabled = enabled);
+
+        Ok(true)
+    }
+
+    async fn remove_chat(&self, chat_id: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.chats.len();
+        let polls = t
+            .polls
+            .iter()
+            .filter(|p| p.chat_id == chat_id)
+            .map(|p| p.id)
+            .collect::<Vec<i64>>();
+
+        t.voters.retain(|v| !polls.contains(&v.poll_id));
+        t.polls.retain(|p| p.chat_id != chat_id);
+        t.members.retain(|m| m.chat_id != chat_id);
+        t.recent_messages.retain(|m| m.chat_id != chat_id);
+        t.poll_starts.retain(|s| s.chat_id != chat_id);
+        t.strikes.retain(|s| s.chat_id != chat_id);
+        t.fingerprints.retain(|f| f.chat_id != chat_id);
+        t.patterns.retain(|p| p.chat_id != chat_id);
+        t.archives.retain(|a| a.chat_id != chat_id);
+        t.appeals.retain(|a| a.chat_id != chat_id);
+        t.reason_vote_counts.retain(|r| r.chat_id != chat_id);
+        t.type_vote_counts.retain(|r| r.chat_id != chat_id);
+        t.topics.retain(|r| r.chat_id != chat_id);
+        t.scheduled_to_delete.retain(|m| m.chat_id != chat_id);
+        t.chats.retain(|c| c.chat_id != chat_id);
+
+        for f in t.federations.iter_mut() {
+            if f.owner_chat_id == chat_id {
+                f.token = new_token();
+            }
+        }
+
+        Ok(t.chats.len() < before)
+    }
+
+    async fn schedule_message_delete(
+        &self,
+        chat_id: i64,
+        message_id: i64,
+        timestamp: i64,
+    ) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let id = t.next_id();
+
+        t.scheduled_to_delete.push(MessageToDelete {
+            id,
+            chat_id,
+            message_id: message_id.try_into().unwrap(),
+            timestamp,
+        });
+
+        Ok(true)
+    }
+
+    async fn get_pending_messages_to_delete(
+        &self,
+        timestamp: i64,
+    ) -> Result<Vec<MessageToDelete>, Error> {
+        let t = self.tables.lock().unwrap();
+
+        Ok(t.scheduled_to_delete
+            .iter()
+            .filter(|m| m.timestamp <= timestamp)
+            .cloned()
+            .collect())
+    }
+
+    async fn remove_from_scheduled_delete(&self, id: i64) -> Result<bool, Error> {
+        let mut t = self.tables.lock().unwrap();
+        let before = t.scheduled_to_delete.len();
+
+        t.scheduled_to_delete.retain(|m| m.id != id);
+
+        Ok(t.scheduled_to_delete.len() < before)
+    }
+}
@@ -0,0 +1,70 @@
+use regex::Regex;
+
+use crate::storage::MAX_TRACKED_MESSAGES;
+use crate::types::Scope;
+
+/// Reason keywords with their own translation and, optionally, their own vote threshold.
+pub const REASONS: &[&str] = &["spam", "offtopic", "nsfw"];
+
+/// Longest reason, in characters, stored with a poll. The rest is cut off.
+const MAX_REASON_LEN: usize = 200;
+
+/// Longest time, in minutes, a poll can reach back. Older messages can not be deleted by bots
+/// anyway.
+pub const MAX_SCOPE_MINUTES: i64 = 24 * 60;
+
+/// The text left in a trigger message once the `/delete` command and mentions of the bot
+/// are removed, or `None` if nothing is left.
+pub fn parse(username: &str, text: &str) -> Option<String> {
+    let trigger = Regex::new(&format!(
+        r"(?i)^/delete(@{u})?\b|@{u}\b",
+        u = regex::escape(username)
+    ))
+    .unwrap();
+
+    let reason = trigger
+        .replace_all(text, " ")
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .chars()
+        .take(MAX_REASON_LEN)
+        .collect::<String>();
+
+    if reason.is_empty() {
+        None
+    } else {
+        Some(reason)
+    }
+}
+
+/// The keyword a reason starts with, if any. `"Spam, again"` is `Some("spam")`.
+pub fn category(reason: &str) -> Option<&'static str> {
+    let first = reason
+        .split(|c: char| !c.is_alphanumeric())
+        .next()?
+        .to_lowercase();
+
+    REASONS.iter().find(|r| **r == first).copied()
+}
+
+/// Splits a leading scope off a reason: `+10` reaches the author's last 10 messages and `15m`
+/// everything they sent in the last 15 minutes. Out of range values are clamped.
+pub fn split_scope(reason: &str) -> (Option<Scope>, Option<String>) {
+    let scope = Regex::new(r"^(?:\+(\d+)|(\d+)m)(?:\s+|$)").unwrap();
+
+    let captures = match scope.captures(reason) {
+        Some(c) => c,
+        None => return (None, Some(reason.into())),
+    };
+
+    let number = |i: usize| captures.get(i).and_then(|m| m.as_str().parse::<i64>().ok());
+    let scope = match (number(1), number(2)) {
+        (Some(n), _) if n > 0 => Some(Scope::Messages(n.min(MAX_TRACKED_MESSAGES))),
+        (_, Some(m)) if m > 0 => Some(Scope::Minutes(m.min(MAX_SCOPE_MINUTES))),
+        _ => None,
+    };
+    let rest = &reason[captures.get(0).unwrap().end()..];
+
+    (scope, Some(rest.to_string()).filter(|r| !r.is_empty()))
+}
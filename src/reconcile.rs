@@ -0,0 +1,106 @@
+//! Brings stored state back in line with Telegram: forgets chats the bot is no longer in, closes
+//! polls whose poll or target message was deleted by someone else and drops message ids too old
+//! to be deleted, strikes past their chat's window, fingerprints nobody reposted for a
+//! while, archives that can no longer be restored, old appeals and federations every chat
+//! has left.
+
+use teloxide::{
+    requests::{Request, Requester},
+    types::MessageId,
+    ApiError, RequestError,
+};
+
+use crate::handlers::utils::{close_poll, message_gone, now, update_count};
+use crate::storage::{Store, APPEAL_TTL};
+use crate::types::{Configuration, DeleteIttBot, Localization};
+
+/// Seconds a tracked message is remembered. Bots can not delete older messages.
+const TRACKED_MESSAGE_TTL: i64 = 48 * 60 * 60;
+
+/// Seconds a fingerprint is remembered after the content was last deleted by vote.
+const FINGERPRINT_TTL: i64 = 30 * 24 * 60 * 60;
+
+/// Whether `e` means the bot can no longer act in the chat at all.
+fn chat_gone(e: &RequestError) -> bool {
+    matches!(
+        e,
+        RequestError::Api(
+            ApiError::ChatNotFound
+                | ApiError::BotKicked
+                | ApiError::BotKickedFromSupergroup
+                | ApiError::GroupDeactivated
+        )
+    )
+}
+
+/// Removes every chat the bot has left or was removed from.
+async fn purge_chats(bot: &DeleteIttBot, db: &Store) {
+    let me = match bot.get_me().send().await {
+        Ok(me) => me,
+        Err(_) => return,
+    };
+
+    for chat_id in db.get_chat_ids().await.unwrap_or_default() {
+        let gone = match bot.get_chat_member(chat_id.to_string(), me.id).await {
+            Ok(member) => !member.is_present(),
+            Err(e) => chat_gone(&e),
+        };
+
+        if gone {
+            db.remove_chat(chat_id).await.ok();
+        }
+    }
+}
+
+/// Closes every poll whose poll message or target message no longer exists.
+async fn purge_polls(bot: &DeleteIttBot, db: &Store, config: &Configuration, loc: &Localization) {
+    for info in db.get_polls().await.unwrap_or_default() {
+        // Closes the poll by itself if the poll message is gone.
+        if update_count(bot, &info, db, config, loc).await.is_err() {
+            continue;
+        }
+
+        if let Ok(None) = db.get_poll(info.chat_id, info.poll_id).await {
+            continue;
+        }
+
+        // The target is someone else's message, so editing it fails either way. Only the
+        // error tells whether it still exists.
+        if let Err(e) = bot
+            .edit_message_reply_markup(info.chat_id.to_string(), MessageId(info.message_id))
+            .await
+        {
+            if message_gone(&e) {
+                close_poll(bot, &info, db).await.ok();
+            }
+        }
+    }
+}
+
+/// Forgets strikes that no longer count in their chat.
+async fn decay_strikes(db: &Store) {
+    for chat_id in db.get_chat_ids().await.unwrap_or_default() {
+        if let Ok(Some(chat)) = db.get_chat(chat_id).await {
+            db.remove_strikes_before(chat_id, now() - chat.strike_window)
+                .await
+                .ok();
+        }
+    }
+}
+
+/// Runs one full reconciliation pass.
+pub async fn run(bot: &DeleteIttBot, db: &Store, config: &Configuration, loc: &Localization) {
+    purge_chats(bot, db).await;
+    purge_polls(bot, db, config, loc).await;
+    db.remove_orphan_voters().await.ok();
+    db.remove_recent_messages_before(now() - TRACKED_MESSAGE_TTL)
+        .await
+        .ok();
+    decay_strikes(db).await;
+    db.remove_fingerprints_before(now() - FINGERPRINT_TTL)
+        .await
+        .ok();
+    db.remove_stale_archives(now()).await.ok();
+    db.remove_appeals_before(now() - APPEAL_TTL).await.ok();
+    db.remove_empty_federations().await.ok();
+}
@@ -0,0 +1,67 @@
+use teloxide::{requests::Requester, types::MessageId};
+
+use crate::health::Health;
+use crate::reconcile;
+use crate::storage::Store;
+use crate::types::{Configuration, DeleteIttBot, Localization};
+
+/// Seconds between two passes over the scheduled deletions.
+const TICK_INTERVAL: u64 = 5;
+
+/// Seconds between two [`reconcile`] passes.
+const RECONCILE_INTERVAL: u64 = 60 * 60;
+
+/// Deletes every message scheduled at or before `timestamp`.
+pub async fn run_pending(bot: &DeleteIttBot, db: &Store, timestamp: i64) {
+    if let Ok(l) = db.get_pending_messages_to_delete(timestamp).await {
+        for m in l.into_iter() {
+            bot.delete_message(m.chat_id.to_string(), MessageId(m.message_id))
+                .await
+                .ok();
+            db.remove_from_scheduled_delete(m.id).await.ok();
+        }
+    };
+}
+
+/// Runs [`run_pending`] every few seconds, and [`reconcile::run`] every hour, until the process
+/// exits. The two run in separate tasks, so a long reconcile pass neither holds up scheduled
+/// deletions nor makes the scheduler look stale.
+pub fn spawn(
+    bot: DeleteIttBot,
+    db: Store,
+    config: Configuration,
+    loc: Localization,
+    health: Health,
+) {
+    {
+        let bot = bot.clone();
+        let db = db.clone();
+
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(tokio::time::Duration::from_secs(RECONCILE_INTERVAL));
+
+            loop {
+                interval.tick().await;
+                reconcile::run(&bot, &db, &config, &loc).await;
+            }
+        });
+    }
+
+    tokio::spawn(async move {
+        loop {
+            health.scheduler_ticked();
+
+            let ts: i64 = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+                .try_into()
+                .unwrap();
+
+            run_pending(&bot, &db, ts).await;
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(TICK_INTERVAL)).await;
+        }
+    });
+}
@@ -0,0 +1,84 @@
+//! Who sent a message. Besides users, messages in groups can be sent on behalf of a chat: a
+//! channel, including the posts a group's linked channel forwards into it, or the group itself
+//! when an admin stays anonymous. Chats are stored under their ids, which are negative and so
+//! never clash with user ids.
+
+use teloxide::{
+    requests::Requester,
+    types::{Chat, ChatId, Message, User, UserId},
+    RequestError,
+};
+
+use crate::format::Markdown;
+use crate::types::DeleteIttBot;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Sender<'a> {
+    User(&'a User),
+    Chat(&'a Chat),
+}
+
+impl Sender<'_> {
+    /// The id the sender is stored under.
+    pub fn id(&self) -> i64 {
+        match self {
+            Sender::User(user) => user.id.0.try_into().unwrap(),
+            Sender::Chat(chat) => chat.id.0,
+        }
+    }
+
+    pub fn name(&self) -> Markdown {
+        match self {
+            Sender::User(user) => Markdown::user(user),
+            Sender::Chat(chat) => Markdown::chat(chat),
+        }
+    }
+}
+
+/// The sender of `msg`: the chat it was sent on behalf of, or else its author. `None` for
+/// service messages.
+pub fn sender(msg: &Message) -> Option<Sender<'_>> {
+    match msg.sender_chat() {
+        Some(chat) => Some(Sender::Chat(chat)),
+        None => msg.from().map(Sender::User),
+    }
+}
+
+/// Whether `msg` was sent by an admin posting as the group itself.
+pub fn is_anonymous_admin(msg: &Message) -> bool {
+    matches!(msg.sender_chat(), Some(chat) if chat.id == msg.chat.id)
+}
+
+/// Whether the sender stored as `id` is a chat rather than a user.
+pub fn is_chat(id: i64) -> bool {
+    id < 0
+}
+
+/// How to show the sender stored as `id` in `chat_id`.
+pub async fn name(bot: &DeleteIttBot, chat_id: i64, id: i64) -> Result<Markdown, RequestError> {
+    if is_chat(id) {
+        let chat = bot.get_chat(ChatId(id)).await?;
+
+        return Ok(Markdown::chat(&chat));
+    }
+
+    let member = bot
+        .get_chat_member(ChatId(chat_id), UserId(id.try_into().unwrap()))
+        .await?;
+
+    Ok(Markdown::user(&member.user))
+}
+
+/// Bans the sender stored as `id` from `chat_id`. Banned chats can no longer post there on
+/// behalf of themselves.
+pub async fn ban(bot: &DeleteIttBot, chat_id: i64, id: i64) -> Result<(), RequestError> {
+    if is_chat(id) {
+        bot.ban_chat_sender_chat(ChatId(chat_id), ChatId(id))
+            .await?;
+    } else {
+        bot.ban_chat_member(ChatId(chat_id), UserId(id.try_into().unwrap()))
+            .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,490 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::Error;
+
+use crate::database::{
+    Appeal, Archive, Chat, Database, Federation, FederationBan, Member, MessageToDelete, Pattern,
+    Poll, RecentMessage, Topic, Voter,
+};
+use crate::memory::MemoryStorage;
+use crate::types::{
+    AppealStatus, ContentKind, Escalation, FederationAction, FingerprintAction, PollOrigin, Scope,
+    Trigger, VoteType,
+};
+
+/// Everything the handlers persist: polls, voters, per-chat settings and scheduled deletions.
+///
+/// [`Database`](crate::database::Database) stores them in SQL,
+/// [`MemoryStorage`](crate::memory::MemoryStorage) keeps them in process memory.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn ping(&self) -> Result<(), Error>;
+
+    /// Fails if `chat_id` already has an open poll about `message_id`.
+    async fn create_poll(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+        message_id: i32,
+        message_user_id: i64,
+        minimum_vote_count: i64,
+        reason: Option<&str>,
+    ) -> Result<(), Error>;
+
+    async fn get_poll(&self, chat_id: i64, poll_id: i32) -> Result<Option<Poll>, Error>;
+
+    /// The open poll about `message_id`, if there is one.
+    async fn get_poll_by_message(
+        &self,
+        chat_id: i64,
+        message_id: i32,
+    ) -> Result<Option<Poll>, Error>;
+
+    /// Has the poll reach further messages of the target's author. A `Scope::Minutes` window is
+    /// measured back from `date`, the target's timestamp.
+    async fn set_poll_scope(&self, poll_id: i64, scope: Scope, date: i64) -> Result<bool, Error>;
+
+    /// Remembers what the poll's target looked like, so it can be recognized if it is deleted
+    /// and posted again.
+    async fn set_poll_fingerprints(
+        &self,
+        poll_id: i64,
+        fingerprints: &[String],
+    ) -> Result<bool, Error>;
+
+    /// Marks a poll as opened by the bot itself.
+    async fn set_poll_origin(&self, poll_id: i64, origin: PollOrigin) -> Result<bool, Error>;
+
+    /// Remembers the forum topic the poll was opened in.
+    async fn set_poll_thread(&self, poll_id: i64, thread_id: i32) -> Result<bool, Error>;
+
+    async fn register_vote(&self, poll_id: i64, v: VoteType) -> Result<bool, Error>;
+
+    async fn remove_poll(&self, poll_id: i64) -> Result<bool, Error>;
+
+    /// Every open poll, in every chat.
+    async fn get_polls(&self) -> Result<Vec<Poll>, Error>;
+
+    /// Number of polls in `chat_id` that are still open.
+    async fn count_polls(&self, chat_id: i64) -> Result<i64, Error>;
+
+    /// Number of open polls in `chat_id` about messages from `message_user_id`.
+    async fn count_polls_about(&self, chat_id: i64, message_user_id: i64) -> Result<i64, Error>;
+
+    async fn create_poll_start(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        timestamp: i64,
+    ) -> Result<(), Error>;
+
+    /// Number of polls `user_id` started in `chat_id` at or after `since`.
+    async fn count_poll_starts(&self, chat_id: i64, user_id: i64, since: i64)
+        -> Result<i64, Error>;
+
+    /// Forgets the polls started in `chat_id` before `before`.
+    async fn remove_poll_starts(&self, chat_id: i64, before: i64) -> Result<bool, Error>;
+
+    async fn create_voter(&self, poll_id: i64, user_id: i64) -> Result<(), Error>;
+
+    async fn get_voter(&self, poll_id: i64, user_id: i64) -> Result<Option<Voter>, Error>;
+
+    async fn remove_voter(&self, voter_id: i64) -> Result<bool, Error>;
+
+    async fn remove_voters(&self, poll_id: i64) -> Result<bool, Error>;
+
+    /// Removes voters left behind by polls that no longer exist.
+    async fn remove_orphan_voters(&self) -> Result<bool, Error>;
+
+    async fn create_chat(
+        &self,
+        chat_id: i64,
+        minimum_vote_count: i64,
+        locale: &str,
+        poll_delete_delay: i64,
+    ) -> Result<bool, Error>;
+
+    /// Ids of every chat with settings or open polls.
+    async fn get_chat_ids(&self) -> Result<Vec<i64>, Error>;
+
+    async fn get_chat(&self, chat_id: i64) -> Result<Option<Chat>, Error>;
+
+    async fn get_chat_votes(&self, chat_id: i64) -> Result<Option<i64>, Error>;
+
+    async fn set_chat_votes(&self, chat_id: i64, votes_count: i64) -> Result<bool, Error>;
+
+    async fn get_chat_locale(&self, chat_id: i64) -> Result<Option<String>, Error>;
+
+    async fn set_chat_locale(&self, chat_id: i64, locale: &str) -> Result<bool, Error>;
+
+    async fn get_chat_poll_delete_delay(&self, chat_id: i64) -> Result<Option<i64>, Error>;
+
+    async fn set_chat_poll_delete_delay(
+        &self,
+        chat_id: i64,
+        poll_delete_delay: i64,
+    ) -> Result<bool, Error>;
+
+    async fn get_chat_trigger(&self, chat_id: i64, trigger: Trigger)
+        -> Result<Option<bool>, Error>;
+
+    async fn set_chat_trigger(
+        &self,
+        chat_id: i64,
+        trigger: Trigger,
+        enabled: bool,
+    ) -> Result<bool, Error>;
+
+    async fn get_chat_initiator_votes(&self, chat_id: i64) -> Result<Option<bool>, Error>;
+
+    async fn set_chat_initiator_votes(&self, chat_id: i64, enabled: bool) -> Result<bool, Error>;
+
+    async fn set_chat_exclude_bots(&self, chat_id: i64, enabled: bool) -> Result<bool, Error>;
+
+    async fn set_chat_voter_min_age(&self, chat_id: i64, seconds: i64) -> Result<bool, Error>;
+
+    async fn set_chat_voter_min_messages(&self, chat_id: i64, count: i64) -> Result<bool, Error>;
+
+    async fn set_chat_poll_rate(
+        &self,
+        chat_id: i64,
+        count: i64,
+        window: i64,
+    ) -> Result<bool, Error>;
+
+    async fn set_chat_max_open_polls(&self, chat_id: i64, count: i64) -> Result<bool, Error>;
+
+    async fn set_chat_strike_window(&self, chat_id: i64, seconds: i64) -> Result<bool, Error>;
+
+    /// Sets how many strikes lead to `escalation`. Zero turns it off.
+    async fn set_chat_escalation(
+        &self,
+        chat_id: i64,
+        escalation: Escalation,
+        count: i64,
+    ) -> Result<bool, Error>;
+
+    /// Sets what happens to messages matching a fingerprint. `None` turns matching off.
+    async fn set_chat_fingerprint_action(
+        &self,
+        chat_id: i64,
+        action: Option<FingerprintAction>,
+    ) -> Result<bool, Error>;
+
+    async fn set_chat_share_fingerprints(&self, chat_id: i64, enabled: bool)
+        -> Result<bool, Error>;
+
+    /// Sets how long, in seconds, admins can restore messages deleted by vote. Zero turns
+    /// archiving off.
+    async fn set_chat_undo_window(&self, chat_id: i64, seconds: i64) -> Result<bool, Error>;
+
+    /// Sends appeals against deletions in `chat_id` to another chat, or back to the chat
+    /// itself with `None`.
+    async fn set_chat_appeal_chat(
+        &self,
+        chat_id: i64,
+        appeal_chat_id: Option<i64>,
+    ) -> Result<bool, Error>;
+
+    /// Makes `chat_id` a member of a federation, or of none.
+    async fn set_chat_federation(
+        &self,
+        chat_id: i64,
+        federation_id: Option<i64>,
+    ) -> Result<bool, Error>;
+
+    async fn set_chat_federation_action(
+        &self,
+        chat_id: i64,
+        action: FederationAction,
+    ) -> Result<bool, Error>;
+
+    /// Counts a message from `user_id`, remembering `timestamp` if it is the first one and
+    /// their current `username`.
+    async fn track_member(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        username: Option<&str>,
+        timestamp: i64,
+    ) -> Result<(), Error>;
+
+    /// Remembers a message so a poll can later reach it. Only the last
+    /// [`MAX_TRACKED_MESSAGES`] of each user in each chat are kept.
+    async fn track_message(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        message_id: i32,
+        timestamp: i64,
+    ) -> Result<(), Error>;
+
+    /// The newest tracked messages of `user_id` sent at or after `since`, newest first.
+    async fn get_recent_messages(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        since: i64,
+        limit: i64,
+    ) -> Result<Vec<RecentMessage>, Error>;
+
+    async fn remove_recent_message(&self, chat_id: i64, message_id: i32) -> Result<bool, Error>;
+
+    async fn remove_recent_messages_before(&self, timestamp: i64) -> Result<bool, Error>;
+
+    async fn get_member(&self, chat_id: i64, user_id: i64) -> Result<Option<Member>, Error>;
+
+    /// The member last seen with `username`, ignoring case.
+    async fn get_member_by_username(
+        &self,
+        chat_id: i64,
+        username: &str,
+    ) -> Result<Option<Member>, Error>;
+
+    /// Remembers that the poll `poll_id` deleted a message of `user_id`. A poll gives one strike:
+    /// returns false, and adds none, if it gave one already.
+    async fn create_strike(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        poll_id: i32,
+        timestamp: i64,
+    ) -> Result<bool, Error>;
+
+    /// Records that the strike the poll `poll_id` gave led to `escalation`.
+    async fn set_strike_escalation(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+        escalation: Escalation,
+    ) -> Result<bool, Error>;
+
+    /// Number of strikes `user_id` got in `chat_id` at or after `since`.
+    async fn count_strikes(&self, chat_id: i64, user_id: i64, since: i64) -> Result<i64, Error>;
+
+    async fn remove_strikes(&self, chat_id: i64, user_id: i64) -> Result<bool, Error>;
+
+    /// Forgets the strikes given in `chat_id` before `before`.
+    async fn remove_strikes_before(&self, chat_id: i64, before: i64) -> Result<bool, Error>;
+
+    /// Takes back the strike the poll `poll_id` gave. Returns the escalation it led to, if any.
+    async fn remove_poll_strike(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+    ) -> Result<Option<Escalation>, Error>;
+
+    /// Lets `user_id` appeal the deletion decided by the poll `poll_id`.
+    async fn create_appeal(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+        user_id: i64,
+        timestamp: i64,
+    ) -> Result<(), Error>;
+
+    async fn get_appeal(&self, chat_id: i64, poll_id: i32) -> Result<Option<Appeal>, Error>;
+
+    /// The newest appeal of `user_id` with `status`, in any chat.
+    async fn get_user_appeal(
+        &self,
+        user_id: i64,
+        status: AppealStatus,
+    ) -> Result<Option<Appeal>, Error>;
+
+    async fn set_appeal_status(&self, appeal_id: i64, status: AppealStatus) -> Result<bool, Error>;
+
+    /// Moves a pending appeal to `status`. Returns false if it was not pending anymore, so that
+    /// only one admin decides it.
+    async fn decide_appeal(&self, appeal_id: i64, status: AppealStatus) -> Result<bool, Error>;
+
+    /// Stores the author's statement, which makes the appeal pending.
+    async fn set_appeal_statement(&self, appeal_id: i64, statement: &str) -> Result<bool, Error>;
+
+    async fn remove_appeals_before(&self, timestamp: i64) -> Result<bool, Error>;
+
+    /// Adds a `/filter` pattern to `chat_id`, or changes whether it is strict if it is already
+    /// there.
+    async fn create_pattern(&self, chat_id: i64, pattern: &str, strict: bool) -> Result<(), Error>;
+
+    /// The chat's `/filter` patterns, oldest first.
+    async fn get_patterns(&self, chat_id: i64) -> Result<Vec<Pattern>, Error>;
+
+    async fn remove_pattern(&self, chat_id: i64, pattern: &str) -> Result<bool, Error>;
+
+    /// Keeps a copy of the target of the poll `poll_id` until the poll is decided.
+    async fn create_archive(
+        &self,
+        chat_id: i64,
+        poll_id: i32,
+        user_id: i64,
+        kind: ContentKind,
+        file_id: Option<&str>,
+        text: Option<&str>,
+    ) -> Result<(), Error>;
+
+    async fn get_archive(&self, chat_id: i64, poll_id: i32) -> Result<Option<Archive>, Error>;
+
+    /// Keeps the archive restorable until `expires`.
+    async fn set_archive_expiry(&self, archive_id: i64, expires: i64) -> Result<bool, Error>;
+
+    /// Has the archive restored into the forum topic `thread_id`.
+    async fn set_archive_thread(&self, archive_id: i64, thread_id: i32) -> Result<bool, Error>;
+
+    async fn remove_archive(&self, archive_id: i64) -> Result<bool, Error>;
+
+    /// Removes archives that expired before `timestamp`, and those of polls that were closed
+    /// without deleting anything.
+    async fn remove_stale_archives(&self, timestamp: i64) -> Result<bool, Error>;
+
+    async fn create_federation(
+        &self,
+        name: &str,
+        owner_chat_id: i64,
+        token: &str,
+    ) -> Result<(), Error>;
+
+    async fn get_federation(&self, federation_id: i64) -> Result<Option<Federation>, Error>;
+
+    async fn get_federation_by_token(&self, token: &str) -> Result<Option<Federation>, Error>;
+
+    /// Replaces the federation's join token. The old one stops working.
+    async fn set_federation_token(&self, federation_id: i64, token: &str) -> Result<bool, Error>;
+
+    /// The chats that are members of the federation.
+    async fn get_federation_chats(&self, federation_id: i64) -> Result<Vec<Chat>, Error>;
+
+    /// Removes federations no chat is a member of anymore, along with their bans.
+    async fn remove_empty_federations(&self) -> Result<bool, Error>;
+
+    /// Puts `user_id` on the federation's blocklist on behalf of `chat_id`, or refreshes the
+    /// entry if they are already on it.
+    async fn create_federation_ban(
+        &self,
+        federation_id: i64,
+        user_id: i64,
+        chat_id: i64,
+        timestamp: i64,
+    ) -> Result<(), Error>;
+
+    async fn get_federation_ban(
+        &self,
+        federation_id: i64,
+        user_id: i64,
+    ) -> Result<Option<FederationBan>, Error>;
+
+    async fn remove_federation_ban(&self, federation_id: i64, user_id: i64) -> Result<bool, Error>;
+
+    /// Remembers the fingerprint of content deleted by vote in `chat_id`, or refreshes its
+    /// `timestamp` if it is already known.
+    async fn create_fingerprint(
+        &self,
+        chat_id: i64,
+        value: &str,
+        timestamp: i64,
+    ) -> Result<(), Error>;
+
+    /// Whether `value` was deleted by vote in `chat_id` or, if the chat shares fingerprints,
+    /// in any other chat that shares them.
+    async fn has_fingerprint(&self, chat_id: i64, value: &str) -> Result<bool, Error>;
+
+    async fn remove_fingerprints_before(&self, timestamp: i64) -> Result<bool, Error>;
+
+    async fn get_reason_votes(&self, chat_id: i64, reason: &str) -> Result<Option<i64>, Error>;
+
+    async fn set_reason_votes(
+        &self,
+        chat_id: i64,
+        reason: &str,
+        votes_count: i64,
+    ) -> Result<bool, Error>;
+
+    async fn remove_reason_votes(&self, chat_id: i64, reason: &str) -> Result<bool, Error>;
+
+    /// The votes polls about messages of `kind` need in the chat, if it overrides them.
+    async fn get_type_votes(&self, chat_id: i64, kind: ContentKind) -> Result<Option<i64>, Error>;
+
+    async fn set_type_votes(
+        &self,
+        chat_id: i64,
+        kind: ContentKind,
+        votes_count: i64,
+    ) -> Result<bool, Error>;
+
+    async fn remove_type_votes(&self, chat_id: i64, kind: ContentKind) -> Result<bool, Error>;
+
+    /// The settings of the forum topic `thread_id`, if it overrides any.
+    async fn get_topic(&self, chat_id: i64, thread_id: i32) -> Result<Option<Topic>, Error>;
+
+    /// Sets the votes polls in the topic need. 0 goes back to the chat's count.
+    async fn set_topic_votes(
+        &self,
+        chat_id: i64,
+        thread_id: i32,
+        votes_count: i64,
+    ) -> Result<bool, Error>;
+
+    async fn set_topic_enabled(
+        &self,
+        chat_id: i64,
+        thread_id: i32,
+        enabled: bool,
+    ) -> Result<bool, Error>;
+
+    /// Forgets the chat's settings along with its polls, voters, members, strikes,
+    /// fingerprints, patterns, topic settings and scheduled deletions. Federations the chat
+    /// owns get a new token, as nobody is left to hand it out.
+    async fn remove_chat(&self, chat_id: i64) -> Result<bool, Error>;
+
+    async fn schedule_message_delete(
+        &self,
+        chat_id: i64,
+        message_id: i64,
+        timestamp: i64,
+    ) -> Result<bool, Error>;
+
+    async fn get_pending_messages_to_delete(
+        &self,
+        timestamp: i64,
+    ) -> Result<Vec<MessageToDelete>, Error>;
+
+    async fn remove_from_scheduled_delete(&self, id: i64) -> Result<bool, Error>;
+}
+
+/// The storage handle placed in the dependency map.
+pub type Store = Arc<dyn Storage>;
+
+/// Messages remembered per user and chat for polls that reach beyond their target.
+pub const MAX_TRACKED_MESSAGES: i64 = 50;
+
+/// Seconds a strike counts towards escalation unless a chat sets its own window.
+pub const DEFAULT_STRIKE_WINDOW: i64 = 7 * 24 * 60 * 60;
+
+/// Seconds the author of a deleted message has to appeal, and admins to decide.
+pub const APPEAL_TTL: i64 = 7 * 24 * 60 * 60;
+
+/// `db_url` value that selects [`MemoryStorage`] instead of a SQL database.
+pub const MEMORY_URL: &str = "memory";
+
+/// Opens the storage backend named by `url`.
+pub async fn open(url: &str, max_connections: u32) -> Store {
+    if url == MEMORY_URL {
+        Arc::new(MemoryStorage::new())
+    } else {
+        Arc::new(Database::new(url, max_connections).await)
+    }
+}
+
+/// Characters in a federation join token. Anyone holding it can join a chat they administer, so
+/// it has to be impossible to guess.
+const TOKEN_LEN: usize = 24;
+
+pub fn new_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
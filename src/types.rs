@@ -2,12 +2,14 @@ use std::sync::Arc;
 
 use loon::Dictionary;
 use teloxide::{
-    adaptors::{cache_me::CacheMe, AutoSend},
+    adaptors::cache_me::CacheMe,
     dispatching::DpHandlerDescription,
     prelude::{DependencyMap, Handler},
     Bot,
 };
 
+use crate::config::Config;
+
 pub type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
 pub type AtomicHandler = Handler<
     'static,
@@ -16,10 +18,225 @@ pub type AtomicHandler = Handler<
     DpHandlerDescription,
 >;
 pub type Localization = Arc<Dictionary>;
+pub type Configuration = Arc<Config>;
 pub type Locale = String;
-pub type DeleteIttBot = CacheMe<AutoSend<Bot>>;
+pub type DeleteIttBot = CacheMe<Bot>;
 
 pub enum VoteType {
     Yes,
     No,
 }
+
+/// How far a poll reaches beyond its target message, into the author's other recent messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// The author's last few messages.
+    Messages(i64),
+    /// Everything the author sent in the last few minutes.
+    Minutes(i64),
+}
+
+/// Ways of starting a poll. Each can be switched off per chat.
+#[derive(Debug, Clone, Copy)]
+pub enum Trigger {
+    Mention,
+    Command,
+}
+
+/// What happens to a user once enough of their messages were deleted by vote. Each step has
+/// its own strike count per chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escalation {
+    Mute,
+    Ban,
+}
+
+impl Escalation {
+    /// The name used in commands and stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Escalation::Mute => "mute",
+            Escalation::Ban => "ban",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "mute" => Some(Escalation::Mute),
+            "ban" => Some(Escalation::Ban),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to a new message whose content matches one deleted by vote before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FingerprintAction {
+    /// Delete it right away.
+    Delete,
+    /// Open a poll about it.
+    Poll,
+}
+
+impl FingerprintAction {
+    /// The name used in commands and stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FingerprintAction::Delete => "delete",
+            FingerprintAction::Poll => "poll",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "delete" => Some(FingerprintAction::Delete),
+            "poll" => Some(FingerprintAction::Poll),
+            _ => None,
+        }
+    }
+}
+
+/// Why the bot opened a poll by itself. Polls started by members have no origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollOrigin {
+    /// The message matches one of the chat's `/filter` patterns.
+    Filter,
+    /// The message repeats content deleted by vote before.
+    Repost,
+    /// The author is banned in the chat's federation.
+    Federation,
+}
+
+impl PollOrigin {
+    /// The name stored in the database and used in locale keys.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PollOrigin::Filter => "filter",
+            PollOrigin::Repost => "repost",
+            PollOrigin::Federation => "federation",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "filter" => Some(PollOrigin::Filter),
+            "repost" => Some(PollOrigin::Repost),
+            "federation" => Some(PollOrigin::Federation),
+            _ => None,
+        }
+    }
+}
+
+/// What a chat does about users banned elsewhere in its federation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FederationAction {
+    /// Ban them here too.
+    Ban,
+    /// Open a poll about each of their messages.
+    Poll,
+    /// Tell the chat, nothing more.
+    Warn,
+}
+
+impl FederationAction {
+    /// The name used in commands and stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FederationAction::Ban => "ban",
+            FederationAction::Poll => "poll",
+            FederationAction::Warn => "warn",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "ban" => Some(FederationAction::Ban),
+            "poll" => Some(FederationAction::Poll),
+            "warn" => Some(FederationAction::Warn),
+            _ => None,
+        }
+    }
+}
+
+/// The kind of content a message carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+    Text,
+    Photo,
+    Video,
+    Animation,
+    Document,
+    Audio,
+    Voice,
+    VideoNote,
+    Sticker,
+}
+
+impl ContentKind {
+    /// The name stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentKind::Text => "text",
+            ContentKind::Photo => "photo",
+            ContentKind::Video => "video",
+            ContentKind::Animation => "animation",
+            ContentKind::Document => "document",
+            ContentKind::Audio => "audio",
+            ContentKind::Voice => "voice",
+            ContentKind::VideoNote => "video_note",
+            ContentKind::Sticker => "sticker",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(ContentKind::Text),
+            "photo" => Some(ContentKind::Photo),
+            "video" => Some(ContentKind::Video),
+            "animation" => Some(ContentKind::Animation),
+            "document" => Some(ContentKind::Document),
+            "audio" => Some(ContentKind::Audio),
+            "voice" => Some(ContentKind::Voice),
+            "video_note" => Some(ContentKind::VideoNote),
+            "sticker" => Some(ContentKind::Sticker),
+            _ => None,
+        }
+    }
+}
+
+/// Where an appeal against a deletion stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppealStatus {
+    /// The author may appeal but has not started to.
+    Open,
+    /// The author opened the appeal link; their next private message is the statement.
+    Awaiting,
+    /// The statement was relayed and waits for an admin.
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl AppealStatus {
+    /// The name stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AppealStatus::Open => "open",
+            AppealStatus::Awaiting => "awaiting",
+            AppealStatus::Pending => "pending",
+            AppealStatus::Approved => "approved",
+            AppealStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "open" => Some(AppealStatus::Open),
+            "awaiting" => Some(AppealStatus::Awaiting),
+            "pending" => Some(AppealStatus::Pending),
+            "approved" => Some(AppealStatus::Approved),
+            "rejected" => Some(AppealStatus::Rejected),
+            _ => None,
+        }
+    }
+}
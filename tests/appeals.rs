@@ -0,0 +1,312 @@
+mod common;
+
+use common::*;
+use delete_itt::handlers::utils::now;
+use delete_itt::storage::APPEAL_TTL;
+use delete_itt::types::{AppealStatus, Escalation};
+use serde_json::{json, Value};
+
+const TARGET: i64 = 20;
+const ADMIN: i64 = 40;
+const LOG_CHAT: i64 = -1009;
+const START: &str = "/start appeal_-1001_10001";
+
+fn private(id: i64, from: i64, text: &str) -> Value {
+    let mut m = message(id, from, text);
+    m["chat"] = json!({ "id": from, "type": "private", "first_name": "User" });
+    m
+}
+
+/// Has message 1 from `TARGET` deleted by a poll that passes with a single vote.
+async fn delete_by_vote(h: &Harness) {
+    h.send(message_update(reply(
+        100,
+        10,
+        "/delete",
+        message(1, TARGET, "original text"),
+    )))
+    .await;
+
+    let poll = last_sent(h);
+    h.send(callback_update(31, poll, "vote_yes")).await;
+}
+
+/// The last message the bot sent to `chat_id`, as Telegram returned it.
+fn last_sent_to(h: &Harness, chat_id: i64) -> Value {
+    h.api
+        .calls()
+        .into_iter()
+        .rev()
+        .find(|c| c.method == "sendMessage" && c.body["chat_id"] == chat_id)
+        .map(|c| c.response["result"].clone())
+        .unwrap()
+}
+
+/// Deletes a message and has its author appeal it. Returns the message relayed to `relay_to`.
+async fn appeal(h: &Harness, relay_to: i64) -> Value {
+    delete_by_vote(h).await;
+    h.send(message_update(private(5, TARGET, START))).await;
+    h.send(message_update(private(6, TARGET, "It was a joke")))
+        .await;
+
+    last_sent_to(h, relay_to)
+}
+
+async fn status(h: &Harness) -> AppealStatus {
+    h.db.get_appeal(CHAT_ID, 10001)
+        .await
+        .unwrap()
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn approved_appeals_restore_and_clear_the_strike() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_undo_window(CHAT_ID, 600).await.unwrap();
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+
+    delete_by_vote(&h).await;
+    assert!(h.api.calls_to("editMessageText")[0]["text"]
+        .as_str()
+        .unwrap()
+        .contains("start=appeal_-1001_10001"));
+    assert_eq!(h.db.count_strikes(CHAT_ID, TARGET, 0).await.unwrap(), 1);
+
+    h.send(message_update(private(5, TARGET, START))).await;
+    assert!(last_sent_to(&h, TARGET)["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Tell me in one message"));
+    assert_eq!(status(&h).await, AppealStatus::Awaiting);
+
+    h.send(message_update(private(6, TARGET, "It was a joke")))
+        .await;
+    assert_eq!(
+        last_sent_to(&h, TARGET)["text"],
+        "Your appeal was sent to the admins"
+    );
+    let relay = last_sent_to(&h, CHAT_ID);
+    assert!(relay["text"].as_str().unwrap().ends_with("It was a joke"));
+    assert_eq!(
+        relay["reply_markup"]["inline_keyboard"][0][0]["callback_data"],
+        "appeal_approve:-1001_10001"
+    );
+    h.api.clear();
+
+    h.send(callback_update(
+        ADMIN,
+        relay.clone(),
+        "appeal_approve:-1001_10001",
+    ))
+    .await;
+
+    assert_eq!(status(&h).await, AppealStatus::Approved);
+    assert_eq!(h.db.count_strikes(CHAT_ID, TARGET, 0).await.unwrap(), 0);
+    let restored = h
+        .api
+        .calls_to("sendMessage")
+        .into_iter()
+        .filter(|m| m["chat_id"] == CHAT_ID)
+        .collect::<Vec<Value>>();
+    assert_eq!(restored[1]["text"], "original text");
+    assert!(h.api.calls_to("editMessageText")[0]["text"]
+        .as_str()
+        .unwrap()
+        .contains("Approved by"));
+    assert_eq!(last_sent_to(&h, TARGET)["text"], "Your appeal was approved");
+
+    h.api.clear();
+    h.send(callback_update(ADMIN, relay, "appeal_reject:-1001_10001"))
+        .await;
+    assert_eq!(
+        h.api.calls_to("answerCallbackQuery")[0]["text"],
+        "This appeal was decided already"
+    );
+    assert_eq!(status(&h).await, AppealStatus::Approved);
+}
+
+/// Mutes users at 2 strikes and has `TARGET` appeal the deletion that gave them their second.
+async fn appeal_a_mute(h: &Harness) -> Value {
+    h.set_vote_count(1).await;
+    h.db.set_chat_escalation(CHAT_ID, Escalation::Mute, 2)
+        .await
+        .unwrap();
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+    h.db.create_strike(CHAT_ID, TARGET, 500, now() - 60)
+        .await
+        .unwrap();
+
+    let relay = appeal(h, CHAT_ID).await;
+    assert_eq!(h.api.calls_to("restrictChatMember").len(), 1);
+    relay
+}
+
+#[tokio::test]
+async fn approval_takes_back_the_strike_of_the_poll() {
+    let h = Harness::new().await;
+    let relay = appeal_a_mute(&h).await;
+    h.db.create_strike(CHAT_ID, TARGET, 600, now())
+        .await
+        .unwrap();
+    h.api.clear();
+
+    h.send(callback_update(ADMIN, relay, "appeal_approve:-1001_10001"))
+        .await;
+
+    // The strike from the later poll stays, and with it the mute.
+    assert_eq!(h.db.count_strikes(CHAT_ID, TARGET, 0).await.unwrap(), 2);
+    h.db.remove_poll_strike(CHAT_ID, 600).await.unwrap();
+    assert_eq!(h.db.count_strikes(CHAT_ID, TARGET, 0).await.unwrap(), 1);
+    assert!(h.api.calls_to("restrictChatMember").is_empty());
+}
+
+#[tokio::test]
+async fn approval_lifts_the_mute_of_the_strike() {
+    let h = Harness::new().await;
+    let relay = appeal_a_mute(&h).await;
+    h.api.clear();
+
+    h.send(callback_update(ADMIN, relay, "appeal_approve:-1001_10001"))
+        .await;
+
+    assert_eq!(h.db.count_strikes(CHAT_ID, TARGET, 0).await.unwrap(), 1);
+    let lifted = h.api.calls_to("restrictChatMember");
+    assert_eq!(lifted.len(), 1);
+    assert_eq!(lifted[0]["user_id"], TARGET);
+    assert_eq!(lifted[0]["permissions"]["can_send_messages"], true);
+}
+
+#[tokio::test]
+async fn results_with_an_appeal_link_stay_until_the_appeal_expires() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    delete_by_vote(&h).await;
+
+    let scheduled = h.db.get_pending_messages_to_delete(i64::MAX).await.unwrap();
+    let result = scheduled.iter().find(|m| m.message_id == 10001).unwrap();
+    assert!(result.timestamp >= now() + APPEAL_TTL - 60);
+}
+
+#[tokio::test]
+async fn approval_survives_a_failed_restore() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_undo_window(CHAT_ID, 600).await.unwrap();
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+
+    let relay = appeal(&h, CHAT_ID).await;
+    h.api.clear();
+
+    h.api
+        .script("sendMessage", api_error("Bad Request: chat not found"));
+    h.send(callback_update(ADMIN, relay, "appeal_approve:-1001_10001"))
+        .await;
+
+    assert_eq!(status(&h).await, AppealStatus::Approved);
+    assert_eq!(h.db.count_strikes(CHAT_ID, TARGET, 0).await.unwrap(), 0);
+    assert!(h.db.get_archive(CHAT_ID, 10001).await.unwrap().is_some());
+    assert!(h.api.calls_to("answerCallbackQuery")[0]["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Approved, but I could not post the message again"));
+}
+
+#[tokio::test]
+async fn rejected_appeals_go_to_the_appeal_chat() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_appeal_chat(CHAT_ID, Some(LOG_CHAT))
+        .await
+        .unwrap();
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+
+    let relay = appeal(&h, LOG_CHAT).await;
+    h.api.clear();
+
+    h.send(callback_update(
+        31,
+        relay.clone(),
+        "appeal_reject:-1001_10001",
+    ))
+    .await;
+    assert_eq!(
+        h.api.calls_to("answerCallbackQuery")[0]["text"],
+        "Only admins of the chat can decide on appeals"
+    );
+    assert_eq!(status(&h).await, AppealStatus::Pending);
+
+    h.send(callback_update(ADMIN, relay, "appeal_reject:-1001_10001"))
+        .await;
+    assert_eq!(status(&h).await, AppealStatus::Rejected);
+    assert_eq!(h.db.count_strikes(CHAT_ID, TARGET, 0).await.unwrap(), 1);
+    assert_eq!(last_sent_to(&h, TARGET)["text"], "Your appeal was rejected");
+}
+
+#[tokio::test]
+async fn only_the_author_can_appeal() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    delete_by_vote(&h).await;
+    h.send(message_update(private(5, 31, START))).await;
+    h.send(message_update(private(6, 31, "Me too"))).await;
+
+    assert_eq!(
+        last_sent_to(&h, 31)["text"],
+        "There is nothing to appeal here. The appeal may have been sent already or expired"
+    );
+    assert_eq!(status(&h).await, AppealStatus::Open);
+}
+
+#[tokio::test]
+async fn long_statements_are_refused() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    delete_by_vote(&h).await;
+    h.send(message_update(private(5, TARGET, START))).await;
+    h.send(message_update(private(6, TARGET, &"a".repeat(501))))
+        .await;
+
+    assert_eq!(status(&h).await, AppealStatus::Awaiting);
+    assert!(last_sent_to(&h, TARGET)["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Tell me in one message"));
+}
+
+#[tokio::test]
+async fn appeal_chat_command() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+
+    h.send(message_update(message(90, ADMIN, "/appeal_chat -1009")))
+        .await;
+    assert_eq!(
+        last_sent(&h)["text"],
+        "Appeals are now sent to the chat -1009"
+    );
+    assert_eq!(
+        h.db.get_chat(CHAT_ID)
+            .await
+            .unwrap()
+            .unwrap()
+            .appeal_chat_id,
+        Some(LOG_CHAT)
+    );
+
+    h.send(message_update(message(91, ADMIN, "/appeal_chat off")))
+        .await;
+    assert_eq!(last_sent(&h)["text"], "Appeals are now sent to this chat");
+
+    h.send(message_update(message(92, ADMIN, "/appeal_chat here")))
+        .await;
+    assert!(last_sent(&h)["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Usage: /appeal_chat"));
+}
@@ -0,0 +1,392 @@
+//! A local stand-in for the Telegram Bot API plus helpers to feed synthetic updates through
+//! [`delete_itt::schema`].
+#![allow(dead_code)]
+
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    net::SocketAddr,
+    ops::ControlFlow,
+    sync::{Arc, Mutex},
+};
+
+use delete_itt::{
+    handlers::PatternCache,
+    schema,
+    types::{Configuration, DeleteIttBot, Locale, Localization},
+    Config, MemoryStorage, Store,
+};
+use hyper::{
+    body::to_bytes,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+use serde_json::{json, Value};
+use teloxide::{
+    dptree,
+    requests::RequesterExt,
+    types::{Me, Update},
+    Bot,
+};
+
+pub const CHAT_ID: i64 = -1001;
+pub const BOT_ID: i64 = 1000;
+pub const BOT_USERNAME: &str = "delete_itt_bot";
+
+/// A request the bot made, with the method name normalized to Bot API spelling
+/// (`sendMessage`, not `SendMessage`).
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub method: String,
+    pub body: Value,
+    pub response: Value,
+}
+
+#[derive(Default)]
+struct State {
+    calls: Vec<Call>,
+    scripted: HashMap<String, VecDeque<Value>>,
+    members: HashMap<i64, Value>,
+    last_message_id: i64,
+}
+
+#[derive(Clone)]
+pub struct FakeApi {
+    state: Arc<Mutex<State>>,
+    addr: SocketAddr,
+}
+
+fn normalize(method: &str) -> String {
+    let mut c = method.chars();
+
+    match c.next() {
+        Some(f) => f.to_lowercase().chain(c).collect(),
+        None => String::new(),
+    }
+}
+
+fn chat_id(body: &Value) -> i64 {
+    match &body["chat_id"] {
+        Value::Number(n) => n.as_i64().unwrap(),
+        Value::String(s) => s.parse().unwrap(),
+        _ => CHAT_ID,
+    }
+}
+
+pub fn user(id: i64) -> Value {
+    json!({ "id": id, "is_bot": false, "first_name": format!("User{}", id) })
+}
+
+pub fn bot_user() -> Value {
+    json!({
+        "id": BOT_ID,
+        "is_bot": true,
+        "first_name": "Delete Itt",
+        "username": BOT_USERNAME,
+    })
+}
+
+pub fn chat() -> Value {
+    json!({ "id": CHAT_ID, "type": "supergroup", "title": "Test chat" })
+}
+
+pub fn member(id: i64) -> Value {
+    json!({ "status": "member", "user": user(id) })
+}
+
+pub fn admin(user: Value) -> Value {
+    json!({
+        "status": "administrator",
+        "user": user,
+        "is_anonymous": false,
+        "can_be_edited": false,
+        "can_manage_chat": true,
+        "can_change_info": true,
+        "can_delete_messages": true,
+        "can_manage_video_chats": true,
+        "can_invite_users": true,
+        "can_restrict_members": true,
+        "can_pin_messages": true,
+        "can_promote_members": false,
+    })
+}
+
+/// `{"ok": false, ...}` as returned by Telegram when a request fails.
+pub fn api_error(description: &str) -> Value {
+    json!({ "ok": false, "error_code": 400, "description": description })
+}
+
+impl State {
+    fn message(&mut self, body: &Value) -> Value {
+        let message_id = match body["message_id"].as_i64() {
+            Some(id) => id,
+            None => {
+                self.last_message_id += 1;
+                self.last_message_id
+            }
+        };
+
+        let mut m = json!({
+            "message_id": message_id,
+            "date": 0,
+            "chat": { "id": chat_id(body), "type": "supergroup", "title": "Test chat" },
+            "from": bot_user(),
+            "text": body["text"].as_str().unwrap_or(""),
+        });
+
+        if let Some(markup) = body.get("reply_markup") {
+            m["reply_markup"] = markup.clone();
+        }
+
+        m
+    }
+
+    fn default_result(&mut self, method: &str, body: &Value) -> Value {
+        match method {
+            "getMe" => {
+                let mut me = bot_user();
+                me["can_join_groups"] = json!(true);
+                me["can_read_all_group_messages"] = json!(false);
+                me["supports_inline_queries"] = json!(false);
+                me
+            }
+            "sendMessage" | "editMessageText" | "editMessageReplyMarkup" | "copyMessage" => {
+                self.message(body)
+            }
+            "getChatMember" => {
+                let id = body["user_id"].as_i64().unwrap();
+
+                match self.members.get(&id) {
+                    Some(m) => m.clone(),
+                    None if id == BOT_ID => admin(bot_user()),
+                    None => member(id),
+                }
+            }
+            "getChatAdministrators" => json!([admin(bot_user())]),
+            "getChat" => {
+                let id = chat_id(body);
+
+                json!({ "id": id, "type": "channel", "title": format!("Chat {}", id) })
+            }
+            _ => json!(true),
+        }
+    }
+
+    fn respond(&mut self, method: String, body: Value) -> Value {
+        let scripted = self.scripted.get_mut(&method).and_then(|q| q.pop_front());
+
+        let response = match scripted {
+            Some(r) => r,
+            None => json!({ "ok": true, "result": self.default_result(&method, &body) }),
+        };
+
+        self.calls.push(Call {
+            method,
+            body,
+            response: response.clone(),
+        });
+
+        response
+    }
+}
+
+impl FakeApi {
+    pub async fn start() -> Self {
+        let state = Arc::new(Mutex::new(State {
+            last_message_id: 10_000,
+            ..State::default()
+        }));
+        let s = state.clone();
+
+        let make_svc = make_service_fn(move |_| {
+            let s = s.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let s = s.clone();
+
+                    async move {
+                        let method = normalize(req.uri().path().rsplit('/').next().unwrap());
+                        let bytes = to_bytes(req.into_body()).await.unwrap();
+                        let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+                        let response = s.lock().unwrap().respond(method, body);
+
+                        Ok::<_, Infallible>(Response::new(Body::from(response.to_string())))
+                    }
+                }))
+            }
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+
+        tokio::spawn(server);
+
+        FakeApi { state, addr }
+    }
+
+    pub fn url(&self) -> url::Url {
+        url::Url::parse(&format!("http://{}/", self.addr)).unwrap()
+    }
+
+    /// Makes the next call to `method` answer with `response` instead of the default.
+    /// `response` is the whole envelope, e.g. `{"ok": true, "result": ...}` or [`api_error`].
+    pub fn script(&self, method: &str, response: Value) {
+        self.state
+            .lock()
+            .unwrap()
+            .scripted
+            .entry(method.into())
+            .or_default()
+            .push_back(response);
+    }
+
+    /// Makes every `getChatMember` call for `user_id` answer with `member`.
+    pub fn set_member(&self, user_id: i64, member: Value) {
+        self.state.lock().unwrap().members.insert(user_id, member);
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    pub fn calls_to(&self, method: &str) -> Vec<Value> {
+        self.calls()
+            .into_iter()
+            .filter(|c| c.method == method)
+            .map(|c| c.body)
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.state.lock().unwrap().calls.clear();
+    }
+}
+
+pub struct Harness {
+    pub api: FakeApi,
+    pub bot: DeleteIttBot,
+    pub db: Store,
+    pub config: Configuration,
+    pub loc: Localization,
+    pub locales: Vec<Locale>,
+    pub me: Me,
+    pub patterns: PatternCache,
+    last_update_id: Mutex<i64>,
+}
+
+impl Harness {
+    pub async fn new() -> Self {
+        Harness::with_config(Config {
+            bot_token: "TOKEN".into(),
+            db_url: "memory".into(),
+            ..Config::default()
+        })
+        .await
+    }
+
+    pub async fn with_config(config: Config) -> Self {
+        let api = FakeApi::start().await;
+        let bot = Bot::new(&config.bot_token)
+            .set_api_url(api.url())
+            .cache_me();
+        let (loc, locales) = delete_itt::load_localization("locales/");
+
+        let mut me = bot_user();
+        me["can_join_groups"] = json!(true);
+        me["can_read_all_group_messages"] = json!(false);
+        me["supports_inline_queries"] = json!(false);
+
+        Harness {
+            api,
+            bot,
+            db: Arc::new(MemoryStorage::new()),
+            config: Arc::new(config),
+            loc,
+            locales,
+            me: serde_json::from_value(me).unwrap(),
+            patterns: PatternCache::default(),
+            last_update_id: Mutex::new(0),
+        }
+    }
+
+    /// Runs `update` (the JSON of an update without `update_id`) through the handler tree and
+    /// panics if a handler returned an error.
+    pub async fn send(&self, mut update: Value) {
+        let update_id = {
+            let mut id = self.last_update_id.lock().unwrap();
+            *id += 1;
+            *id
+        };
+        update["update_id"] = json!(update_id);
+
+        // `Update` only deserializes correctly from text, not from a `Value`.
+        let update: Update = serde_json::from_str(&update.to_string()).unwrap();
+        let deps = dptree::deps![
+            self.bot.clone(),
+            self.me.clone(),
+            update,
+            self.db.clone(),
+            self.config.clone(),
+            self.loc.clone(),
+            self.locales.clone(),
+            self.patterns.clone()
+        ];
+
+        if let ControlFlow::Break(Err(e)) = schema().dispatch(deps).await {
+            panic!("Handler failed: {}", e);
+        }
+    }
+
+    /// The chat settings every test starts from, with `count` required votes.
+    pub async fn set_vote_count(&self, count: i64) {
+        self.db
+            .create_chat(CHAT_ID, count, "en", self.config.poll_delete_delay.default)
+            .await
+            .unwrap();
+    }
+}
+
+pub fn message(id: i64, from: i64, text: &str) -> Value {
+    json!({
+        "message_id": id,
+        "date": 0,
+        "chat": chat(),
+        "from": user(from),
+        "text": text,
+    })
+}
+
+pub fn reply(id: i64, from: i64, text: &str, to: Value) -> Value {
+    let mut m = message(id, from, text);
+    m["reply_to_message"] = to;
+    m
+}
+
+pub fn message_update(message: Value) -> Value {
+    json!({ "message": message })
+}
+
+pub fn callback_update(from: i64, message: Value, data: &str) -> Value {
+    json!({
+        "callback_query": {
+            "id": format!("cq{}", from),
+            "from": user(from),
+            "message": message,
+            "chat_instance": "test",
+            "data": data,
+        }
+    })
+}
+
+/// The last message the bot sent, as Telegram would attach it to a callback query.
+pub fn last_sent(harness: &Harness) -> Value {
+    harness
+        .api
+        .calls()
+        .into_iter()
+        .rev()
+        .find(|c| c.method == "sendMessage")
+        .map(|c| c.response["result"].clone())
+        .unwrap()
+}
@@ -0,0 +1,21 @@
+use delete_itt::Config;
+
+#[test]
+fn partial_limits_keep_built_in_values() {
+    let config: Config = toml::from_str("[vote_count]\nmax = 20\n").unwrap();
+
+    assert_eq!(config.vote_count.min, 1);
+    assert_eq!(config.vote_count.max, 20);
+    assert_eq!(config.vote_count.default, 5);
+    assert_eq!(config.poll_delete_delay.default, 5);
+}
+
+#[test]
+fn limits_can_be_left_out() {
+    let config: Config = toml::from_str("[poll_delete_delay]\ndefault = 30\n").unwrap();
+
+    assert_eq!(config.poll_delete_delay.min, 5);
+    assert_eq!(config.poll_delete_delay.max, 60);
+    assert_eq!(config.poll_delete_delay.default, 30);
+    assert_eq!(config.vote_count.max, 10);
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use common::*;
+use delete_itt::{Database, Storage};
+use sqlx::{any::AnyPoolOptions, Executor};
+
+async fn trigger(h: &Harness, id: i64) {
+    h.send(message_update(reply(
+        id,
+        10,
+        "/delete",
+        message(5, 20, "spam"),
+    )))
+    .await;
+}
+
+#[tokio::test]
+async fn second_trigger_points_at_existing_poll() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+
+    trigger(&h, 6).await;
+    let poll = last_sent(&h);
+    h.api.clear();
+
+    trigger(&h, 7).await;
+
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["reply_to_message_id"], poll["message_id"]);
+    assert_eq!(
+        sent[0]["text"],
+        "There is already a poll about that message, vote here"
+    );
+    assert_eq!(h.db.count_polls(CHAT_ID).await.unwrap(), 1);
+
+    let deleted = h.api.calls_to("deleteMessage");
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0]["message_id"], 7);
+
+    let pointer = last_sent(&h)["message_id"].as_i64().unwrap();
+    let scheduled = h.db.get_pending_messages_to_delete(i64::MAX).await.unwrap();
+    assert!(scheduled.iter().any(|m| m.message_id as i64 == pointer));
+}
+
+#[tokio::test]
+async fn storage_rejects_second_poll_on_message() {
+    let h = Harness::new().await;
+
+    h.db.create_poll(CHAT_ID, 100, 5, 20, 3, None)
+        .await
+        .unwrap();
+
+    assert!(h
+        .db
+        .create_poll(CHAT_ID, 101, 5, 20, 3, None)
+        .await
+        .is_err());
+    assert_eq!(
+        h.db.get_poll_by_message(CHAT_ID, 5)
+            .await
+            .unwrap()
+            .unwrap()
+            .poll_id,
+        100
+    );
+}
+
+#[tokio::test]
+async fn new_poll_allowed_once_previous_resolved() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    trigger(&h, 6).await;
+    let poll = last_sent(&h);
+    h.send(callback_update(31, poll, "vote_no")).await;
+    h.api.clear();
+
+    trigger(&h, 7).await;
+
+    assert_eq!(h.db.count_polls(CHAT_ID).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn upgrade_drops_duplicate_polls() {
+    let path = std::env::temp_dir().join(format!("delete_itt_upgrade_{}.db", std::process::id()));
+    let url = format!("sqlite://{}?mode=rwc", path.display());
+
+    let pool = AnyPoolOptions::new().connect(&url).await.unwrap();
+    pool.execute(
+        "CREATE TABLE polls (id INTEGER PRIMARY KEY, chat_id INTEGER NOT NULL, \
+        poll_id INTEGER NOT NULL, message_id INTEGER NOT NULL, \
+        message_user_id INTEGER NOT NULL, minimum_vote_count INTEGER NOT NULL, \
+        vote_count_yes INTEGER DEFAULT 0, vote_count_no INTEGER DEFAULT 0);
+        CREATE TABLE voters (id INTEGER PRIMARY KEY, poll_id INTEGER NOT NULL, \
+        user_id INTEGER NOT NULL);
+        INSERT INTO polls (id, chat_id, poll_id, message_id, message_user_id, minimum_vote_count) \
+        VALUES (1, -1001, 100, 5, 20, 3), (2, -1001, 101, 5, 20, 3);
+        INSERT INTO voters (poll_id, user_id) VALUES (1, 30), (2, 31);",
+    )
+    .await
+    .unwrap();
+    pool.close().await;
+
+    let db = Database::new(url, 1).await;
+    let kept = db.get_poll_by_message(CHAT_ID, 5).await.unwrap().unwrap();
+    assert_eq!(kept.poll_id, 100);
+    assert!(db.get_voter(1, 30).await.unwrap().is_some());
+    assert!(db.get_voter(2, 31).await.unwrap().is_none());
+    assert!(db.create_poll(CHAT_ID, 102, 5, 20, 3, None).await.is_err());
+
+    std::fs::remove_file(path).ok();
+}
@@ -0,0 +1,426 @@
+mod common;
+
+use common::*;
+use delete_itt::types::{Escalation, FederationAction, PollOrigin};
+use delete_itt::{Database, Storage};
+use serde_json::{json, Value};
+
+const TARGET: i64 = 20;
+const ADMIN: i64 = 40;
+const OTHER_CHAT: i64 = -1002;
+const THIRD_CHAT: i64 = -1003;
+
+fn in_chat(mut message: Value, chat_id: i64) -> Value {
+    message["chat"]["id"] = json!(chat_id);
+    message
+}
+
+async fn admin_command(h: &Harness, chat_id: i64, text: &str) -> String {
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+    h.send(message_update(in_chat(message(90, ADMIN, text), chat_id)))
+        .await;
+
+    last_sent(h)["text"].as_str().unwrap().into()
+}
+
+/// Creates a federation owned by `CHAT_ID` and has every chat in `members` join it with the
+/// given action.
+async fn federate(h: &Harness, members: &[(i64, FederationAction)]) -> i64 {
+    h.db.create_federation("Friends", CHAT_ID, "token")
+        .await
+        .unwrap();
+    let id =
+        h.db.get_federation_by_token("token")
+            .await
+            .unwrap()
+            .unwrap()
+            .id;
+
+    h.db.set_chat_federation(CHAT_ID, Some(id)).await.unwrap();
+    for (chat_id, action) in members {
+        h.db.create_chat(*chat_id, 1, "en", 5).await.unwrap();
+        h.db.set_chat_federation(*chat_id, Some(id)).await.unwrap();
+        h.db.set_chat_federation_action(*chat_id, *action)
+            .await
+            .unwrap();
+    }
+
+    id
+}
+
+/// The messages the bot sent to `chat_id`.
+fn sent_to(h: &Harness, chat_id: i64) -> Vec<String> {
+    h.api
+        .calls_to("sendMessage")
+        .into_iter()
+        .filter(|m| m["chat_id"] == chat_id)
+        .map(|m| m["text"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn fed_command_creates_and_joins() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+
+    assert!(admin_command(&h, CHAT_ID, "/fed")
+        .await
+        .starts_with("This chat is not in a federation"));
+    assert_eq!(
+        admin_command(&h, CHAT_ID, "/fed new Friends").await,
+        "Created the federation Friends\\. I sent its join token to you in a private chat"
+    );
+
+    let federation_id = h.db.get_chat(CHAT_ID).await.unwrap().unwrap().federation_id;
+    let federation =
+        h.db.get_federation(federation_id.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+    assert_eq!(federation.owner_chat_id, CHAT_ID);
+    assert!(sent_to(&h, ADMIN)[0].contains(&format!("/fed join {}", federation.token)));
+    assert!(!sent_to(&h, CHAT_ID)
+        .iter()
+        .any(|text| text.contains(&federation.token)));
+
+    assert_eq!(
+        admin_command(&h, OTHER_CHAT, "/fed join wrong").await,
+        "There is no federation with that token"
+    );
+    h.api.clear();
+    assert_eq!(
+        admin_command(&h, OTHER_CHAT, &format!("/fed join {}", federation.token)).await,
+        "This chat joined the federation Friends"
+    );
+    assert!(sent_to(&h, CHAT_ID)[0].ends_with("Remove it with /fed kick \\-1002"));
+    assert_eq!(
+        admin_command(&h, OTHER_CHAT, "/fed action poll").await,
+        "Users banned in the federation now get a poll about each message here"
+    );
+    assert_eq!(
+        admin_command(&h, OTHER_CHAT, "/fed").await,
+        "This chat is in the federation Friends\\. Users banned in it get a poll about each message here"
+    );
+    assert!(admin_command(&h, CHAT_ID, "/fed")
+        .await
+        .ends_with("/fed token sends you the join token privately"));
+
+    assert_eq!(
+        admin_command(&h, OTHER_CHAT, "/fed leave").await,
+        "This chat left the federation Friends"
+    );
+    h.db.remove_empty_federations().await.unwrap();
+    assert!(h.db.get_federation(federation.id).await.unwrap().is_some());
+
+    admin_command(&h, CHAT_ID, "/fed leave").await;
+    h.db.remove_empty_federations().await.unwrap();
+    assert!(h.db.get_federation(federation.id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn tokens_are_only_sent_privately() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    federate(&h, &[(OTHER_CHAT, FederationAction::Warn)]).await;
+
+    h.api.script(
+        "sendMessage",
+        api_error("Forbidden: bot can't initiate conversation"),
+    );
+    assert_eq!(
+        admin_command(&h, CHAT_ID, "/fed token").await,
+        "I could not send you its join token privately\\. Start a private chat with me, then \
+        send /fed token here"
+    );
+
+    h.api.clear();
+    assert_eq!(
+        admin_command(&h, CHAT_ID, "/fed token").await,
+        "I sent its join token to you in a private chat"
+    );
+    assert!(sent_to(&h, ADMIN)[0].contains("/fed join token"));
+
+    assert_eq!(
+        admin_command(&h, OTHER_CHAT, "/fed token").await,
+        "Only the chat that created the federation Friends can do that"
+    );
+}
+
+#[tokio::test]
+async fn owners_rotate_tokens_and_remove_chats() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    let federation_id = federate(&h, &[(OTHER_CHAT, FederationAction::Warn)]).await;
+
+    assert_eq!(
+        admin_command(&h, OTHER_CHAT, "/fed rotate").await,
+        "Only the chat that created the federation Friends can do that"
+    );
+    assert!(admin_command(&h, CHAT_ID, "/fed rotate")
+        .await
+        .starts_with("The old join token of the federation Friends no longer works"));
+    assert!(h
+        .db
+        .get_federation_by_token("token")
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        admin_command(&h, THIRD_CHAT, "/fed join token").await,
+        "There is no federation with that token"
+    );
+
+    assert_eq!(
+        admin_command(&h, OTHER_CHAT, &format!("/fed kick {}", CHAT_ID)).await,
+        "Only the chat that created the federation Friends can do that"
+    );
+    assert_eq!(
+        admin_command(&h, CHAT_ID, &format!("/fed kick {}", THIRD_CHAT)).await,
+        "There is no other chat with that id in the federation Friends"
+    );
+    h.api.clear();
+    assert_eq!(
+        admin_command(&h, CHAT_ID, &format!("/fed kick {}", OTHER_CHAT)).await,
+        "The chat \\-1002 was removed from the federation Friends"
+    );
+    assert_eq!(
+        sent_to(&h, OTHER_CHAT),
+        vec!["This chat was removed from the federation Friends"]
+    );
+    assert_eq!(
+        h.db.get_chat(OTHER_CHAT)
+            .await
+            .unwrap()
+            .unwrap()
+            .federation_id,
+        None
+    );
+    assert_eq!(
+        h.db.get_chat(CHAT_ID).await.unwrap().unwrap().federation_id,
+        Some(federation_id)
+    );
+}
+
+#[tokio::test]
+async fn removing_the_bot_from_the_owner_invalidates_the_token() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    let federation_id = federate(&h, &[(OTHER_CHAT, FederationAction::Warn)]).await;
+
+    h.send(json!({
+        "my_chat_member": {
+            "chat": chat(),
+            "from": user(ADMIN),
+            "date": 0,
+            "old_chat_member": admin(bot_user()),
+            "new_chat_member": { "status": "left", "user": bot_user() },
+        }
+    }))
+    .await;
+
+    assert!(h.db.get_chat(CHAT_ID).await.unwrap().is_none());
+    assert!(h
+        .db
+        .get_federation_by_token("token")
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        h.db.get_chat(OTHER_CHAT)
+            .await
+            .unwrap()
+            .unwrap()
+            .federation_id,
+        Some(federation_id)
+    );
+}
+
+#[tokio::test]
+async fn database_rotates_tokens_of_removed_owners() {
+    let db = Database::new("sqlite::memory:", 1).await;
+    db.create_chat(CHAT_ID, 3, "en", 5).await.unwrap();
+    db.create_federation("Friends", CHAT_ID, "token")
+        .await
+        .unwrap();
+    let federation = db.get_federation_by_token("token").await.unwrap().unwrap();
+
+    db.remove_chat(CHAT_ID).await.unwrap();
+
+    assert!(db.get_federation_by_token("token").await.unwrap().is_none());
+    let rotated = db.get_federation(federation.id).await.unwrap().unwrap();
+    assert_eq!(rotated.token.len(), 24);
+}
+
+#[tokio::test]
+async fn only_owners_flag_users() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    let federation_id = federate(&h, &[(OTHER_CHAT, FederationAction::Ban)]).await;
+
+    assert_eq!(
+        admin_command(&h, OTHER_CHAT, &format!("/fed ban {}", TARGET)).await,
+        "Only the chat that created the federation Friends can do that"
+    );
+    assert!(h
+        .db
+        .get_federation_ban(federation_id, TARGET)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn fed_command_is_for_admins() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+
+    h.send(message_update(message(90, 31, "/fed new Friends")))
+        .await;
+
+    assert!(h.api.calls_to("sendMessage").is_empty());
+    assert!(h
+        .db
+        .get_federation_by_token("token")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn vote_bans_are_propagated() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_escalation(CHAT_ID, Escalation::Ban, 1)
+        .await
+        .unwrap();
+    let federation_id = federate(
+        &h,
+        &[
+            (OTHER_CHAT, FederationAction::Ban),
+            (THIRD_CHAT, FederationAction::Warn),
+        ],
+    )
+    .await;
+
+    h.send(message_update(reply(
+        100,
+        10,
+        "/delete",
+        message(1, TARGET, "spam"),
+    )))
+    .await;
+    let poll = last_sent(&h);
+    h.send(callback_update(31, poll, "vote_yes")).await;
+
+    let banned = h
+        .api
+        .calls_to("banChatMember")
+        .into_iter()
+        .map(|b| {
+            (
+                b["chat_id"].as_i64().unwrap(),
+                b["user_id"].as_i64().unwrap(),
+            )
+        })
+        .collect::<Vec<(i64, i64)>>();
+    assert_eq!(banned, vec![(CHAT_ID, TARGET), (OTHER_CHAT, TARGET)]);
+
+    let warned = h
+        .api
+        .calls_to("sendMessage")
+        .into_iter()
+        .filter(|m| m["chat_id"] == THIRD_CHAT)
+        .collect::<Vec<Value>>();
+    assert_eq!(warned.len(), 1);
+    assert!(warned[0]["text"]
+        .as_str()
+        .unwrap()
+        .ends_with("was banned in the federation Friends"));
+
+    let ban =
+        h.db.get_federation_ban(federation_id, TARGET)
+            .await
+            .unwrap()
+            .unwrap();
+    assert_eq!(ban.chat_id, CHAT_ID);
+}
+
+#[tokio::test]
+async fn flagged_users_are_handled_when_they_post() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    federate(
+        &h,
+        &[
+            (OTHER_CHAT, FederationAction::Poll),
+            (THIRD_CHAT, FederationAction::Ban),
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        admin_command(&h, CHAT_ID, &format!("/fed ban {}", TARGET)).await,
+        "20 is now banned in the federation Friends"
+    );
+    h.api.clear();
+
+    h.send(message_update(in_chat(
+        message(5, TARGET, "hi"),
+        OTHER_CHAT,
+    )))
+    .await;
+    h.send(message_update(in_chat(
+        message(6, TARGET, "hi"),
+        OTHER_CHAT,
+    )))
+    .await;
+
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["reply_to_message_id"], 5);
+    let poll =
+        h.db.get_poll_by_message(OTHER_CHAT, 5)
+            .await
+            .unwrap()
+            .unwrap();
+    assert_eq!(poll.origin(), Some(PollOrigin::Federation));
+
+    h.send(message_update(in_chat(
+        message(7, TARGET, "hi"),
+        THIRD_CHAT,
+    )))
+    .await;
+    assert_eq!(h.api.calls_to("deleteMessage")[0]["message_id"], 7);
+
+    // The chat that flagged them keeps the default, a warning only.
+    h.api.clear();
+    h.send(message_update(message(8, TARGET, "hi"))).await;
+    assert!(h.api.calls().is_empty());
+}
+
+#[tokio::test]
+async fn unbanned_users_are_left_alone() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    let federation_id = federate(&h, &[(OTHER_CHAT, FederationAction::Ban)]).await;
+    h.db.create_federation_ban(federation_id, TARGET, CHAT_ID, 1)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        admin_command(&h, CHAT_ID, &format!("/fed unban {}", TARGET)).await,
+        "20 is no longer banned in the federation Friends"
+    );
+    assert_eq!(
+        admin_command(&h, CHAT_ID, &format!("/fed unban {}", TARGET)).await,
+        "20 is not banned in the federation Friends"
+    );
+    h.api.clear();
+
+    h.send(message_update(in_chat(
+        message(5, TARGET, "hi"),
+        OTHER_CHAT,
+    )))
+    .await;
+    assert!(h.api.calls_to("banChatMember").is_empty());
+}
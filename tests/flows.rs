@@ -0,0 +1,157 @@
+mod common;
+
+use common::*;
+use delete_itt::scheduler;
+
+const TARGET: i64 = 20;
+
+async fn mention(h: &Harness) {
+    let target = message(5, TARGET, "spam");
+
+    h.send(message_update(reply(
+        6,
+        10,
+        &format!("@{}", BOT_USERNAME),
+        target,
+    )))
+    .await;
+}
+
+async fn start_poll(h: &Harness) -> serde_json::Value {
+    mention(h).await;
+
+    last_sent(h)
+}
+
+#[tokio::test]
+async fn mention_creates_poll() {
+    let h = Harness::new().await;
+    h.set_vote_count(2).await;
+
+    let poll = start_poll(&h).await;
+
+    let deleted = h.api.calls_to("deleteMessage");
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0]["message_id"], 6);
+
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["reply_to_message_id"], 5);
+    assert!(sent[0]["text"].as_str().unwrap().contains("User20"));
+
+    let markup = h.api.calls_to("editMessageReplyMarkup");
+    assert_eq!(markup.len(), 1);
+    assert_eq!(markup[0]["message_id"], poll["message_id"]);
+}
+
+#[tokio::test]
+async fn mention_of_privileged_user_is_ignored() {
+    let h = Harness::new().await;
+    h.api.set_member(TARGET, admin(user(TARGET)));
+
+    mention(&h).await;
+
+    assert!(h.api.calls_to("sendMessage").is_empty());
+}
+
+#[tokio::test]
+async fn yes_votes_delete_message_and_schedule_cleanup() {
+    let h = Harness::new().await;
+    h.set_vote_count(2).await;
+
+    let poll = start_poll(&h).await;
+    h.api.clear();
+
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+
+    assert!(h.api.calls_to("deleteMessage").is_empty());
+    assert_eq!(h.api.calls_to("answerCallbackQuery").len(), 1);
+
+    h.send(callback_update(32, poll.clone(), "vote_yes")).await;
+
+    let deleted = h.api.calls_to("deleteMessage");
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0]["message_id"], 5);
+
+    let edited = h.api.calls_to("editMessageText");
+    assert_eq!(edited.len(), 1);
+    assert!(edited[0]["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Deleted a message"));
+
+    h.api.clear();
+    scheduler::run_pending(&h.bot, &h.db, i64::MAX).await;
+
+    let cleaned = h.api.calls_to("deleteMessage");
+    assert_eq!(cleaned.len(), 1);
+    assert_eq!(cleaned[0]["message_id"], poll["message_id"]);
+    assert!(h
+        .db
+        .get_pending_messages_to_delete(i64::MAX)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn failed_result_edit_still_closes_the_poll() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    let poll = start_poll(&h).await;
+    h.api.clear();
+    h.api.script(
+        "editMessageText",
+        api_error("Bad Request: message can't be edited"),
+    );
+
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+
+    let poll_id = poll["message_id"].as_i64().unwrap();
+    assert!(h
+        .db
+        .get_poll(CHAT_ID, poll_id as i32)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(h.db.count_strikes(CHAT_ID, TARGET, 0).await.unwrap(), 1);
+    let markup = h.api.calls_to("editMessageReplyMarkup");
+    assert_eq!(markup.len(), 1);
+    assert!(markup[0].get("reply_markup").is_none());
+    let scheduled = h.db.get_pending_messages_to_delete(i64::MAX).await.unwrap();
+    assert!(scheduled.iter().any(|m| m.message_id as i64 == poll_id));
+}
+
+#[tokio::test]
+async fn no_votes_remove_poll_and_keep_message() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    let poll = start_poll(&h).await;
+    h.api.clear();
+
+    h.send(callback_update(31, poll.clone(), "vote_no")).await;
+
+    let deleted = h.api.calls_to("deleteMessage");
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0]["message_id"], poll["message_id"]);
+    assert!(h
+        .db
+        .get_poll(CHAT_ID, poll["message_id"].as_i64().unwrap() as i32)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn admin_sets_vote_count() {
+    let h = Harness::new().await;
+    h.api.set_member(10, admin(user(10)));
+
+    h.send(message_update(message(7, 10, "/vote_count 3")))
+        .await;
+
+    assert_eq!(h.db.get_chat_votes(CHAT_ID).await.unwrap(), Some(3));
+    assert_eq!(h.api.calls_to("deleteMessage")[0]["message_id"], 7);
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::*;
+use delete_itt::format::{escape, translate, Markdown};
+use serde_json::{json, Value};
+
+const TARGET: i64 = 20;
+const NASTY_NAME: &str = r"_*[evil](tg://x)~`>#+-=|{}.!\";
+
+/// A message from `TARGET`, whose display name is made of MarkdownV2 syntax.
+fn nasty_message(id: i64) -> Value {
+    let mut m = message(id, TARGET, "spam");
+    m["from"] =
+        json!({ "id": TARGET, "is_bot": false, "first_name": NASTY_NAME, "last_name": "*" });
+    m
+}
+
+fn escaped_name() -> String {
+    r"\_\*\[evil\]\(tg://x\)\~\`\>\#\+\-\=\|\{\}\.\!\\ \*".into()
+}
+
+#[test]
+fn escape_covers_backslash() {
+    assert_eq!(escape(r"a\b_"), r"a\\b\_");
+}
+
+#[test]
+fn translation_is_escaped_but_values_are_not() {
+    let (loc, _) = delete_itt::load_localization("locales/");
+
+    let text = translate(
+        &loc,
+        "vote_count.updated",
+        "en",
+        &[("count", Markdown::text("1.5"))],
+    )
+    .unwrap();
+
+    assert_eq!(
+        text.as_str(),
+        r"Successfully updated minimum vote count to 1\.5"
+    );
+}
+
+#[tokio::test]
+async fn poll_title_escapes_target_name() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+
+    h.send(message_update(reply(6, 10, "/delete", nasty_message(5))))
+        .await;
+
+    let sent = h.api.calls_to("sendMessage").pop().unwrap();
+    assert_eq!(
+        sent["text"],
+        format!(
+            "*Should I delete this message from [{}](tg://user?id={})? Minimum number of votes \
+            needed is 3*",
+            escaped_name(),
+            TARGET
+        )
+    );
+    assert_eq!(h.db.count_polls(CHAT_ID).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn result_escapes_target_name() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.api.set_member(
+        TARGET,
+        json!({
+            "status": "member",
+            "user": { "id": TARGET, "is_bot": false, "first_name": NASTY_NAME, "last_name": "*" },
+        }),
+    );
+
+    h.send(message_update(reply(6, 10, "/delete", nasty_message(5))))
+        .await;
+    let poll = last_sent(&h);
+    h.send(callback_update(31, poll, "vote_yes")).await;
+
+    let edited = h.api.calls_to("editMessageText").pop().unwrap();
+    assert_eq!(
+        edited["text"],
+        format!(
+            "Deleted a message from [{}](tg://user?id={}) \
+            [Appeal](https://t.me/{}?start=appeal_{}_10001)",
+            escaped_name(),
+            TARGET,
+            BOT_USERNAME,
+            CHAT_ID
+        )
+    );
+}
+
+#[tokio::test]
+async fn free_text_reason_with_markup_is_escaped() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+
+    h.send(message_update(reply(
+        6,
+        10,
+        "/delete *bold* [link](x) \\",
+        message(5, TARGET, "spam"),
+    )))
+    .await;
+
+    let text = last_sent(&h)["text"].as_str().unwrap().to_string();
+    assert!(text.contains(r"for \*bold\* \[link\]\(x\) \\?"));
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::*;
+
+const TARGET: i64 = 20;
+const INITIATOR: i64 = 10;
+
+async fn enable(h: &Harness, count: i64) {
+    h.set_vote_count(count).await;
+    h.db.set_chat_initiator_votes(CHAT_ID, true).await.unwrap();
+}
+
+async fn mention(h: &Harness) {
+    h.send(message_update(reply(
+        6,
+        INITIATOR,
+        "/delete",
+        message(5, TARGET, "spam"),
+    )))
+    .await;
+}
+
+#[tokio::test]
+async fn initiator_is_counted_as_yes() {
+    let h = Harness::new().await;
+    enable(&h, 3).await;
+
+    mention(&h).await;
+
+    let poll = last_sent(&h);
+    let info =
+        h.db.get_poll(CHAT_ID, poll["message_id"].as_i64().unwrap() as i32)
+            .await
+            .unwrap()
+            .unwrap();
+
+    assert_eq!(info.vote_count_yes, 1);
+    assert!(h.db.get_voter(info.id, INITIATOR).await.unwrap().is_some());
+
+    let markup = h.api.calls_to("editMessageReplyMarkup").pop().unwrap();
+    assert_eq!(
+        markup["reply_markup"]["inline_keyboard"][0][0]["text"],
+        "Yes (1)"
+    );
+}
+
+#[tokio::test]
+async fn threshold_of_one_resolves_immediately() {
+    let h = Harness::new().await;
+    enable(&h, 1).await;
+
+    mention(&h).await;
+
+    let deleted = h
+        .api
+        .calls_to("deleteMessage")
+        .into_iter()
+        .map(|d| d["message_id"].as_i64().unwrap())
+        .collect::<Vec<i64>>();
+
+    assert_eq!(deleted, vec![6, 5]);
+    assert_eq!(h.api.calls_to("editMessageText").len(), 1);
+}
+
+#[tokio::test]
+async fn disabled_by_default() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    mention(&h).await;
+
+    assert_eq!(h.api.calls_to("deleteMessage").len(), 1);
+    assert!(h.api.calls_to("editMessageText").is_empty());
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use common::*;
+
+const INITIATOR: i64 = 10;
+
+async fn trigger(h: &Harness, id: i64, from: i64) {
+    h.send(message_update(reply(
+        id,
+        from,
+        "/delete",
+        message(id - 1, 20, "spam"),
+    )))
+    .await;
+}
+
+fn polls_started(h: &Harness) -> usize {
+    h.api
+        .calls_to("sendMessage")
+        .into_iter()
+        .filter(|m| m["reply_markup"].is_null() && m["parse_mode"] == "MarkdownV2")
+        .count()
+}
+
+#[tokio::test]
+async fn user_rate_is_limited() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.db.set_chat_poll_rate(CHAT_ID, 2, 3600).await.unwrap();
+
+    trigger(&h, 6, INITIATOR).await;
+    trigger(&h, 8, INITIATOR).await;
+    assert_eq!(polls_started(&h), 2);
+    h.api.clear();
+
+    trigger(&h, 10, INITIATOR).await;
+
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(
+        sent[0]["text"],
+        "You can only start 2 polls every 60 minutes"
+    );
+    assert_eq!(sent[0]["reply_to_message_id"], 10);
+    assert!(h.api.calls_to("deleteMessage").is_empty());
+
+    let scheduled = h.db.get_pending_messages_to_delete(i64::MAX).await.unwrap();
+    let mut ids = scheduled
+        .iter()
+        .map(|m| m.message_id as i64)
+        .collect::<Vec<i64>>();
+    ids.sort();
+    let refusal = last_sent(&h)["message_id"].as_i64().unwrap();
+    assert_eq!(ids, vec![10, refusal]);
+
+    trigger(&h, 12, 11).await;
+    assert_eq!(polls_started(&h), 1);
+}
+
+#[tokio::test]
+async fn open_polls_are_limited() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.db.set_chat_max_open_polls(CHAT_ID, 1).await.unwrap();
+
+    trigger(&h, 6, INITIATOR).await;
+    h.api.clear();
+
+    trigger(&h, 8, 11).await;
+
+    assert_eq!(
+        last_sent(&h)["text"],
+        "There are already 1 open polls in this chat. Please vote on those first"
+    );
+    assert_eq!(h.db.count_polls(CHAT_ID).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn admins_are_not_limited() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.db.set_chat_max_open_polls(CHAT_ID, 1).await.unwrap();
+    h.api.set_member(INITIATOR, admin(user(INITIATOR)));
+
+    trigger(&h, 6, INITIATOR).await;
+    trigger(&h, 8, INITIATOR).await;
+
+    assert_eq!(h.db.count_polls(CHAT_ID).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn admin_sets_poll_rate() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.api.set_member(INITIATOR, admin(user(INITIATOR)));
+
+    h.send(message_update(message(7, INITIATOR, "/poll_rate 3 30")))
+        .await;
+
+    let chat = h.db.get_chat(CHAT_ID).await.unwrap().unwrap();
+    assert_eq!((chat.poll_rate_count, chat.poll_rate_window), (3, 1800));
+}
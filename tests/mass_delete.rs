@@ -0,0 +1,126 @@
+mod common;
+
+use common::*;
+use delete_itt::reason::split_scope;
+use delete_itt::storage::MAX_TRACKED_MESSAGES;
+use delete_itt::types::Scope;
+use serde_json::{json, Value};
+
+const TARGET: i64 = 20;
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+fn dated(id: i64, date: i64) -> Value {
+    let mut m = message(id, TARGET, "buy now");
+    m["date"] = json!(date);
+    m
+}
+
+async fn resolve(h: &Harness, text: &str, target: Value) -> Vec<i64> {
+    h.set_vote_count(1).await;
+    h.send(message_update(reply(100, 10, text, target))).await;
+
+    let poll = last_sent(h);
+    h.api.clear();
+    h.send(callback_update(31, poll, "vote_yes")).await;
+
+    h.api
+        .calls_to("deleteMessage")
+        .into_iter()
+        .map(|d| d["message_id"].as_i64().unwrap())
+        .collect()
+}
+
+#[test]
+fn scope_is_split_off_reason() {
+    assert_eq!(
+        split_scope("+10 spam"),
+        (Some(Scope::Messages(10)), Some("spam".into()))
+    );
+    assert_eq!(split_scope("15m"), (Some(Scope::Minutes(15)), None));
+    assert_eq!(
+        split_scope("+1000"),
+        (Some(Scope::Messages(MAX_TRACKED_MESSAGES)), None)
+    );
+    assert_eq!(split_scope("15 minutes"), (None, Some("15 minutes".into())));
+    assert_eq!(split_scope("+0 spam"), (None, Some("spam".into())));
+}
+
+#[tokio::test]
+async fn last_messages_are_deleted_with_target() {
+    let h = Harness::new().await;
+    for id in 1..=4 {
+        h.send(message_update(message(id, TARGET, "buy now"))).await;
+    }
+    h.send(message_update(message(50, 31, "hello"))).await;
+
+    let deleted = resolve(&h, "/delete +2 spam", message(4, TARGET, "buy now")).await;
+
+    assert_eq!(deleted[..3], [4, 3, 2]);
+    assert!(!deleted.contains(&1));
+    assert!(!deleted.contains(&50));
+}
+
+#[tokio::test]
+async fn last_minutes_are_deleted_with_target() {
+    let h = Harness::new().await;
+    h.send(message_update(dated(1, now() - 3600))).await;
+    h.send(message_update(dated(2, now()))).await;
+    h.send(message_update(dated(3, now()))).await;
+
+    let deleted = resolve(&h, "/delete 10m", dated(3, now())).await;
+
+    assert_eq!(deleted[..2], [3, 2]);
+    assert!(!deleted.contains(&1));
+}
+
+#[tokio::test]
+async fn minutes_are_counted_back_from_the_target() {
+    let h = Harness::new().await;
+    h.send(message_update(dated(1, now() - 7200))).await;
+    h.send(message_update(dated(2, now() - 3600))).await;
+    h.send(message_update(dated(3, now() - 3600))).await;
+
+    let deleted = resolve(&h, "/delete 10m", dated(3, now() - 3600)).await;
+
+    assert_eq!(deleted[..2], [3, 2]);
+    assert!(!deleted.contains(&1));
+}
+
+#[tokio::test]
+async fn poll_text_names_scope() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+
+    h.send(message_update(reply(
+        6,
+        10,
+        "/delete +5",
+        message(5, TARGET, "buy now"),
+    )))
+    .await;
+
+    let text = last_sent(&h)["text"].as_str().unwrap().to_string();
+    assert!(text.ends_with("\nTheir last 5 messages will be deleted too"));
+}
+
+#[tokio::test]
+async fn tracking_is_bounded() {
+    let h = Harness::new().await;
+
+    for id in 0..(MAX_TRACKED_MESSAGES as i32 + 10) {
+        h.db.track_message(CHAT_ID, TARGET, id, 0).await.unwrap();
+    }
+
+    let kept =
+        h.db.get_recent_messages(CHAT_ID, TARGET, 0, 1000)
+            .await
+            .unwrap();
+    assert_eq!(kept.len() as i64, MAX_TRACKED_MESSAGES);
+    assert_eq!(kept[0].message_id, MAX_TRACKED_MESSAGES as i32 + 9);
+}
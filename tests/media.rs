@@ -0,0 +1,119 @@
+mod common;
+
+use common::*;
+use serde_json::{json, Value};
+
+const TARGET: i64 = 20;
+
+fn sticker(id: i64) -> Value {
+    let mut msg = message(id, TARGET, "");
+    msg.as_object_mut().unwrap().remove("text");
+    msg["sticker"] = json!({
+        "file_id": "s",
+        "file_unique_id": "su",
+        "width": 512,
+        "height": 512,
+        "type": "regular",
+        "is_animated": false,
+        "is_video": false,
+    });
+    msg
+}
+
+fn photo(id: i64) -> Value {
+    let mut msg = message(id, TARGET, "");
+    msg.as_object_mut().unwrap().remove("text");
+    msg["photo"] = json!([{ "file_id": "p", "file_unique_id": "pu", "width": 1, "height": 1 }]);
+    msg
+}
+
+async fn poll_text(h: &Harness, target: Value) -> String {
+    h.send(message_update(reply(100, 10, "/delete", target)))
+        .await;
+
+    last_sent(h)["text"].as_str().unwrap().into()
+}
+
+#[tokio::test]
+async fn delete_command_in_caption_creates_poll() {
+    let h = Harness::new().await;
+
+    let mut msg = photo(6);
+    msg["caption"] = json!("/delete");
+    msg["reply_to_message"] = message(5, TARGET, "spam");
+
+    h.send(message_update(msg)).await;
+
+    assert_eq!(h.api.calls_to("sendMessage").len(), 1);
+}
+
+#[tokio::test]
+async fn polls_describe_what_they_are_about() {
+    let h = Harness::new().await;
+
+    let text = poll_text(&h, message(1, TARGET, "hello")).await;
+    assert!(!text.contains("Content:"));
+
+    let text = poll_text(&h, sticker(2)).await;
+    assert!(text.contains("\nContent: sticker"));
+
+    let mut forwarded = message(3, TARGET, "breaking news");
+    forwarded["forward_from_chat"] = json!({ "id": -100500, "type": "channel", "title": "News" });
+    forwarded["forward_date"] = json!(0);
+    let text = poll_text(&h, forwarded).await;
+    assert!(text.contains("\nContent: text forwarded from News"));
+}
+
+#[tokio::test]
+async fn content_types_have_their_own_threshold() {
+    let h = Harness::new().await;
+    h.set_vote_count(5).await;
+    h.api.set_member(10, admin(user(10)));
+
+    h.send(message_update(message(7, 10, "/type_vote_count sticker 2")))
+        .await;
+    assert_eq!(
+        last_sent(&h)["text"],
+        "Polls about messages of type sticker now need 2 votes"
+    );
+
+    let text = poll_text(&h, sticker(1)).await;
+    assert!(text.contains("is 2*"));
+    let text = poll_text(&h, photo(2)).await;
+    assert!(text.contains("is 5*"));
+
+    h.send(message_update(message(8, 10, "/type_vote_count gif 2")))
+        .await;
+    assert!(last_sent(&h)["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Usage: /type_vote_count"));
+}
+
+#[tokio::test]
+async fn polls_about_service_messages_are_refused() {
+    let h = Harness::new().await;
+
+    let joined = json!({
+        "message_id": 5,
+        "date": 0,
+        "chat": chat(),
+        "from": user(TARGET),
+        "new_chat_members": [user(TARGET)],
+    });
+    h.send(message_update(reply(6, 10, "/delete", joined)))
+        .await;
+
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(
+        sent[0]["text"],
+        "I can only open polls about messages someone sent, not about service messages"
+    );
+    assert!(h
+        .db
+        .get_poll_by_message(CHAT_ID, 5)
+        .await
+        .unwrap()
+        .is_none());
+}
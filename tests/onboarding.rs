@@ -0,0 +1,121 @@
+mod common;
+
+use common::*;
+use serde_json::{json, Value};
+
+fn bot_status(status: &str) -> Value {
+    json!({ "status": status, "user": bot_user() })
+}
+
+fn my_chat_member_update(old: Value, new: Value) -> Value {
+    json!({
+        "my_chat_member": {
+            "chat": chat(),
+            "from": user(10),
+            "date": 0,
+            "old_chat_member": old,
+            "new_chat_member": new,
+        }
+    })
+}
+
+async fn join(h: &Harness) -> Value {
+    h.send(my_chat_member_update(bot_status("left"), admin(bot_user())))
+        .await;
+
+    last_sent(h)
+}
+
+fn button(welcome: &Value, data: &str) -> Value {
+    welcome["reply_markup"]["inline_keyboard"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|row| row.as_array().unwrap().iter())
+        .find(|b| b["callback_data"] == data)
+        .cloned()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn join_creates_chat_and_welcomes() {
+    let h = Harness::new().await;
+
+    let welcome = join(&h).await;
+
+    assert!(h.db.get_chat(CHAT_ID).await.unwrap().is_some());
+    assert!(welcome["text"].as_str().unwrap().starts_with("Hi!"));
+    assert_eq!(button(&welcome, "setup:vote_count:3")["text"], "Votes: 3");
+    assert_eq!(
+        button(&welcome, "setup:language:en")["text"],
+        "Language: en"
+    );
+    assert_eq!(
+        button(&welcome, "setup:poll_delete_delay:30")["text"],
+        "Delay: 30s"
+    );
+}
+
+#[tokio::test]
+async fn admin_uses_quick_setup() {
+    let h = Harness::new().await;
+    let welcome = join(&h).await;
+    h.api.set_member(10, admin(user(10)));
+
+    h.send(callback_update(10, welcome.clone(), "setup:vote_count:7"))
+        .await;
+    h.send(callback_update(
+        10,
+        welcome.clone(),
+        "setup:poll_delete_delay:30",
+    ))
+    .await;
+
+    let chat = h.db.get_chat(CHAT_ID).await.unwrap().unwrap();
+    assert_eq!(chat.minimum_vote_count, 7);
+    assert_eq!(chat.poll_delete_delay, 30);
+    assert_eq!(
+        h.api.calls_to("answerCallbackQuery")[0]["text"],
+        "Successfully updated minimum vote count to 7"
+    );
+}
+
+#[tokio::test]
+async fn members_can_not_use_quick_setup() {
+    let h = Harness::new().await;
+    let welcome = join(&h).await;
+
+    h.send(callback_update(31, welcome, "setup:vote_count:7"))
+        .await;
+
+    let answer = h.api.calls_to("answerCallbackQuery").pop().unwrap();
+    assert_eq!(answer["text"], "Only admins can change settings");
+    assert_eq!(
+        h.db.get_chat(CHAT_ID)
+            .await
+            .unwrap()
+            .unwrap()
+            .minimum_vote_count,
+        h.config.vote_count.default
+    );
+}
+
+#[tokio::test]
+async fn removal_forgets_chat() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.send(message_update(reply(
+        6,
+        10,
+        "/delete",
+        message(5, 20, "spam"),
+    )))
+    .await;
+
+    h.send(my_chat_member_update(admin(bot_user()), bot_status("left")))
+        .await;
+
+    assert!(h.db.get_chat(CHAT_ID).await.unwrap().is_none());
+    assert_eq!(h.db.count_polls(CHAT_ID).await.unwrap(), 0);
+    assert!(h.db.get_member(CHAT_ID, 10).await.unwrap().is_none());
+}
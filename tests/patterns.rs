@@ -0,0 +1,184 @@
+mod common;
+
+use common::*;
+use delete_itt::types::PollOrigin;
+
+const ADMIN: i64 = 40;
+
+async fn admin_command(h: &Harness, text: &str) -> String {
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+    h.send(message_update(message(90, ADMIN, text))).await;
+
+    last_sent(h)["text"].as_str().unwrap().into()
+}
+
+fn deleted(h: &Harness) -> Vec<i64> {
+    h.api
+        .calls_to("deleteMessage")
+        .into_iter()
+        .map(|d| d["message_id"].as_i64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn filter_command_manages_patterns() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+
+    assert_eq!(
+        admin_command(&h, "/filter list").await,
+        "This chat has no filters"
+    );
+    assert_eq!(
+        admin_command(&h, "/filter add buy .* followers").await,
+        "Messages matching buy .* followers now get a poll"
+    );
+    assert_eq!(
+        admin_command(&h, "/filter strict casino").await,
+        "Messages matching casino are now deleted right away"
+    );
+    assert_eq!(
+        admin_command(&h, "/filter list").await,
+        "Filters of this chat:\nbuy .* followers\ncasino (strict)"
+    );
+    assert_eq!(
+        admin_command(&h, "/filter remove casino").await,
+        "Removed the filter casino"
+    );
+    assert_eq!(
+        admin_command(&h, "/filter remove casino").await,
+        "There is no filter casino"
+    );
+    assert!(admin_command(&h, "/filter add (unclosed")
+        .await
+        .starts_with("That is not a valid pattern"));
+    assert!(admin_command(&h, "/filter add")
+        .await
+        .starts_with("Usage: /filter"));
+
+    let patterns = h.db.get_patterns(CHAT_ID).await.unwrap();
+    assert_eq!(patterns.len(), 1);
+    assert!(!patterns[0].strict);
+}
+
+#[tokio::test]
+async fn matching_messages_get_a_tagged_poll() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.db.create_pattern(CHAT_ID, "cheap followers", false)
+        .await
+        .unwrap();
+
+    h.send(message_update(message(5, 20, "Hi all"))).await;
+    assert!(h.api.calls_to("sendMessage").is_empty());
+
+    h.send(message_update(message(6, 20, "Get CHEAP followers here")))
+        .await;
+
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["reply_to_message_id"], 6);
+    assert!(sent[0]["text"]
+        .as_str()
+        .unwrap()
+        .ends_with("_Opened automatically: the message matches a filter of this chat_"));
+
+    let poll = h.db.get_poll_by_message(CHAT_ID, 6).await.unwrap().unwrap();
+    assert_eq!(poll.origin(), Some(PollOrigin::Filter));
+    assert!(deleted(&h).is_empty());
+}
+
+#[tokio::test]
+async fn results_of_filter_polls_are_tagged() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.create_pattern(CHAT_ID, "cheap followers", false)
+        .await
+        .unwrap();
+
+    h.send(message_update(message(6, 20, "Get CHEAP followers here")))
+        .await;
+    let poll = last_sent(&h);
+    h.send(callback_update(31, poll, "vote_yes")).await;
+
+    let result = h.api.calls_to("editMessageText").pop().unwrap();
+    assert!(result["text"]
+        .as_str()
+        .unwrap()
+        .contains("\n_Opened automatically: the message matches a filter of this chat_"));
+    assert_eq!(deleted(&h), vec![6]);
+}
+
+#[tokio::test]
+async fn strict_patterns_delete() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.db.create_pattern(CHAT_ID, "casino", false).await.unwrap();
+    h.db.create_pattern(CHAT_ID, r"casino\.example", true)
+        .await
+        .unwrap();
+
+    h.send(message_update(message(6, 20, "visit casino.example")))
+        .await;
+
+    assert_eq!(deleted(&h), vec![6]);
+    assert!(h.api.calls_to("sendMessage").is_empty());
+}
+
+#[tokio::test]
+async fn commands_are_not_filtered() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.db.create_pattern(CHAT_ID, "casino", true).await.unwrap();
+
+    h.send(message_update(reply(
+        7,
+        30,
+        "/delete casino spam",
+        message(6, 20, "hello"),
+    )))
+    .await;
+
+    assert_eq!(h.api.calls_to("sendMessage")[0]["reply_to_message_id"], 6);
+    assert!(h
+        .db
+        .get_poll_by_message(CHAT_ID, 6)
+        .await
+        .unwrap()
+        .is_some());
+}
+
+#[tokio::test]
+async fn admins_are_not_filtered() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.db.create_pattern(CHAT_ID, "casino", true).await.unwrap();
+    h.api.set_member(20, admin(user(20)));
+
+    h.send(message_update(message(6, 20, "no casino talk please")))
+        .await;
+
+    assert!(deleted(&h).is_empty());
+}
+
+#[tokio::test]
+async fn filter_changes_apply_right_away() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+
+    h.send(message_update(message(5, 20, "visit the casino")))
+        .await;
+    assert!(deleted(&h).is_empty());
+
+    admin_command(&h, "/filter strict casino").await;
+    h.api.clear();
+    h.send(message_update(message(6, 20, "visit the casino")))
+        .await;
+    assert_eq!(deleted(&h), vec![6]);
+
+    admin_command(&h, "/filter remove casino").await;
+    h.api.clear();
+    h.send(message_update(message(7, 20, "visit the casino")))
+        .await;
+    assert!(deleted(&h).is_empty());
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use common::*;
+use delete_itt::types::Escalation;
+use serde_json::{json, Value};
+
+fn my_chat_member_update(old: Value, new: Value) -> Value {
+    json!({
+        "my_chat_member": {
+            "chat": chat(),
+            "from": user(10),
+            "date": 0,
+            "old_chat_member": old,
+            "new_chat_member": new,
+        }
+    })
+}
+
+fn bot_left() -> Value {
+    json!({ "status": "left", "user": bot_user() })
+}
+
+fn bot_member() -> Value {
+    json!({ "status": "member", "user": bot_user() })
+}
+
+async fn trigger(h: &Harness) {
+    h.send(message_update(reply(
+        6,
+        10,
+        "/delete",
+        message(5, 20, "spam"),
+    )))
+    .await;
+}
+
+#[tokio::test]
+async fn no_poll_without_rights() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.api.set_member(BOT_ID, bot_member());
+
+    trigger(&h).await;
+
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(
+        sent[0]["text"],
+        "I can not work properly here. Please grant me these admin rights: Delete messages"
+    );
+    assert_eq!(h.db.count_polls(CHAT_ID).await.unwrap(), 0);
+}
+
+fn bot_without_ban_rights() -> Value {
+    let mut bot = admin(bot_user());
+    bot["can_restrict_members"] = json!(false);
+    bot
+}
+
+#[tokio::test]
+async fn ban_rights_are_only_needed_for_escalation() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.api.set_member(BOT_ID, bot_without_ban_rights());
+
+    trigger(&h).await;
+    assert_eq!(h.api.calls_to("sendMessage").len(), 1);
+    assert_eq!(h.db.count_polls(CHAT_ID).await.unwrap(), 1);
+
+    h.db.set_chat_escalation(CHAT_ID, Escalation::Ban, 3)
+        .await
+        .unwrap();
+    h.api.clear();
+    h.send(message_update(reply(
+        8,
+        10,
+        "/delete",
+        message(7, 20, "more spam"),
+    )))
+    .await;
+
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent.len(), 2);
+    assert_eq!(
+        sent[0]["text"],
+        "I can not mute or ban anyone here as escalation or the federation asks. Please grant me \
+        these admin rights: Ban users"
+    );
+    assert_eq!(h.db.count_polls(CHAT_ID).await.unwrap(), 2);
+
+    let warning = h
+        .api
+        .calls()
+        .into_iter()
+        .find(|c| c.method == "sendMessage")
+        .unwrap()
+        .response["result"]["message_id"]
+        .as_i64()
+        .unwrap();
+    let scheduled = h.db.get_pending_messages_to_delete(i64::MAX).await.unwrap();
+    assert!(scheduled.iter().any(|m| m.message_id as i64 == warning));
+}
+
+#[tokio::test]
+async fn check_command_reports_rights() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.api.set_member(10, admin(user(10)));
+
+    h.send(message_update(message(7, 10, "/check"))).await;
+    assert_eq!(
+        last_sent(&h)["text"],
+        "I have every admin right I need here"
+    );
+
+    h.api.set_member(BOT_ID, bot_without_ban_rights());
+
+    h.send(message_update(message(8, 10, "/check"))).await;
+    assert_eq!(
+        last_sent(&h)["text"],
+        "I have every admin right I need here"
+    );
+
+    h.db.set_chat_escalation(CHAT_ID, Escalation::Mute, 3)
+        .await
+        .unwrap();
+    h.send(message_update(message(9, 10, "/check"))).await;
+    assert_eq!(
+        last_sent(&h)["text"],
+        "I can not work properly here. Please grant me these admin rights: Ban users"
+    );
+}
+
+#[tokio::test]
+async fn failed_deletion_still_closes_poll() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    trigger(&h).await;
+    let poll = last_sent(&h);
+    h.api.clear();
+
+    h.api.script(
+        "deleteMessage",
+        api_error("Bad Request: message can't be deleted"),
+    );
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+
+    assert!(h
+        .db
+        .get_poll(CHAT_ID, poll["message_id"].as_i64().unwrap() as i32)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(h.api.calls_to("editMessageText")[0]["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Could not delete the message"));
+}
+
+#[tokio::test]
+async fn joining_without_rights_is_reported() {
+    let h = Harness::new().await;
+
+    h.send(my_chat_member_update(bot_left(), bot_member()))
+        .await;
+
+    assert!(last_sent(&h)["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("I can not work properly here"));
+
+    h.api.clear();
+    h.send(my_chat_member_update(bot_member(), admin(bot_user())))
+        .await;
+
+    assert!(h.api.calls_to("sendMessage").is_empty());
+}
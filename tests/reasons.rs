@@ -0,0 +1,81 @@
+mod common;
+
+use common::*;
+
+const TARGET: i64 = 20;
+
+async fn trigger(h: &Harness, text: &str) -> serde_json::Value {
+    trigger_on(h, 5, text).await
+}
+
+async fn trigger_on(h: &Harness, target_id: i64, text: &str) -> serde_json::Value {
+    h.send(message_update(reply(
+        target_id + 1,
+        10,
+        text,
+        message(target_id, TARGET, "buy now"),
+    )))
+    .await;
+
+    last_sent(h)
+}
+
+async fn stored_reason(h: &Harness, poll: &serde_json::Value) -> Option<String> {
+    h.db.get_poll(CHAT_ID, poll["message_id"].as_i64().unwrap() as i32)
+        .await
+        .unwrap()
+        .unwrap()
+        .reason
+}
+
+#[test]
+fn parse_strips_trigger() {
+    use delete_itt::reason::parse;
+
+    assert_eq!(parse(BOT_USERNAME, "/delete spam"), Some("spam".into()));
+    assert_eq!(
+        parse(BOT_USERNAME, "/delete@Delete_Itt_Bot  off topic"),
+        Some("off topic".into())
+    );
+    assert_eq!(
+        parse(BOT_USERNAME, "@delete_itt_bot nsfw pics"),
+        Some("nsfw pics".into())
+    );
+    assert_eq!(parse(BOT_USERNAME, "@delete_itt_bot"), None);
+}
+
+#[tokio::test]
+async fn keyword_reason_is_translated_and_stored() {
+    let h = Harness::new().await;
+
+    let poll = trigger(&h, "/delete offtopic").await;
+
+    assert!(poll["text"].as_str().unwrap().contains("for off\\-topic?"));
+    assert_eq!(stored_reason(&h, &poll).await, Some("offtopic".into()));
+}
+
+#[tokio::test]
+async fn free_text_reason_is_escaped() {
+    let h = Harness::new().await;
+
+    let poll = trigger(&h, &format!("@{} rude_words.", BOT_USERNAME)).await;
+
+    assert!(poll["text"].as_str().unwrap().contains("rude\\_words\\."));
+    assert_eq!(stored_reason(&h, &poll).await, Some("rude_words.".into()));
+}
+
+#[tokio::test]
+async fn reason_keyword_uses_its_own_threshold() {
+    let h = Harness::new().await;
+    h.set_vote_count(5).await;
+    h.api.set_member(10, admin(user(10)));
+
+    h.send(message_update(message(7, 10, "/reason_vote_count spam 2")))
+        .await;
+
+    let poll = trigger(&h, "/delete spam again").await;
+    assert!(poll["text"].as_str().unwrap().ends_with("is 2*"));
+
+    let poll = trigger_on(&h, 8, "/delete nsfw").await;
+    assert!(poll["text"].as_str().unwrap().ends_with("is 5*"));
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::*;
+use delete_itt::reconcile;
+use serde_json::{json, Value};
+
+const NOT_MODIFIED: &str = "Bad Request: message is not modified: specified new message content \
+    and reply markup are exactly the same as a current content and reply markup of the message";
+const EDIT_NOT_FOUND: &str = "Bad Request: message to edit not found";
+
+async fn start_poll(h: &Harness) -> Value {
+    h.set_vote_count(3).await;
+    h.send(message_update(reply(
+        6,
+        10,
+        "/delete",
+        message(5, 20, "spam"),
+    )))
+    .await;
+
+    let poll = last_sent(h);
+    h.api.clear();
+    poll
+}
+
+async fn run(h: &Harness) {
+    reconcile::run(&h.bot, &h.db, &h.config, &h.loc).await;
+}
+
+async fn poll_exists(h: &Harness, poll: &Value) -> bool {
+    h.db.get_poll(CHAT_ID, poll["message_id"].as_i64().unwrap() as i32)
+        .await
+        .unwrap()
+        .is_some()
+}
+
+#[tokio::test]
+async fn chat_the_bot_left_is_forgotten() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.api
+        .set_member(BOT_ID, json!({ "status": "left", "user": bot_user() }));
+
+    run(&h).await;
+
+    assert!(h.db.get_chat(CHAT_ID).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn chat_that_disappeared_is_forgotten() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.api
+        .script("getChatMember", api_error("Bad Request: chat not found"));
+
+    run(&h).await;
+
+    assert!(h.db.get_chat(CHAT_ID).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn poll_without_poll_message_is_closed() {
+    let h = Harness::new().await;
+    let poll = start_poll(&h).await;
+    let info =
+        h.db.get_poll(CHAT_ID, poll["message_id"].as_i64().unwrap() as i32)
+            .await
+            .unwrap()
+            .unwrap();
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+    assert!(h.db.get_voter(info.id, 31).await.unwrap().is_some());
+
+    h.api
+        .script("editMessageReplyMarkup", api_error(EDIT_NOT_FOUND));
+    run(&h).await;
+
+    assert!(!poll_exists(&h, &poll).await);
+    assert!(h.db.get_voter(info.id, 31).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn poll_without_target_is_closed() {
+    let h = Harness::new().await;
+    let poll = start_poll(&h).await;
+
+    h.api
+        .script("editMessageReplyMarkup", api_error(NOT_MODIFIED));
+    h.api
+        .script("editMessageReplyMarkup", api_error(EDIT_NOT_FOUND));
+    run(&h).await;
+
+    assert!(!poll_exists(&h, &poll).await);
+    assert_eq!(
+        h.api.calls_to("deleteMessage")[0]["message_id"],
+        poll["message_id"]
+    );
+}
+
+#[tokio::test]
+async fn poll_with_both_messages_stays_open() {
+    let h = Harness::new().await;
+    let poll = start_poll(&h).await;
+
+    h.api
+        .script("editMessageReplyMarkup", api_error(NOT_MODIFIED));
+    h.api.script(
+        "editMessageReplyMarkup",
+        api_error("Bad Request: message can't be edited"),
+    );
+    run(&h).await;
+
+    assert!(poll_exists(&h, &poll).await);
+    assert!(h.api.calls_to("deleteMessage").is_empty());
+}
+
+#[tokio::test]
+async fn orphan_voters_are_removed() {
+    let h = Harness::new().await;
+    h.db.create_voter(999, 31).await.unwrap();
+
+    run(&h).await;
+
+    assert!(h.db.get_voter(999, 31).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn vote_on_deleted_poll_message_closes_poll() {
+    let h = Harness::new().await;
+    let poll = start_poll(&h).await;
+
+    h.api
+        .script("editMessageReplyMarkup", api_error(EDIT_NOT_FOUND));
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+
+    assert!(!poll_exists(&h, &poll).await);
+}
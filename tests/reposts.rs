@@ -0,0 +1,178 @@
+mod common;
+
+use common::*;
+use delete_itt::fingerprint::fingerprints;
+use delete_itt::types::{FingerprintAction, PollOrigin};
+use serde_json::{json, Value};
+use teloxide::types::Message;
+
+const SPAM: &str = "Buy cheap followers now, only today!";
+const OTHER_CHAT: i64 = -1002;
+
+fn parse(message: Value) -> Message {
+    serde_json::from_str(&message.to_string()).unwrap()
+}
+
+fn in_chat(mut message: Value, chat_id: i64) -> Value {
+    message["chat"]["id"] = json!(chat_id);
+    message
+}
+
+/// Has `target` deleted by a poll that passes with a single vote.
+async fn delete_by_vote(h: &Harness, target: Value) {
+    h.send(message_update(reply(100, 10, "/delete", target)))
+        .await;
+
+    let poll = last_sent(h);
+    h.send(callback_update(31, poll, "vote_yes")).await;
+    h.api.clear();
+}
+
+fn deleted(h: &Harness) -> Vec<i64> {
+    h.api
+        .calls_to("deleteMessage")
+        .into_iter()
+        .map(|d| d["message_id"].as_i64().unwrap())
+        .collect()
+}
+
+#[test]
+fn fingerprints_ignore_case_and_punctuation() {
+    let a = fingerprints(&parse(message(1, 20, SPAM)));
+    let b = fingerprints(&parse(message(
+        2,
+        21,
+        "BUY cheap followers now only today 🔥",
+    )));
+
+    assert_eq!(a.len(), 1);
+    assert!(a[0].starts_with("text:"));
+    assert_eq!(a, b);
+    assert!(fingerprints(&parse(message(3, 20, "ok!"))).is_empty());
+}
+
+#[test]
+fn fingerprints_include_media_and_domains() {
+    let mut m = message(1, 20, "");
+    m.as_object_mut().unwrap().remove("text");
+    m["caption"] = json!("see www.Example.com/offer");
+    m["caption_entities"] = json!([{ "type": "url", "offset": 4, "length": 21 }]);
+    m["photo"] = json!([
+        { "file_id": "small", "file_unique_id": "s1", "width": 90, "height": 90 },
+        { "file_id": "big", "file_unique_id": "b1", "width": 800, "height": 800 },
+    ]);
+
+    let prints = fingerprints(&parse(m));
+
+    assert!(prints[0].starts_with("text:"));
+    assert_eq!(prints[1..], ["file:b1", "domain:example.com"]);
+}
+
+#[test]
+fn fingerprints_skip_common_domains() {
+    let mut m = message(1, 20, "look at m.youtube.com/watch");
+    m["entities"] = json!([
+        { "type": "url", "offset": 8, "length": 19 },
+        { "type": "text_link", "offset": 0, "length": 4, "url": "https://t.me/somechannel" },
+    ]);
+
+    let prints = fingerprints(&parse(m));
+
+    assert_eq!(prints.len(), 1);
+    assert!(prints[0].starts_with("text:"));
+}
+
+#[tokio::test]
+async fn reposts_are_ignored_by_default() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    delete_by_vote(&h, message(1, 20, SPAM)).await;
+    h.send(message_update(message(5, 21, SPAM))).await;
+
+    assert!(h.api.calls().is_empty());
+}
+
+#[tokio::test]
+async fn reposts_are_deleted() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_fingerprint_action(CHAT_ID, Some(FingerprintAction::Delete))
+        .await
+        .unwrap();
+
+    delete_by_vote(&h, message(1, 20, SPAM)).await;
+    h.send(message_update(message(5, 21, &SPAM.to_uppercase())))
+        .await;
+    h.send(message_update(message(
+        6,
+        21,
+        "Something else entirely, really",
+    )))
+    .await;
+
+    assert_eq!(deleted(&h), vec![5]);
+}
+
+#[tokio::test]
+async fn reposts_open_a_poll() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_fingerprint_action(CHAT_ID, Some(FingerprintAction::Poll))
+        .await
+        .unwrap();
+
+    delete_by_vote(&h, message(1, 20, SPAM)).await;
+    h.send(message_update(message(5, 21, SPAM))).await;
+
+    assert!(deleted(&h).is_empty());
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent[0]["reply_to_message_id"], 5);
+    let poll = h.db.get_poll_by_message(CHAT_ID, 5).await.unwrap().unwrap();
+    assert_eq!(poll.origin(), Some(PollOrigin::Repost));
+}
+
+#[tokio::test]
+async fn admins_may_repost() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_fingerprint_action(CHAT_ID, Some(FingerprintAction::Delete))
+        .await
+        .unwrap();
+    h.api.set_member(21, admin(user(21)));
+
+    delete_by_vote(&h, message(1, 20, SPAM)).await;
+    h.send(message_update(message(5, 21, SPAM))).await;
+
+    assert!(deleted(&h).is_empty());
+}
+
+#[tokio::test]
+async fn fingerprints_are_shared_between_sharing_chats() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.create_chat(OTHER_CHAT, 1, "en", 5).await.unwrap();
+    h.db.set_chat_fingerprint_action(OTHER_CHAT, Some(FingerprintAction::Delete))
+        .await
+        .unwrap();
+
+    delete_by_vote(&h, message(1, 20, SPAM)).await;
+
+    h.send(message_update(in_chat(message(5, 20, SPAM), OTHER_CHAT)))
+        .await;
+    assert!(deleted(&h).is_empty());
+
+    h.db.set_chat_share_fingerprints(OTHER_CHAT, true)
+        .await
+        .unwrap();
+    h.send(message_update(in_chat(message(6, 20, SPAM), OTHER_CHAT)))
+        .await;
+    assert!(deleted(&h).is_empty());
+
+    h.db.set_chat_share_fingerprints(CHAT_ID, true)
+        .await
+        .unwrap();
+    h.send(message_update(in_chat(message(7, 20, SPAM), OTHER_CHAT)))
+        .await;
+    assert_eq!(deleted(&h), vec![7]);
+}
@@ -0,0 +1,251 @@
+mod common;
+
+use common::*;
+use delete_itt::types::ContentKind;
+use serde_json::{json, Value};
+
+const TARGET: i64 = 20;
+const ADMIN: i64 = 40;
+
+async fn admin_command(h: &Harness, text: &str) -> String {
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+    h.send(message_update(message(90, ADMIN, text))).await;
+
+    last_sent(h)["text"].as_str().unwrap().into()
+}
+
+/// Has `target` deleted by a poll that passes with a single vote. Returns the result message.
+async fn delete_by_vote(h: &Harness, target: Value) -> Value {
+    h.send(message_update(reply(100, 10, "/delete", target)))
+        .await;
+
+    let poll = last_sent(h);
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+    h.api.clear();
+
+    poll
+}
+
+async fn enable_undo(h: &Harness) {
+    h.set_vote_count(1).await;
+    h.db.set_chat_undo_window(CHAT_ID, 600).await.unwrap();
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+}
+
+#[tokio::test]
+async fn undo_window_command() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+
+    assert_eq!(
+        admin_command(&h, "/undo_window 30").await,
+        "Admins can now restore messages deleted by vote for 30 minutes"
+    );
+    assert_eq!(
+        h.db.get_chat(CHAT_ID).await.unwrap().unwrap().undo_window,
+        1800
+    );
+    assert!(admin_command(&h, "/undo_window 5000")
+        .await
+        .starts_with("Usage: /undo_window"));
+    assert_eq!(
+        admin_command(&h, "/undo_window 0").await,
+        "Messages deleted by vote can no longer be restored"
+    );
+}
+
+#[tokio::test]
+async fn admins_restore_deleted_messages() {
+    let h = Harness::new().await;
+    enable_undo(&h).await;
+
+    h.send(message_update(reply(
+        100,
+        10,
+        "/delete",
+        message(1, TARGET, "original text"),
+    )))
+    .await;
+    let result = last_sent(&h);
+    h.send(callback_update(31, result.clone(), "vote_yes"))
+        .await;
+
+    let markup = h.api.calls_to("editMessageReplyMarkup");
+    assert_eq!(
+        markup.last().unwrap()["reply_markup"]["inline_keyboard"][0][0]["callback_data"],
+        "restore"
+    );
+    let archive = h.db.get_archive(CHAT_ID, 10001).await.unwrap().unwrap();
+    assert_eq!(archive.kind(), ContentKind::Text);
+    assert!(archive.expires.is_some());
+    h.api.clear();
+
+    h.send(callback_update(ADMIN, result.clone(), "restore"))
+        .await;
+
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent.len(), 2);
+    assert!(sent[0]["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Message from"));
+    assert_eq!(sent[1]["text"], "original text");
+    assert_eq!(sent[1]["reply_to_message_id"], 10002);
+    assert!(h.api.calls_to("editMessageReplyMarkup")[0]
+        .get("reply_markup")
+        .is_none());
+    assert!(h.db.get_archive(CHAT_ID, 10001).await.unwrap().is_none());
+
+    h.api.clear();
+    h.send(callback_update(ADMIN, result, "restore")).await;
+
+    assert!(h.api.calls_to("sendMessage").is_empty());
+    assert_eq!(
+        h.api.calls_to("answerCallbackQuery")[0]["text"],
+        "This message can no longer be restored"
+    );
+}
+
+#[tokio::test]
+async fn media_is_restored() {
+    let h = Harness::new().await;
+    enable_undo(&h).await;
+
+    let mut photo = message(1, TARGET, "");
+    photo.as_object_mut().unwrap().remove("text");
+    photo["caption"] = json!("look");
+    photo["photo"] = json!([
+        { "file_id": "small", "file_unique_id": "s1", "width": 90, "height": 90 },
+        { "file_id": "big", "file_unique_id": "b1", "width": 800, "height": 800 },
+    ]);
+
+    let result = delete_by_vote(&h, photo).await;
+
+    let archive = h.db.get_archive(CHAT_ID, 10001).await.unwrap().unwrap();
+    assert_eq!(archive.kind(), ContentKind::Photo);
+    assert_eq!(archive.file_id.as_deref(), Some("big"));
+    assert_eq!(archive.text.as_deref(), Some("look"));
+
+    h.api.script(
+        "sendPhoto",
+        json!({ "ok": true, "result": message(10003, BOT_ID, "") }),
+    );
+    h.send(callback_update(ADMIN, result, "restore")).await;
+
+    assert_eq!(h.api.calls_to("sendPhoto").len(), 1);
+}
+
+#[tokio::test]
+async fn failed_restores_can_be_retried() {
+    let h = Harness::new().await;
+    enable_undo(&h).await;
+
+    let mut photo = message(1, TARGET, "");
+    photo.as_object_mut().unwrap().remove("text");
+    photo["photo"] = json!([{ "file_id": "old", "file_unique_id": "o1", "width": 1, "height": 1 }]);
+
+    let result = delete_by_vote(&h, photo).await;
+    let expires =
+        h.db.get_archive(CHAT_ID, 10001)
+            .await
+            .unwrap()
+            .unwrap()
+            .expires;
+
+    h.api.script(
+        "sendPhoto",
+        api_error("Bad Request: wrong file identifier/HTTP URL specified"),
+    );
+    h.send(callback_update(ADMIN, result.clone(), "restore"))
+        .await;
+
+    assert_eq!(
+        h.api.calls_to("answerCallbackQuery")[0]["text"],
+        "I could not post this message again. Try again later"
+    );
+    let attribution = h
+        .api
+        .calls()
+        .into_iter()
+        .find(|c| c.method == "sendMessage")
+        .unwrap()
+        .response["result"]["message_id"]
+        .clone();
+    assert_eq!(
+        h.api.calls_to("deleteMessage")[0]["message_id"],
+        attribution
+    );
+    let archive = h.db.get_archive(CHAT_ID, 10001).await.unwrap().unwrap();
+    assert_eq!(archive.file_id.as_deref(), Some("old"));
+    assert_eq!(archive.expires, expires);
+
+    h.api.clear();
+    h.api.script(
+        "sendPhoto",
+        json!({ "ok": true, "result": message(10004, BOT_ID, "") }),
+    );
+    h.send(callback_update(ADMIN, result, "restore")).await;
+
+    assert_eq!(h.api.calls_to("answerCallbackQuery")[0]["text"], "Restored");
+    assert!(h.db.get_archive(CHAT_ID, 10001).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn only_admins_restore() {
+    let h = Harness::new().await;
+    enable_undo(&h).await;
+
+    let result = delete_by_vote(&h, message(1, TARGET, "original text")).await;
+    h.send(callback_update(31, result, "restore")).await;
+
+    assert!(h.api.calls_to("sendMessage").is_empty());
+    let answer = &h.api.calls_to("answerCallbackQuery")[0];
+    assert_eq!(answer["text"], "Only admins can restore messages");
+    assert_eq!(answer["show_alert"], true);
+    assert!(h.db.get_archive(CHAT_ID, 10001).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn nothing_is_archived_without_a_window() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    h.send(message_update(reply(
+        100,
+        10,
+        "/delete",
+        message(1, TARGET, "original text"),
+    )))
+    .await;
+    let poll = last_sent(&h);
+    h.send(callback_update(31, poll, "vote_yes")).await;
+
+    assert!(h.db.get_archive(CHAT_ID, 10001).await.unwrap().is_none());
+    assert!(h
+        .api
+        .calls_to("editMessageReplyMarkup")
+        .last()
+        .unwrap()
+        .get("reply_markup")
+        .is_none());
+}
+
+#[tokio::test]
+async fn archives_of_closed_polls_are_removed() {
+    let h = Harness::new().await;
+    h.db.create_archive(CHAT_ID, 10001, TARGET, ContentKind::Text, None, Some("hi"))
+        .await
+        .unwrap();
+    h.db.create_archive(CHAT_ID, 10002, TARGET, ContentKind::Text, None, Some("hi"))
+        .await
+        .unwrap();
+    let kept = h.db.get_archive(CHAT_ID, 10002).await.unwrap().unwrap();
+    h.db.set_archive_expiry(kept.id, 100).await.unwrap();
+
+    h.db.remove_stale_archives(50).await.unwrap();
+    assert!(h.db.get_archive(CHAT_ID, 10001).await.unwrap().is_none());
+    assert!(h.db.get_archive(CHAT_ID, 10002).await.unwrap().is_some());
+
+    h.db.remove_stale_archives(150).await.unwrap();
+    assert!(h.db.get_archive(CHAT_ID, 10002).await.unwrap().is_none());
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use common::*;
+use delete_itt::types::Escalation;
+use serde_json::{json, Value};
+
+const CHANNEL_ID: i64 = -100777;
+
+fn channel() -> Value {
+    json!({ "id": CHANNEL_ID, "type": "channel", "title": "Spam channel", "username": "spamchan" })
+}
+
+/// A message posted into the group on behalf of a channel.
+fn channel_post(id: i64, text: &str) -> Value {
+    let mut msg = message(id, 0, text);
+    msg["from"] = json!({ "id": 136817688, "is_bot": true, "first_name": "Channel" });
+    msg["sender_chat"] = channel();
+    msg
+}
+
+/// A message from an admin posting as the group itself.
+fn anonymous(id: i64, text: &str) -> Value {
+    let mut msg = message(id, 0, text);
+    msg["from"] = json!({ "id": 1087968824, "is_bot": true, "first_name": "Group" });
+    msg["sender_chat"] = chat();
+    msg
+}
+
+/// Has a channel post deleted by a poll that passes with a single vote.
+async fn delete_channel_post(h: &Harness) {
+    h.api
+        .script("getChat", json!({ "ok": true, "result": channel() }));
+    h.send(message_update(reply(
+        100,
+        10,
+        "/delete",
+        channel_post(1, "buy now"),
+    )))
+    .await;
+
+    let poll = last_sent(h);
+    h.send(callback_update(31, poll, "vote_yes")).await;
+}
+
+#[tokio::test]
+async fn channel_posts_are_attributed_to_the_channel() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    h.send(message_update(reply(
+        100,
+        10,
+        "/delete",
+        channel_post(1, "buy now"),
+    )))
+    .await;
+
+    let poll = last_sent(&h);
+    assert!(poll["text"]
+        .as_str()
+        .unwrap()
+        .contains("[Spam channel](https://t.me/spamchan)"));
+    let stored = h.db.get_poll_by_message(CHAT_ID, 1).await.unwrap().unwrap();
+    assert_eq!(stored.message_user_id, CHANNEL_ID);
+
+    h.api
+        .script("getChat", json!({ "ok": true, "result": channel() }));
+    h.send(callback_update(31, poll, "vote_yes")).await;
+
+    let result = h.api.calls_to("editMessageText")[0]["text"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(result.contains("Spam channel"));
+    assert!(!result.contains("start=appeal"));
+    assert_eq!(h.db.count_strikes(CHAT_ID, CHANNEL_ID, 0).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn channels_are_banned_but_not_muted() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_escalation(CHAT_ID, Escalation::Mute, 1)
+        .await
+        .unwrap();
+
+    delete_channel_post(&h).await;
+    assert!(h.api.calls_to("restrictChatMember").is_empty());
+
+    h.db.set_chat_escalation(CHAT_ID, Escalation::Ban, 2)
+        .await
+        .unwrap();
+    delete_channel_post(&h).await;
+
+    let bans = h.api.calls_to("banChatSenderChat");
+    assert_eq!(bans.len(), 1);
+    assert_eq!(bans[0]["sender_chat_id"], CHANNEL_ID);
+    assert!(h.api.calls_to("banChatMember").is_empty());
+}
+
+#[tokio::test]
+async fn anonymous_admins_are_not_voted_on() {
+    let h = Harness::new().await;
+
+    h.send(message_update(reply(
+        100,
+        10,
+        "/delete",
+        anonymous(1, "announcement"),
+    )))
+    .await;
+
+    assert!(h.api.calls_to("sendMessage").is_empty());
+}
+
+#[tokio::test]
+async fn anonymous_admins_are_privileged_triggers() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.db.set_chat_max_open_polls(CHAT_ID, 1).await.unwrap();
+
+    h.send(message_update(reply(
+        100,
+        10,
+        "/delete",
+        message(1, 20, "spam"),
+    )))
+    .await;
+    h.send(message_update(reply(
+        101,
+        10,
+        "/delete",
+        message(2, 20, "more spam"),
+    )))
+    .await;
+    assert!(last_sent(&h)["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("There are already"));
+
+    let mut trigger = anonymous(102, &format!("@{}", BOT_USERNAME));
+    trigger["reply_to_message"] = message(3, 20, "even more spam");
+    h.send(message_update(trigger)).await;
+    assert!(h
+        .db
+        .get_poll_by_message(CHAT_ID, 3)
+        .await
+        .unwrap()
+        .is_some());
+
+    h.send(message_update(anonymous(103, "/vote_count 4")))
+        .await;
+    assert_eq!(
+        h.db.get_chat(CHAT_ID)
+            .await
+            .unwrap()
+            .unwrap()
+            .minimum_vote_count,
+        4
+    );
+}
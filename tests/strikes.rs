@@ -0,0 +1,139 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::*;
+use delete_itt::types::Escalation;
+use delete_itt::{Database, Store};
+use serde_json::{json, Value};
+
+const TARGET: i64 = 20;
+const ADMIN: i64 = 40;
+
+/// Starts a poll about `target` and has it pass with a single vote.
+async fn delete_by_vote(h: &Harness, trigger_id: i64, target: Value) {
+    h.send(message_update(reply(trigger_id, 10, "/delete", target)))
+        .await;
+
+    let poll = last_sent(h);
+    h.send(callback_update(31, poll, "vote_yes")).await;
+}
+
+fn result_text(h: &Harness) -> String {
+    h.api.calls_to("editMessageText").last().unwrap()["text"]
+        .as_str()
+        .unwrap()
+        .into()
+}
+
+async fn admin_command(h: &Harness, text: &str) -> String {
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+    h.send(message_update(message(90, ADMIN, text))).await;
+
+    last_sent(h)["text"].as_str().unwrap().into()
+}
+
+#[tokio::test]
+async fn enough_strikes_mute() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_escalation(CHAT_ID, Escalation::Mute, 2)
+        .await
+        .unwrap();
+
+    delete_by_vote(&h, 100, message(1, TARGET, "spam")).await;
+    assert!(h.api.calls_to("restrictChatMember").is_empty());
+
+    delete_by_vote(&h, 101, message(2, TARGET, "spam")).await;
+
+    let restricted = h.api.calls_to("restrictChatMember");
+    assert_eq!(restricted.len(), 1);
+    assert_eq!(restricted[0]["user_id"], TARGET);
+    assert!(restricted[0]["until_date"].as_i64().unwrap() > 0);
+    assert!(result_text(&h).ends_with("is muted for a day after 2 strikes"));
+}
+
+#[tokio::test]
+async fn ban_takes_over_from_mute() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_escalation(CHAT_ID, Escalation::Mute, 1)
+        .await
+        .unwrap();
+    h.db.set_chat_escalation(CHAT_ID, Escalation::Ban, 2)
+        .await
+        .unwrap();
+
+    delete_by_vote(&h, 100, message(1, TARGET, "spam")).await;
+    delete_by_vote(&h, 101, message(2, TARGET, "spam")).await;
+
+    assert_eq!(h.api.calls_to("restrictChatMember").len(), 1);
+    let banned = h.api.calls_to("banChatMember");
+    assert_eq!(banned.len(), 1);
+    assert_eq!(banned[0]["user_id"], TARGET);
+    assert!(result_text(&h).ends_with("is banned after 2 strikes"));
+}
+
+#[tokio::test]
+async fn old_strikes_decay() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_escalation(CHAT_ID, Escalation::Mute, 2)
+        .await
+        .unwrap();
+    h.db.create_strike(CHAT_ID, TARGET, 100, 1).await.unwrap();
+
+    delete_by_vote(&h, 100, message(1, TARGET, "spam")).await;
+
+    assert!(h.api.calls_to("restrictChatMember").is_empty());
+    assert_eq!(h.db.count_strikes(CHAT_ID, TARGET, 0).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn a_poll_gives_one_strike() {
+    let sql: Store = Arc::new(Database::new("sqlite::memory:", 1).await);
+
+    for db in [Harness::new().await.db, sql] {
+        assert!(db.create_strike(CHAT_ID, TARGET, 100, 1).await.unwrap());
+        assert!(!db.create_strike(CHAT_ID, TARGET, 100, 2).await.unwrap());
+        assert!(db.create_strike(CHAT_ID, TARGET, 101, 3).await.unwrap());
+        assert_eq!(db.count_strikes(CHAT_ID, TARGET, 0).await.unwrap(), 2);
+    }
+}
+
+#[tokio::test]
+async fn strikes_command_reports_and_resets() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    let mut spammer = message(1, TARGET, "spam");
+    spammer["from"]["username"] = json!("Spammer");
+    h.send(message_update(spammer.clone())).await;
+    delete_by_vote(&h, 100, spammer).await;
+
+    assert_eq!(
+        admin_command(&h, "/strikes @spammer").await,
+        "@spammer has 1 strikes from the last 7 days"
+    );
+
+    assert_eq!(
+        admin_command(&h, "/strikes @spammer reset").await,
+        "Strikes of @spammer were reset"
+    );
+    assert_eq!(h.db.count_strikes(CHAT_ID, TARGET, 0).await.unwrap(), 0);
+
+    assert_eq!(
+        admin_command(&h, "/strikes @nobody").await,
+        "Usage: /strikes @user, or reply with /strikes. Add reset to clear their strikes"
+    );
+}
+
+#[tokio::test]
+async fn strikes_command_is_for_admins() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+
+    h.send(message_update(message(90, 31, "/strikes 20"))).await;
+
+    assert!(h.api.calls_to("sendMessage").is_empty());
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use common::*;
+use delete_itt::{Database, Storage};
+use serde_json::{json, Value};
+
+const TARGET: i64 = 20;
+const ADMIN: i64 = 40;
+
+/// `msg` as posted in the topic `thread_id` of a forum.
+fn in_topic(mut msg: Value, thread_id: i32) -> Value {
+    msg["chat"]["is_forum"] = json!(true);
+    msg["message_thread_id"] = json!(thread_id);
+    msg["is_topic_message"] = json!(true);
+    msg
+}
+
+async fn admin_command(h: &Harness, msg: Value) -> Value {
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+    h.api.clear();
+    h.send(message_update(msg)).await;
+
+    h.api.calls_to("sendMessage").pop().unwrap()
+}
+
+/// Starts a poll in the topic `thread_id`, about a message of its own. Returns the poll as sent,
+/// if there is one.
+async fn poll_in_topic(h: &Harness, thread_id: i32) -> Option<Value> {
+    h.api.clear();
+    h.send(message_update(in_topic(
+        reply(
+            100,
+            10,
+            "/delete",
+            in_topic(message(thread_id.into(), TARGET, "hi"), thread_id),
+        ),
+        thread_id,
+    )))
+    .await;
+
+    h.api.calls_to("sendMessage").pop()
+}
+
+#[tokio::test]
+async fn polls_stay_in_their_topic() {
+    let h = Harness::new().await;
+
+    let poll = poll_in_topic(&h, 7).await.unwrap();
+
+    assert_eq!(poll["message_thread_id"], 7);
+    assert_eq!(
+        h.db.get_poll(CHAT_ID, 10001)
+            .await
+            .unwrap()
+            .unwrap()
+            .thread_id,
+        Some(7)
+    );
+}
+
+#[tokio::test]
+async fn reply_threads_outside_forums_are_not_topics() {
+    let h = Harness::new().await;
+
+    let mut target = message(1, TARGET, "hi");
+    target["message_thread_id"] = json!(7);
+    let mut trigger = reply(100, 10, "/delete", target);
+    trigger["message_thread_id"] = json!(7);
+    h.send(message_update(trigger)).await;
+
+    let poll = h.api.calls_to("sendMessage").pop().unwrap();
+    assert!(poll.get("message_thread_id").is_none());
+}
+
+#[tokio::test]
+async fn topics_override_the_vote_count() {
+    let h = Harness::new().await;
+    h.set_vote_count(5).await;
+
+    let response = admin_command(&h, in_topic(message(90, ADMIN, "/topic_vote_count 2"), 7)).await;
+    assert_eq!(response["text"], "Polls in this topic now need 2 votes");
+    assert_eq!(response["message_thread_id"], 7);
+
+    let text = poll_in_topic(&h, 7).await.unwrap()["text"].clone();
+    assert!(text.as_str().unwrap().contains("is 2*"));
+    let text = poll_in_topic(&h, 8).await.unwrap()["text"].clone();
+    assert!(text.as_str().unwrap().contains("is 5*"));
+
+    let response = admin_command(&h, in_topic(message(91, ADMIN, "/topic_vote_count 0"), 7)).await;
+    assert_eq!(
+        response["text"],
+        "Polls in this topic now need the usual number of votes"
+    );
+    assert_eq!(
+        h.db.get_topic(CHAT_ID, 7)
+            .await
+            .unwrap()
+            .unwrap()
+            .minimum_vote_count,
+        0
+    );
+}
+
+#[tokio::test]
+async fn polls_can_be_turned_off_in_a_topic() {
+    let h = Harness::new().await;
+
+    let response = admin_command(&h, in_topic(message(90, ADMIN, "/topic off"), 7)).await;
+    assert_eq!(response["text"], "Polls in this topic are now disabled");
+
+    assert!(poll_in_topic(&h, 7).await.is_none());
+    assert!(h.db.get_polls().await.unwrap().is_empty());
+    assert!(poll_in_topic(&h, 8).await.is_some());
+
+    admin_command(&h, in_topic(message(91, ADMIN, "/topic on"), 7)).await;
+    assert!(poll_in_topic(&h, 7).await.is_some());
+}
+
+#[tokio::test]
+async fn topic_settings_are_sent_in_a_topic() {
+    let h = Harness::new().await;
+
+    let response = admin_command(&h, message(90, ADMIN, "/topic off")).await;
+
+    assert_eq!(
+        response["text"],
+        "Send this in a forum topic to change the settings of that topic"
+    );
+    assert!(h.db.get_topic(CHAT_ID, 0).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn restored_messages_return_to_their_topic() {
+    let h = Harness::new().await;
+    h.set_vote_count(1).await;
+    h.db.set_chat_undo_window(CHAT_ID, 600).await.unwrap();
+    h.api.set_member(ADMIN, admin(user(ADMIN)));
+
+    poll_in_topic(&h, 7).await.unwrap();
+    let poll = last_sent(&h);
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+    h.api.clear();
+
+    h.send(callback_update(ADMIN, poll, "restore")).await;
+
+    let sent = h.api.calls_to("sendMessage");
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0]["message_thread_id"], 7);
+    assert_eq!(sent[1]["reply_to_message_id"], 10002);
+}
+
+#[tokio::test]
+async fn database_keeps_topic_settings() {
+    let db = Database::new("sqlite::memory:", 1).await;
+
+    assert!(db.get_topic(CHAT_ID, 7).await.unwrap().is_none());
+    db.set_topic_enabled(CHAT_ID, 7, false).await.unwrap();
+    db.set_topic_votes(CHAT_ID, 7, 4).await.unwrap();
+
+    let topic = db.get_topic(CHAT_ID, 7).await.unwrap().unwrap();
+    assert!(!topic.enabled);
+    assert_eq!(topic.minimum_vote_count, 4);
+
+    db.create_poll(CHAT_ID, 100, 5, TARGET, 3, None)
+        .await
+        .unwrap();
+    let poll = db.get_poll(CHAT_ID, 100).await.unwrap().unwrap();
+    assert_eq!(poll.thread_id, None);
+    db.set_poll_thread(poll.id, 7).await.unwrap();
+    let poll = db.get_poll(CHAT_ID, 100).await.unwrap().unwrap();
+    assert_eq!(poll.thread_id, Some(7));
+
+    db.remove_chat(CHAT_ID).await.unwrap();
+    assert!(db.get_topic(CHAT_ID, 7).await.unwrap().is_none());
+}
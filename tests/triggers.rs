@@ -0,0 +1,103 @@
+mod common;
+
+use common::*;
+use serde_json::json;
+
+const TARGET: i64 = 20;
+
+fn polls_sent(h: &Harness) -> usize {
+    h.api.calls_to("sendMessage").len()
+}
+
+#[tokio::test]
+async fn delete_command_creates_poll() {
+    let h = Harness::new().await;
+
+    h.send(message_update(reply(
+        6,
+        10,
+        "/delete",
+        message(5, TARGET, "spam"),
+    )))
+    .await;
+
+    assert_eq!(polls_sent(&h), 1);
+}
+
+#[tokio::test]
+async fn delete_command_addressed_to_another_bot_is_ignored() {
+    let h = Harness::new().await;
+
+    h.send(message_update(reply(
+        6,
+        10,
+        "/delete@some_other_bot",
+        message(5, TARGET, "spam"),
+    )))
+    .await;
+
+    assert_eq!(polls_sent(&h), 0);
+}
+
+#[tokio::test]
+async fn mention_in_caption_creates_poll() {
+    let h = Harness::new().await;
+    let caption = format!("look @{}", BOT_USERNAME);
+
+    let mut msg = reply(6, 10, "", message(5, TARGET, "spam"));
+    msg.as_object_mut().unwrap().remove("text");
+    msg["photo"] = json!([{ "file_id": "f", "file_unique_id": "u", "width": 1, "height": 1 }]);
+    msg["caption"] = json!(caption);
+    msg["caption_entities"] = json!([{ "type": "mention", "offset": 5, "length": 15 }]);
+
+    h.send(message_update(msg)).await;
+
+    assert_eq!(polls_sent(&h), 1);
+}
+
+#[tokio::test]
+async fn mention_entity_followed_by_punctuation_creates_poll() {
+    let h = Harness::new().await;
+
+    let mut msg = reply(
+        6,
+        10,
+        &format!("@{}, please", BOT_USERNAME),
+        message(5, TARGET, "spam"),
+    );
+    msg["entities"] = json!([{ "type": "mention", "offset": 0, "length": 15 }]);
+
+    h.send(message_update(msg)).await;
+
+    assert_eq!(polls_sent(&h), 1);
+}
+
+#[tokio::test]
+async fn disabled_trigger_is_ignored() {
+    let h = Harness::new().await;
+    h.api.set_member(10, admin(user(10)));
+
+    h.send(message_update(message(7, 10, "/trigger command off")))
+        .await;
+    h.api.clear();
+
+    h.send(message_update(reply(
+        8,
+        10,
+        "/delete",
+        message(5, TARGET, "spam"),
+    )))
+    .await;
+
+    assert_eq!(polls_sent(&h), 0);
+
+    h.send(message_update(reply(
+        9,
+        10,
+        &format!("@{}", BOT_USERNAME),
+        message(5, TARGET, "spam"),
+    )))
+    .await;
+
+    assert_eq!(polls_sent(&h), 1);
+}
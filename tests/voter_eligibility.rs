@@ -0,0 +1,157 @@
+mod common;
+
+use common::*;
+use serde_json::{json, Value};
+
+const TARGET: i64 = 20;
+
+async fn start_poll(h: &Harness, count: i64) -> Value {
+    h.set_vote_count(count).await;
+    h.send(message_update(reply(
+        6,
+        10,
+        "/delete",
+        message(5, TARGET, "spam"),
+    )))
+    .await;
+
+    let poll = last_sent(h);
+    h.api.clear();
+    poll
+}
+
+fn answer(h: &Harness) -> Value {
+    h.api.calls_to("answerCallbackQuery").pop().unwrap()
+}
+
+async fn yes_count(h: &Harness, poll: &Value) -> i64 {
+    h.db.get_poll(CHAT_ID, poll["message_id"].as_i64().unwrap() as i32)
+        .await
+        .unwrap()
+        .unwrap()
+        .vote_count_yes
+}
+
+#[tokio::test]
+async fn target_can_not_vote() {
+    let h = Harness::new().await;
+    let poll = start_poll(&h, 1).await;
+
+    h.send(callback_update(TARGET, poll.clone(), "vote_no"))
+        .await;
+
+    let answer = answer(&h);
+    assert_eq!(answer["show_alert"], true);
+    assert_eq!(
+        answer["text"],
+        "You can not vote on a poll about your own message"
+    );
+    assert!(h.api.calls_to("deleteMessage").is_empty());
+}
+
+#[tokio::test]
+async fn voter_can_vote_only_once() {
+    let h = Harness::new().await;
+    let poll = start_poll(&h, 3).await;
+
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+
+    assert_eq!(yes_count(&h, &poll).await, 1);
+    assert_eq!(h.api.calls_to("answerCallbackQuery").len(), 1);
+}
+
+#[tokio::test]
+async fn bots_are_excluded_when_enabled() {
+    let h = Harness::new().await;
+    let poll = start_poll(&h, 3).await;
+    h.db.set_chat_exclude_bots(CHAT_ID, true).await.unwrap();
+
+    let mut update = callback_update(31, poll.clone(), "vote_yes");
+    update["callback_query"]["from"]["is_bot"] = json!(true);
+    h.send(update).await;
+
+    assert_eq!(answer(&h)["text"], "Bots can not vote in this chat");
+    assert_eq!(yes_count(&h, &poll).await, 0);
+}
+
+#[tokio::test]
+async fn minimum_messages_are_required() {
+    let h = Harness::new().await;
+    let poll = start_poll(&h, 3).await;
+    h.db.set_chat_voter_min_messages(CHAT_ID, 2).await.unwrap();
+
+    h.send(message_update(message(40, 31, "hello"))).await;
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+
+    assert_eq!(
+        answer(&h)["text"],
+        "You have not sent enough messages in this chat to vote"
+    );
+
+    h.send(message_update(message(41, 31, "hello again"))).await;
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+
+    assert_eq!(yes_count(&h, &poll).await, 1);
+}
+
+#[tokio::test]
+async fn minimum_age_is_required() {
+    let h = Harness::new().await;
+    let poll = start_poll(&h, 3).await;
+    h.db.set_chat_voter_min_age(CHAT_ID, 3600).await.unwrap();
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let mut newcomer = message(40, 31, "hello");
+    newcomer["date"] = json!(now);
+    h.send(message_update(newcomer)).await;
+    h.send(message_update(message(41, 32, "hello"))).await;
+
+    h.send(callback_update(31, poll.clone(), "vote_yes")).await;
+    assert_eq!(
+        answer(&h)["text"],
+        "You joined this chat too recently to vote"
+    );
+
+    h.send(callback_update(33, poll.clone(), "vote_yes")).await;
+    assert_eq!(
+        answer(&h)["text"],
+        "You joined this chat too recently to vote"
+    );
+
+    h.send(callback_update(32, poll.clone(), "vote_yes")).await;
+    assert_eq!(yes_count(&h, &poll).await, 1);
+}
+
+#[tokio::test]
+async fn admin_sets_voter_rules() {
+    let h = Harness::new().await;
+    h.set_vote_count(3).await;
+    h.api.set_member(10, admin(user(10)));
+
+    h.send(message_update(message(7, 10, "/voter_min_age 24")))
+        .await;
+    h.send(message_update(message(8, 10, "/exclude_bots on")))
+        .await;
+
+    let chat = h.db.get_chat(CHAT_ID).await.unwrap().unwrap();
+    assert_eq!(chat.voter_min_age, 24 * 3600);
+    assert!(chat.exclude_bots);
+
+    h.send(message_update(message(
+        9,
+        10,
+        "/voter_min_age 9999999999999999",
+    )))
+    .await;
+
+    assert!(last_sent(&h)["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Usage: /voter_min_age"));
+    let chat = h.db.get_chat(CHAT_ID).await.unwrap().unwrap();
+    assert_eq!(chat.voter_min_age, 24 * 3600);
+}